@@ -1,8 +1,9 @@
 #![warn(unused_extern_crates)]
 
 use std::error::Error;
-use std::io::{self, Write};
+use std::io;
 use std::path::PathBuf;
+use std::process;
 use std::thread;
 
 use log::*;
@@ -12,13 +13,10 @@ use log4rs::{
     encode::pattern::PatternEncoder,
 };
 use structopt::StructOpt;
-use termion::{
-    cursor,
-    event::{Event as TermionEvent, Key},
-    input::TermRead,
-};
 
-use seventeen::{Core, Editor, Notification};
+use seventeen::{
+    ColorDepth, Core, Editor, Event, Events, Notification, Tcp, Transport, Tty, UnixSocket,
+};
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -30,10 +28,23 @@ struct Opt {
     #[structopt(long = "core", parse(from_os_str), default_value = "xi-core")]
     core: PathBuf,
 
+    /// Connect to a running editor core instead of spawning one
+    ///
+    /// The address is either the path of a Unix domain socket, or a host and port for TCP, such as
+    /// `localhost:8000`. Addresses containing a `/` are always socket paths.
+    #[structopt(long = "core-socket")]
+    core_socket: Option<String>,
+
     /// Write log messages to this file
     #[structopt(long = "log-file", parse(from_os_str), default_value = "/tmp/seventeen.log")]
     log_file: PathBuf,
 
+    /// The number of colors supported by the terminal: 8, 16, 256 or truecolor
+    ///
+    /// By default, this is detected from the environment.
+    #[structopt(long = "colors")]
+    colors: Option<ColorDepth>,
+
     /// Log output verbosity
     ///
     /// By default, only errors are logged. Each occurrence of this flag raises the log level: `-v`
@@ -43,36 +54,41 @@ struct Opt {
 }
 
 fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
-    let (input_tx, input_rx) = channel::unbounded::<Key>();
+    let (input_tx, input_rx) = channel::unbounded::<Event>();
     let (notification_tx, notification_rx) = channel::unbounded::<Notification>();
 
+    let tty = Tty::open()?;
+    let input_pause = tty.pause_handle();
     thread::spawn(move || -> io::Result<()> {
-        let tty = termion::get_tty()?;
-
-        for event in tty.events() {
-            match event? {
-                TermionEvent::Key(key) => input_tx.send(key),
-                ev @ TermionEvent::Mouse(_) | ev @ TermionEvent::Unsupported(_) => {
-                    warn!("unsupported event encountered: {:?}", ev);
-                }
-            };
+        for event in Events::new(tty) {
+            input_tx.send(event?);
         }
 
         Ok(())
     });
 
-    let core = Core::spawn(opt.core, notification_tx)?;
-    let editor = Editor::new(core, opt.file);
+    let core = match opt.core_socket {
+        Some(address) => Core::connect(socket_transport(address), notification_tx)?,
+        None => Core::spawn(opt.core, notification_tx)?,
+    };
+    let colors = opt.colors.unwrap_or_else(ColorDepth::detect);
+    let editor = Editor::new(core, input_pause, opt.file, colors);
 
+    // The terminal is restored when the editor is dropped at the end of `run`.
     editor.run(input_rx, notification_rx);
 
-    // We hid the cursor earlier, so we have to restore it before we exit.
-    print!("{}", cursor::Show);
-    io::stdout().flush()?;
-
     Ok(())
 }
 
+/// Returns the transport for a `--core-socket` address.
+fn socket_transport(address: String) -> Box<dyn Transport> {
+    if !address.contains('/') && address.contains(':') {
+        Box::new(Tcp::new(address))
+    } else {
+        Box::new(UnixSocket::new(address))
+    }
+}
+
 fn init_logging(opt: &Opt) -> Result<(), Box<dyn Error>> {
     let file_appender = FileAppender::builder()
         .encoder(Box::new(PatternEncoder::new("{l} {M} - {m}\n")))
@@ -99,8 +115,17 @@ fn init_logging(opt: &Opt) -> Result<(), Box<dyn Error>> {
 fn main() {
     let opt = Opt::from_args();
 
+    // The screen restores the terminal before this hook logs a panic.
     log_panics::init();
-    init_logging(&opt).unwrap();
-
-    run(opt).unwrap();
+    if let Err(e) = init_logging(&opt) {
+        eprintln!("could not initialize logging: {}", e);
+        process::exit(1);
+    }
+
+    // The editor has been dropped by the time an error is returned, so the terminal is restored.
+    if let Err(e) = run(opt) {
+        error!("{}", e);
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
 }
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, prelude::*, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::{Child, ChildStdin, Command, Stdio};
+use std::process::ExitStatus;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -13,6 +14,14 @@ use serde_json::{self, Value};
 
 use crate::protocol::*;
 
+#[cfg(test)]
+mod fake;
+mod transport;
+
+#[cfg(test)]
+pub use self::fake::FakeCore;
+pub use self::transport::*;
+
 #[derive(Debug, Fail)]
 pub enum CoreError {
     #[fail(display = "i/o error: {}", _0)]
@@ -23,82 +32,80 @@ pub enum CoreError {
 
     #[fail(display = "core returned an error value: {}", _0)]
     BadResponse(Value),
+
+    #[fail(display = "core sent a response without exactly one of a result or an error")]
+    InvalidResponse,
+
+    #[fail(display = "core exited before responding")]
+    Exited,
+}
+
+impl From<io::Error> for CoreError {
+    fn from(e: io::Error) -> Self {
+        CoreError::Io(e)
+    }
 }
 
-#[derive(Debug)]
+type RequestMap = Arc<Mutex<HashMap<u64, Complete<Response>>>>;
+
 pub struct Core {
-    stdin: Option<ChildStdin>,
-    process: Child,
-    request_map: Arc<Mutex<HashMap<u64, Complete<Response>>>>,
+    writer: Option<Box<dyn Write + Send>>,
+    transport: Box<dyn Transport>,
+    request_map: RequestMap,
     next_id: u64,
+
+    /// The channel for the core's notifications, which is needed to reconnect.
+    event_tx: Sender<Notification>,
 }
 
 impl Core {
+    /// Spawns the core as a child process.
     pub fn spawn(path: impl AsRef<Path>, event_tx: Sender<Notification>) -> io::Result<Self> {
-        info!("spawning core");
-
-        let mut core = Command::new(path.as_ref())
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let request_map = Arc::new(Mutex::new(HashMap::<u64, Complete<Response>>::new()));
-
-        let stdout = core.stdout.take().unwrap();
-        let response_map = request_map.clone();
-        thread::spawn(move || -> io::Result<()> {
-            let stdout = BufReader::new(stdout);
-            for line in stdout.lines() {
-                let line = line?;
-                trace!("<- {}", line);
-                let message = match serde_json::from_str(&line) {
-                    Ok(message) => message,
-                    Err(err) => {
-                        error!("could not deserialize message from core, skipping: {}", err);
-                        continue;
-                    }
-                };
-                match message {
-                    Message::Notification(not) => {
-                        event_tx.send(not);
-                    }
-                    Message::Request { id, req } => {
-                        error!(
-                            "xi-core is not known to send requests, but got request ({:?}, {:?})",
-                            id, req
-                        );
-                    }
-                    Message::Response { id, res } => {
-                        let completer = response_map
-                            .lock()
-                            .unwrap()
-                            .remove(&id)
-                            .expect("got response without a request");
-                        completer.send(res).unwrap();
-                    }
-                }
-            }
+        Self::connect(Process::new(path.as_ref()), event_tx)
+    }
 
-            Ok(())
-        });
+    /// Connects to the core over a transport.
+    ///
+    /// When the core exits, or disconnects, `Notification::CoreExited` is sent after its other
+    /// notifications.
+    pub fn connect(
+        transport: impl Transport + 'static,
+        event_tx: Sender<Notification>,
+    ) -> io::Result<Self> {
+        let mut core = Self {
+            writer: None,
+            transport: Box::new(transport),
+            request_map: RequestMap::default(),
+            next_id: 0,
+            event_tx,
+        };
+        core.open()?;
+        Ok(core)
+    }
 
-        let stderr = core.stderr.take().unwrap();
-        thread::spawn(move || -> io::Result<()> {
-            let stderr = BufReader::new(stderr);
-            for line in stderr.lines() {
-                info!("xi-core: {}", line?);
-            }
+    /// Reconnects to the core after it exited, spawning it again if it's a child process. The new
+    /// core has no views.
+    pub fn restart(&mut self) -> io::Result<()> {
+        self.close();
+        self.open()
+    }
 
-            Ok(())
-        });
+    /// Returns the exit status of the core, if it has exited and it's a child process.
+    pub fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.transport.exit_status()
+    }
 
-        Ok(Self {
-            stdin: core.stdin.take(),
-            process: core,
-            request_map,
-            next_id: 0,
-        })
+    fn open(&mut self) -> io::Result<()> {
+        let (reader, writer) = self.transport.connect()?;
+        spawn_reader(reader, self.event_tx.clone(), self.request_map.clone());
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        // xi-core closes gracefully when its input is closed.
+        self.writer.take();
+        self.transport.close();
     }
 
     pub fn client_started<P: Into<PathBuf>>(&mut self, config_dir: Option<P>) -> io::Result<()> {
@@ -122,6 +129,13 @@ impl Core {
         })
     }
 
+    pub fn select_all(&mut self, view_id: ViewId) -> io::Result<()> {
+        self.notify(&Notification::Edit {
+            method: EditMethod::SelectAll,
+            view_id,
+        })
+    }
+
     pub fn delete_backward(&mut self, view_id: ViewId) -> io::Result<()> {
         self.notify(&Notification::Edit {
             method: EditMethod::DeleteBackward,
@@ -129,6 +143,13 @@ impl Core {
         })
     }
 
+    pub fn delete_forward(&mut self, view_id: ViewId) -> io::Result<()> {
+        self.notify(&Notification::Edit {
+            method: EditMethod::DeleteForward,
+            view_id,
+        })
+    }
+
     pub fn move_word_left(&mut self, view_id: ViewId) -> io::Result<()> {
         self.notify(&Notification::Edit {
             method: EditMethod::MoveWordLeft,
@@ -171,12 +192,45 @@ impl Core {
         })
     }
 
+    /// Moves the cursor to a position in the buffer.
+    pub fn gesture(&mut self, view_id: ViewId, line: usize, col: usize) -> io::Result<()> {
+        self.notify(&Notification::Edit {
+            method: EditMethod::Gesture {
+                line,
+                col,
+                ty: GestureType::PointSelect,
+            },
+            view_id,
+        })
+    }
+
+    /// Extends the selection to a position in the buffer.
+    pub fn drag(&mut self, view_id: ViewId, line: usize, col: usize) -> io::Result<()> {
+        self.notify(&Notification::Edit {
+            method: EditMethod::Drag(line, col, 0),
+            view_id,
+        })
+    }
+
     pub fn set_theme(&mut self, theme: &str) -> io::Result<()> {
         self.notify(&Notification::SetTheme {
             theme_name: String::from(theme),
         })
     }
 
+    /// Changes the configuration of the views in a domain.
+    pub fn modify_user_config(
+        &mut self,
+        domain: ConfigDomain,
+        changes: ConfigChanges,
+    ) -> io::Result<()> {
+        self.notify(&Notification::ModifyUserConfig { domain, changes })
+    }
+
+    pub fn close_view(&mut self, view_id: ViewId) -> io::Result<()> {
+        self.notify(&Notification::CloseView { view_id })
+    }
+
     pub fn new_view<P: Into<PathBuf>>(
         &mut self,
         file_path: Option<P>,
@@ -186,14 +240,38 @@ impl Core {
         }).and_then(|res: Response| res.into_result())
     }
 
+    /// Returns the selected text, or `None` if nothing is selected.
+    pub fn copy(
+        &mut self,
+        view_id: ViewId,
+    ) -> impl Future<Item = Option<String>, Error = CoreError> {
+        self.request(Request::Edit {
+            method: EditRequest::Copy,
+            view_id,
+        }).and_then(|res: Response| res.into_result())
+    }
+
     fn notify(&mut self, notification: &Notification) -> io::Result<()> {
         let json = serde_json::to_string(&notification).unwrap();
-        trace!("-> {}", json);
-        let stdin = self.stdin.as_mut().unwrap();
-        writeln!(stdin, "{}", json)
+        match self.write(&json) {
+            // The editor is told when the core exits or disconnects, so notifications sent in the
+            // meantime are dropped.
+            Err(ref e)
+                if e.kind() == io::ErrorKind::BrokenPipe
+                    || e.kind() == io::ErrorKind::ConnectionReset
+                    || e.kind() == io::ErrorKind::NotConnected =>
+            {
+                warn!("core has exited, dropping notification");
+                Ok(())
+            }
+            result => result,
+        }
     }
 
     fn request(&mut self, req: Request) -> impl Future<Item = Response, Error = CoreError> {
+        // If the request can't be sent, the error is returned instead of the response.
+        let mut send_error = None;
+
         let (c, p) = futures::oneshot::<Response>();
 
         let id = self.next_id;
@@ -209,28 +287,273 @@ impl Core {
             debug!("next_id: {}", self.next_id);
         }
 
-        // FIXME: Potential I/O error here
-        self.send_to_core(&Message::Request { id, req }).unwrap();
+        if let Err(e) = self.send_to_core(&Message::Request { id, req }) {
+            error!("could not send request: {}", e);
+            self.request_map.lock().unwrap().remove(&id);
+            send_error = Some(CoreError::Io(e));
+        }
 
-        p.map_err(|e| panic!("{}", e))
+        // The request is canceled if the core exits before responding.
+        p.map_err(move |_| send_error.unwrap_or(CoreError::Exited))
     }
 
     fn send_to_core(&mut self, message: &Message) -> io::Result<()> {
         let json = serde_json::to_string(message).unwrap();
+        self.write(&json)
+    }
+
+    fn write(&mut self, json: &str) -> io::Result<()> {
         trace!("-> {}", json);
-        let stdin = self.stdin.as_mut().unwrap();
-        writeln!(stdin, "{}", json)
+        match &mut self.writer {
+            Some(writer) => writeln!(writer, "{}", json),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "not connected to core",
+            )),
+        }
+    }
+}
+
+/// Spawns a thread that handles the core's messages.
+fn spawn_reader(
+    reader: impl Read + Send + 'static,
+    event_tx: Sender<Notification>,
+    response_map: RequestMap,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+
+        // Lines are read as bytes, so that a line that isn't UTF-8 is reported like any other
+        // malformed message instead of ending the connection.
+        let mut line = vec![];
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(e) => {
+                    error!("could not read from core: {}", e);
+                    break;
+                }
+            }
+            trace!("<- {}", String::from_utf8_lossy(&line).trim_end());
+            let message = match serde_json::from_slice(&line) {
+                Ok(message) => message,
+                Err(err) => {
+                    error!("could not deserialize message from core, skipping: {}", err);
+                    event_tx.send(Notification::InvalidMessage(err.to_string()));
+                    continue;
+                }
+            };
+            match message {
+                Message::Notification(not) => {
+                    event_tx.send(not);
+                }
+                Message::Request { id, req } => {
+                    error!(
+                        "xi-core is not known to send requests, but got request ({:?}, {:?})",
+                        id, req
+                    );
+                }
+                Message::Response { id, res } => {
+                    let completer = response_map.lock().unwrap().remove(&id);
+                    match completer {
+                        // The caller may have stopped waiting for the response.
+                        Some(completer) => drop(completer.send(res)),
+                        None => {
+                            let message = format!("response to unknown request {}", id);
+                            error!("{}", message);
+                            event_tx.send(Notification::InvalidMessage(message));
+                        }
+                    }
+                }
+            }
+        }
+
+        // The core won't respond to the pending requests. Dropping them cancels them.
+        response_map.lock().unwrap().clear();
+        event_tx.send(Notification::CoreExited);
+    });
+}
+
+impl fmt::Debug for Core {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Core")
+            .field("transport", &self.transport)
+            .field("next_id", &self.next_id)
+            .finish()
     }
 }
 
 impl Drop for Core {
     fn drop(&mut self) {
-        // xi-core closes gracefully when its stdin is closed.
-        self.stdin.take().unwrap();
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::{self, prelude::*, BufReader, Lines};
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::process;
+    use std::thread;
+
+    use channel::{self, Receiver};
+    use futures::Future;
+
+    use super::{in_process, Core, CoreError, PipeReader, PipeWriter, Tcp, Transport, UnixSocket};
+    use crate::protocol::{Notification, ViewId};
+
+    /// Connects to a core in the same process, returning the lines of the frontend's messages and
+    /// the stream that the core's messages are written to.
+    fn connect() -> (
+        Core,
+        Receiver<Notification>,
+        Lines<BufReader<PipeReader>>,
+        PipeWriter,
+    ) {
+        let (transport, reader, writer) = in_process();
+        let (tx, rx) = channel::bounded(16);
+        let core = Core::connect(transport, tx).unwrap();
+        (core, rx, BufReader::new(reader).lines(), writer)
+    }
+
+    #[test]
+    fn error_responses() {
+        let (mut core, _rx, mut requests, mut out) = connect();
+
+        let response = core.new_view(None::<PathBuf>);
+        requests.next().unwrap().unwrap();
+        writeln!(out, r#"{{"id":0,"error":"no"}}"#).unwrap();
+        match response.wait() {
+            Err(CoreError::BadResponse(error)) => assert_eq!(error, "no"),
+            result => panic!("expected a bad response, got {:?}", result),
+        }
+
+        let response = core.new_view(None::<PathBuf>);
+        requests.next().unwrap().unwrap();
+        writeln!(out, r#"{{"id":1}}"#).unwrap();
+        match response.wait() {
+            Err(CoreError::InvalidResponse) => (),
+            result => panic!("expected an invalid response, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn exited() {
+        let (mut core, rx, mut requests, out) = connect();
+        let response = core.new_view(None::<PathBuf>);
+        requests.next().unwrap().unwrap();
+
+        drop(out);
+        match response.wait() {
+            Err(CoreError::Exited) => (),
+            result => panic!("expected the core to exit, got {:?}", result),
+        }
+        assert_eq!(rx.recv(), Some(Notification::CoreExited));
+    }
 
-        match self.process.wait() {
-            Ok(exit_status) => info!("core exited with {}", exit_status),
-            Err(e) => error!("core exited unexpectedly: {}", e),
+    #[test]
+    fn invalid_messages() {
+        let (_core, rx, _requests, mut out) = connect();
+        let expect_invalid = |expected: Option<&str>| match rx.recv() {
+            Some(Notification::InvalidMessage(message)) => {
+                if let Some(expected) = expected {
+                    assert_eq!(message, expected);
+                }
+            }
+            notification => panic!("expected an invalid message, got {:?}", notification),
+        };
+
+        writeln!(out, r#"{{"id":7,"result":"view-id-1"}}"#).unwrap();
+        expect_invalid(Some("response to unknown request 7"));
+
+        out.write_all(b"not json\n").unwrap();
+        expect_invalid(None);
+
+        out.write_all(b"{\"method\":\"\xff\"}\n").unwrap();
+        expect_invalid(None);
+
+        // Messages after the malformed ones are still handled.
+        let scroll_to = Notification::ScrollTo {
+            view_id: ViewId(String::from("view-id-1")),
+            line: 1,
+            col: 0,
+        };
+        writeln!(out, "{}", serde_json::to_string(&scroll_to).unwrap()).unwrap();
+        assert_eq!(rx.recv(), Some(scroll_to));
+
+        // The core exits in the middle of writing a message.
+        out.write_all(br#"{"method":"scroll_to","par"#).unwrap();
+        drop(out);
+        expect_invalid(None);
+        assert_eq!(rx.recv(), Some(Notification::CoreExited));
+    }
+
+    /// Responds to a `new_view` request like the core, then reads the rest of the input.
+    fn serve_new_view(reader: impl Read, mut writer: impl Write) {
+        let mut lines = BufReader::new(reader).lines();
+        let request = lines.next().unwrap().unwrap();
+        assert!(
+            request.contains("new_view"),
+            "unexpected request: {}",
+            request
+        );
+        writeln!(writer, r#"{{"id":0,"result":"view-id-1"}}"#).unwrap();
+
+        // The frontend closes its side of the connection when it's dropped.
+        for line in lines {
+            line.unwrap();
         }
     }
+
+    fn open_view(transport: impl Transport + 'static) {
+        let (tx, _rx) = channel::bounded(16);
+        let mut core = Core::connect(transport, tx).unwrap();
+        let view_id = core.new_view(None::<PathBuf>).wait().unwrap();
+        assert_eq!(view_id, ViewId(String::from("view-id-1")));
+    }
+
+    #[test]
+    fn transports() {
+        let (transport, reader, writer) = in_process();
+        let core = thread::spawn(move || serve_new_view(reader, writer));
+        open_view(transport);
+        core.join().unwrap();
+
+        let path = env::temp_dir().join(format!("sev-socket-{}", process::id()));
+        let listener = UnixListener::bind(&path).unwrap();
+        let core = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_new_view(&stream, &stream);
+        });
+        open_view(UnixSocket::new(&path));
+        core.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let core = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_new_view(&stream, &stream);
+        });
+        open_view(Tcp::new(address.to_string()));
+        core.join().unwrap();
+    }
+
+    #[test]
+    fn reconnect_in_process() {
+        let (transport, reader, writer) = in_process();
+        let (tx, rx) = channel::bounded(16);
+        let mut core = Core::connect(transport, tx).unwrap();
+
+        drop((reader, writer));
+        assert_eq!(rx.recv(), Some(Notification::CoreExited));
+        let err = core.restart().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
 }
@@ -0,0 +1,258 @@
+//! A fake core, for testing the editor without xi-core.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, prelude::*};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use channel::Sender;
+use serde_json::{self, json, json_internal};
+
+use super::{pipe, Core, PipeWriter, Streams, Transport};
+use crate::protocol::*;
+
+/// A fake core that runs in the same process.
+///
+/// Messages are handled as soon as they are written, so they have been recorded by the time the
+/// method of `Core` that sent them returns. New views display the text of the fake's files, and
+/// notifications can be sent to the editor at any time.
+#[derive(Debug, Clone, Default)]
+pub struct FakeCore(Arc<Mutex<State>>);
+
+#[derive(Debug, Default)]
+struct State {
+    files: HashMap<PathBuf, String>,
+
+    /// The files that can't be opened.
+    unreadable: HashSet<PathBuf>,
+
+    /// The number of views that have been opened.
+    views: usize,
+
+    /// Every message that has been received.
+    received: Vec<Message>,
+
+    /// The stream that messages to the editor are written to, once it has connected.
+    out: Option<PipeWriter>,
+}
+
+impl FakeCore {
+    /// Adds a file that can be opened.
+    pub fn file(self, path: impl Into<PathBuf>, text: &str) -> Self {
+        self.state().files.insert(path.into(), String::from(text));
+        self
+    }
+
+    /// Makes a file fail to open, as if its permissions were changed.
+    pub fn make_unreadable(&self, path: impl Into<PathBuf>) {
+        self.state().unreadable.insert(path.into());
+    }
+
+    /// Connects a `Core` to the fake.
+    pub fn connect(&self, event_tx: Sender<Notification>) -> Core {
+        Core::connect(self.clone(), event_tx).unwrap()
+    }
+
+    /// Returns the messages that have been received since the last call.
+    pub fn received(&self) -> Vec<Message> {
+        mem::replace(&mut self.state().received, Vec::new())
+    }
+
+    /// Returns the edits that have been received since the last call, ignoring other messages.
+    pub fn edits(&self) -> Vec<EditMethod> {
+        self.received()
+            .into_iter()
+            .filter_map(|message| match message {
+                Message::Notification(Notification::Edit { method, .. }) => Some(method),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Sends a notification to the editor.
+    pub fn notify(&self, notification: &Notification) {
+        self.state()
+            .send(&serde_json::to_value(notification).unwrap());
+    }
+
+    /// Replaces the text of a view, as if it was edited, placing the cursor at the start of a
+    /// line.
+    pub fn update(&self, view_id: &ViewId, text: &str, cursor_line: usize) {
+        self.notify(&update(view_id, text, cursor_line, false));
+    }
+
+    /// Sends a line that isn't necessarily a valid message.
+    pub fn send_line(&self, line: &str) {
+        let mut state = self.state();
+        let out = state.out.as_mut().expect("fake core is not connected");
+        writeln!(out, "{}", line).unwrap();
+    }
+
+    /// Exits, which ends the stream that the editor reads from.
+    pub fn exit(&self) {
+        self.state().out.take();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl Transport for FakeCore {
+    fn connect(&mut self) -> io::Result<Streams> {
+        let (reader, writer) = pipe();
+        let mut state = self.state();
+        state.out = Some(writer);
+
+        // Like a restarted xi-core, the IDs of new views start from the first ID again.
+        state.views = 0;
+        drop(state);
+
+        let receiver = Receiver {
+            core: self.clone(),
+            buf: vec![],
+        };
+        Ok((Box::new(reader), Box::new(receiver)))
+    }
+
+    fn close(&mut self) {
+        self.exit();
+    }
+}
+
+impl State {
+    fn send(&mut self, json: &serde_json::Value) {
+        let out = self.out.as_mut().expect("fake core is not connected");
+        writeln!(out, "{}", json).unwrap();
+    }
+
+    fn handle(&mut self, message: Message) {
+        match &message {
+            Message::Request {
+                id,
+                req: Request::NewView { file_path },
+            } => {
+                // Like xi-core, a view's ID is used up even if its file can't be opened.
+                self.views += 1;
+                let view_id = ViewId(format!("view-id-{}", self.views));
+                match file_path {
+                    Some(path) if self.unreadable.contains(path) => {
+                        let error = format!("could not open {}", path.display());
+                        self.send(&json!({ "id": id, "error": error }));
+                    }
+                    _ => self.new_view(*id, view_id, file_path.as_ref()),
+                }
+            }
+            Message::Request { id, .. } => self.send(&json!({ "id": id, "result": null })),
+            _ => (),
+        }
+
+        self.received.push(message);
+    }
+
+    /// Opens a view, sending its initial notifications and the response to the request.
+    fn new_view(&mut self, id: u64, view_id: ViewId, file_path: Option<&PathBuf>) {
+        let text = file_path
+            .and_then(|path| self.files.get(path))
+            .cloned()
+            .unwrap_or_default();
+
+        // xi-core responds before it sends the view's notifications. The editor handles
+        // notifications after the request returns, so sending them first means that they
+        // are ready when it does.
+        let notifications = vec![
+            Notification::ConfigChanged {
+                view_id: view_id.clone(),
+                changes: ConfigChanges {
+                    tab_size: Some(4),
+                    translate_tabs_to_spaces: Some(false),
+                    wrap_width: Some(0),
+                    ..Default::default()
+                },
+            },
+            Notification::DefStyle {
+                id: 2,
+                fg_color: Some(0xff00_00ff),
+                bg_color: None,
+                weight: None,
+                underline: None,
+                italic: Some(true),
+            },
+            update(&view_id, &text, 0, true),
+            Notification::ScrollTo {
+                view_id: view_id.clone(),
+                line: 0,
+                col: 0,
+            },
+        ];
+        for notification in &notifications {
+            self.send(&serde_json::to_value(notification).unwrap());
+        }
+        self.send(&json!({ "id": id, "result": view_id }));
+    }
+}
+
+/// Handles messages written by the editor.
+struct Receiver {
+    core: FakeCore,
+
+    /// The bytes of a message that has been partially written.
+    buf: Vec<u8>,
+}
+
+impl Write for Receiver {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        while let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            let message = serde_json::from_slice(&line).expect("invalid message from editor");
+            self.core.state().handle(message);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns an update that replaces the text of a view, with the cursor at the start of a line.
+fn update(view_id: &ViewId, text: &str, cursor_line: usize, pristine: bool) -> Notification {
+    let mut pieces: Vec<_> = text.split('\n').collect();
+    let last = pieces.pop().unwrap();
+    let mut texts: Vec<_> = pieces.iter().map(|line| format!("{}\n", line)).collect();
+
+    // The text after the last newline is a line without one, unless it's empty. Like xi-core, an
+    // empty buffer has a single empty line.
+    if !last.is_empty() || texts.is_empty() {
+        texts.push(String::from(last));
+    }
+
+    let lines: Vec<_> = texts
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| Line {
+            text: Some(text),
+            cursor: if i == cursor_line {
+                Some(vec![0])
+            } else {
+                None
+            },
+            styles: None,
+        })
+        .collect();
+
+    Notification::Update {
+        view_id: view_id.clone(),
+        update: Update {
+            rev: None,
+            ops: vec![Op {
+                op: OpKind::Ins,
+                n: lines.len() as u64,
+                lines: Some(lines),
+            }],
+            pristine,
+        },
+    }
+}
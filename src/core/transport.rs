@@ -0,0 +1,293 @@
+//! Connections to the core.
+
+use std::fmt;
+use std::io::{self, prelude::*, BufReader};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use log::*;
+
+/// The streams of a connection: one that the core's messages are read from, and one that the
+/// frontend's messages are written to.
+pub type Streams = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+/// A way of connecting to the core.
+///
+/// The core has exited, or disconnected, when the stream that it writes to ends.
+pub trait Transport: fmt::Debug {
+    /// Connects to the core. This is called again to reconnect after the connection is closed.
+    fn connect(&mut self) -> io::Result<Streams>;
+
+    /// Closes the connection after the stream that the frontend writes to has been dropped.
+    fn close(&mut self) {}
+
+    /// Returns the exit status of the core, if it has exited and it was spawned by the transport.
+    fn exit_status(&mut self) -> Option<ExitStatus> {
+        None
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn connect(&mut self) -> io::Result<Streams> {
+        (**self).connect()
+    }
+
+    fn close(&mut self) {
+        (**self).close()
+    }
+
+    fn exit_status(&mut self) -> Option<ExitStatus> {
+        (**self).exit_status()
+    }
+}
+
+/// Spawns the core as a child process that communicates over its standard input and output.
+#[derive(Debug)]
+pub struct Process {
+    path: PathBuf,
+    child: Option<Child>,
+}
+
+impl Process {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Process {
+            path: path.into(),
+            child: None,
+        }
+    }
+}
+
+impl Transport for Process {
+    fn connect(&mut self) -> io::Result<Streams> {
+        info!("spawning core");
+
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stderr = child.stderr.take().unwrap();
+        thread::spawn(move || -> io::Result<()> {
+            let stderr = BufReader::new(stderr);
+            for line in stderr.lines() {
+                info!("xi-core: {}", line?);
+            }
+
+            Ok(())
+        });
+
+        let stdout = child.stdout.take().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        self.child = Some(child);
+        Ok((Box::new(stdout), Box::new(stdin)))
+    }
+
+    /// Waits for the core to exit. xi-core exits gracefully when its standard input is closed.
+    fn close(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            match child.wait() {
+                Ok(exit_status) => info!("core exited with {}", exit_status),
+                Err(e) => error!("core exited unexpectedly: {}", e),
+            }
+        }
+    }
+
+    fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.child.as_mut()?.try_wait().ok()?
+    }
+}
+
+/// Connects to a running core over a Unix domain socket.
+#[derive(Debug)]
+pub struct UnixSocket {
+    path: PathBuf,
+    stream: Option<UnixStream>,
+}
+
+impl UnixSocket {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixSocket {
+            path: path.into(),
+            stream: None,
+        }
+    }
+}
+
+impl Transport for UnixSocket {
+    fn connect(&mut self) -> io::Result<Streams> {
+        info!("connecting to core at {}", self.path.display());
+
+        let stream = UnixStream::connect(&self.path)?;
+        let streams: Streams = (Box::new(stream.try_clone()?), Box::new(stream.try_clone()?));
+        self.stream = Some(stream);
+        Ok(streams)
+    }
+
+    /// Shuts down the socket for writing, which the core sees as the end of its input. The socket
+    /// is shared with the reading stream, so dropping the writing stream doesn't close it.
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            if let Err(e) = stream.shutdown(Shutdown::Write) {
+                warn!("could not shut down connection to core: {}", e);
+            }
+        }
+    }
+}
+
+/// Connects to a running core over TCP.
+#[derive(Debug)]
+pub struct Tcp {
+    address: String,
+    stream: Option<TcpStream>,
+}
+
+impl Tcp {
+    /// Creates a transport for a core listening on an address, such as `localhost:8000`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Tcp {
+            address: address.into(),
+            stream: None,
+        }
+    }
+}
+
+impl Transport for Tcp {
+    fn connect(&mut self) -> io::Result<Streams> {
+        info!("connecting to core at {}", self.address);
+
+        let stream = TcpStream::connect(self.address.as_str())?;
+
+        // Messages are small, and each one is sent as soon as it's written.
+        stream.set_nodelay(true)?;
+
+        let streams: Streams = (Box::new(stream.try_clone()?), Box::new(stream.try_clone()?));
+        self.stream = Some(stream);
+        Ok(streams)
+    }
+
+    /// Shuts down the socket for writing, which the core sees as the end of its input.
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            if let Err(e) = stream.shutdown(Shutdown::Write) {
+                warn!("could not shut down connection to core: {}", e);
+            }
+        }
+    }
+}
+
+/// Connects to a core running in the same process, such as a fake core in tests.
+///
+/// Created by [`in_process`]. It can only connect once.
+#[derive(Debug)]
+pub struct InProcess {
+    streams: Option<(PipeReader, PipeWriter)>,
+}
+
+/// Creates a transport to a core in the same process, along with the streams that the core reads
+/// the frontend's messages from and writes its own messages to.
+pub fn in_process() -> (InProcess, PipeReader, PipeWriter) {
+    let (core_reader, frontend_writer) = pipe();
+    let (frontend_reader, core_writer) = pipe();
+    let transport = InProcess {
+        streams: Some((frontend_reader, frontend_writer)),
+    };
+    (transport, core_reader, core_writer)
+}
+
+impl Transport for InProcess {
+    fn connect(&mut self) -> io::Result<Streams> {
+        match self.streams.take() {
+            Some((reader, writer)) => Ok((Box::new(reader), Box::new(writer))),
+            None => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "in-process core can't be reconnected",
+            )),
+        }
+    }
+}
+
+/// Creates a one-way, in-memory stream of bytes.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let (tx, rx) = mpsc::channel();
+    let reader = PipeReader {
+        rx,
+        buf: Vec::new(),
+    };
+    (reader, PipeWriter(tx))
+}
+
+/// The reading end of a [`pipe`]. Reading blocks until bytes are written, and the stream ends
+/// when the writing end is dropped.
+#[derive(Debug)]
+pub struct PipeReader {
+    rx: Receiver<Vec<u8>>,
+
+    /// Bytes that have been received but not read.
+    buf: Vec<u8>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.buf.is_empty() {
+            match self.rx.recv() {
+                Ok(bytes) => self.buf = bytes,
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        Ok(n)
+    }
+}
+
+/// The writing end of a [`pipe`]. Writing fails with `BrokenPipe` if the reading end was dropped.
+#[derive(Debug, Clone)]
+pub struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe reader was dropped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, prelude::*};
+
+    use super::pipe;
+
+    #[test]
+    fn pipe_streams() {
+        let (mut reader, mut writer) = pipe();
+        writer.write_all(b"hello, ").unwrap();
+        writer.write_all(b"world").unwrap();
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"hell");
+
+        drop(writer);
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "o, world");
+
+        let (reader, mut writer) = pipe();
+        drop(reader);
+        let err = writer.write_all(b"hello").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -1,30 +1,67 @@
-use std::collections::HashMap;
-use std::io;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, prelude::*};
 use std::path::PathBuf;
 
 use channel::{select, Receiver};
 use euclid::Size2D;
 use futures::Future;
 use log::*;
-use termion::event::Key;
+use termion::event::{Key, MouseButton, MouseEvent};
 use xdg::BaseDirectories;
 
-use crate::core::Core;
-use crate::protocol::{ConfigChanges, Notification, ThemeSettings, Update, ViewId};
-use crate::screen::{Color, Coordinate, Screen};
-use serde_json::Value;
+use crate::core::{Core, CoreError};
+use crate::input::{Event, PauseHandle};
+use crate::protocol::{ConfigChanges, ConfigDomain, Notification, ThemeSettings, Update, ViewId};
+use crate::screen::{Color, ColorDepth, Coordinate, CursorShape, Output, Screen, Terminal};
 
 mod command_line;
+mod config;
+mod display_line;
 mod layout;
 mod line_cache;
+mod list_chars;
+mod options;
+mod replace;
+mod settings;
+mod shell;
+mod status_line;
 pub(crate) mod styles;
+mod tabs;
 mod window;
+mod wrap;
 
-use self::command_line::CommandLine;
-use self::layout::Layout;
+use self::command_line::{Command, CommandLine, Range};
+use self::line_cache::LineCache;
+use self::options::{Argument, GlobalOptions, OptionError, Scope, Value, WindowOptions};
+use self::replace::{Replaced, Restore};
+use self::settings::Settings;
 use self::styles::{Style, Styles};
+use self::tabs::Tabs;
 use self::window::Window;
 
+/// The maximum number of notifications that are drawn as a single frame, so that a flood of
+/// notifications can't delay input indefinitely.
+const MAX_COALESCED_NOTIFICATIONS: usize = 64;
+
+/// The number of rows that the mouse wheel scrolls by, like vim.
+const WHEEL_ROWS: isize = 3;
+
+/// Converts the 1-based coordinates of a mouse event to a point on the screen. A malformed
+/// coordinate of 0 is treated as the first row or column.
+fn mouse_point(x: u16, y: u16) -> Coordinate {
+    Coordinate::new(
+        usize::from(x).saturating_sub(1),
+        usize::from(y).saturating_sub(1),
+    )
+}
+
+/// Tells a new core where the user's configuration is.
+fn start_core(core: &mut Core) {
+    let xdg_dirs = BaseDirectories::with_prefix("xi").unwrap();
+    core.client_started(Some(xdg_dirs.get_config_home()))
+        .unwrap();
+}
+
 /// Returned when the editor should begin teardown.
 #[derive(Debug)]
 struct ExitRequest;
@@ -33,6 +70,7 @@ struct ExitRequest;
 enum Mode {
     Normal,
     Insert,
+    Replace,
     Command(CommandLine),
 }
 
@@ -42,95 +80,672 @@ impl Default for Mode {
     }
 }
 
-pub struct Editor {
+impl Mode {
+    /// The name of the mode, as displayed in the status line.
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Replace => "REPLACE",
+            Mode::Command(_) => "COMMAND",
+        }
+    }
+
+    /// The shape of the terminal cursor in the mode.
+    fn cursor_shape(&self) -> CursorShape {
+        match self {
+            Mode::Normal => CursorShape::Block,
+            Mode::Insert | Mode::Command(_) => CursorShape::Bar,
+            Mode::Replace => CursorShape::Underline,
+        }
+    }
+}
+
+pub struct Editor<W: Output = Terminal> {
     core: Core,
     mode: Mode,
-    layout: Layout,
-    screen: Screen,
-    active_view: Option<ViewId>,
+    tabs: Tabs,
+    screen: Screen<W>,
+
+    /// Pauses reading input while a shell command uses the terminal.
+    input: PauseHandle,
+
     windows: HashMap<ViewId, Window>,
     styles: Styles,
+
+    /// The values of options with global scope.
+    options: GlobalOptions,
+
+    /// The values of window options that are used for new windows.
+    window_defaults: WindowOptions,
+
+    /// The names of the themes that the core can use.
+    themes: Vec<String>,
+
+    /// The name of the current theme.
+    theme: Option<String>,
+
+    /// Whether a message longer than one line is covering the windows. The screen is redrawn on
+    /// the next key press.
+    prompting: bool,
+
+    /// Whether the user is being asked to restart the core, which exited.
+    confirming_restart: bool,
+
+    /// The first key of a multi-key normal mode command, such as `gt`.
+    pending: Option<Key>,
+
+    /// The text that has been overwritten in replace mode.
+    replaced: Replaced,
+
+    /// The window that the left mouse button was pressed in, while it is held.
+    dragging: Option<ViewId>,
+
+    /// Whether the screen buffer has changes that will be refreshed once the pending notifications
+    /// are handled.
+    needs_refresh: bool,
 }
 
 impl Editor {
-    pub fn new<P: Into<PathBuf>>(mut core: Core, initial_path: Option<P>) -> Self {
-        let xdg_dirs = BaseDirectories::with_prefix("xi").unwrap();
-        core.client_started(Some(xdg_dirs.get_config_home()))
-            .unwrap();
+    pub fn new<P: Into<PathBuf>>(
+        core: Core,
+        input: PauseHandle,
+        initial_path: Option<P>,
+        color_depth: ColorDepth,
+    ) -> Self {
+        let settings = BaseDirectories::with_prefix("seventeen")
+            .map_err(failure::Error::from)
+            .and_then(|dirs| Settings::load(&dirs).map_err(failure::Error::from))
+            .unwrap_or_else(|e| {
+                error!("could not load settings: {}", e);
+                Settings::default()
+            });
 
         let (cols, rows) = termion::terminal_size().unwrap();
         let screen_size = Size2D::new(usize::from(cols), usize::from(rows));
-        let layout = Layout::new(screen_size);
+        let screen = Screen::new(screen_size, color_depth).unwrap();
+
+        Self::with_screen(core, input, initial_path, screen, settings)
+    }
+}
+
+impl<W: Output> Editor<W> {
+    /// Creates an editor that draws to a screen.
+    fn with_screen<P: Into<PathBuf>>(
+        mut core: Core,
+        input: PauseHandle,
+        initial_path: Option<P>,
+        screen: Screen<W>,
+        settings: Settings,
+    ) -> Self {
+        start_core(&mut core);
+
+        if let Some(theme) = &settings.theme {
+            core.set_theme(theme).unwrap();
+        }
 
         let mut editor = Self {
             core,
-            layout,
-            screen: Screen::new(screen_size).unwrap(),
-            active_view: None,
+            tabs: Tabs::new(screen.size()),
+            screen,
+            input,
             mode: Mode::Normal,
             windows: HashMap::new(),
             styles: Styles::new(),
+            options: GlobalOptions::default(),
+            window_defaults: WindowOptions::default(),
+            themes: vec![],
+            theme: None,
+            prompting: false,
+            confirming_restart: false,
+            pending: None,
+            replaced: Replaced::default(),
+            dragging: None,
+            needs_refresh: false,
         };
 
-        editor.new_view(initial_path).unwrap();
+        if let Some(mouse) = settings.mouse {
+            match editor.options.set("mouse", Value::String(mouse)) {
+                Ok(()) => editor.screen.set_mouse(true).unwrap(),
+                Err(e) => error!("invalid mouse setting: {}", e),
+            }
+        }
+
+        if let Err(e) = editor.new_view(initial_path) {
+            editor.show_error(&format!("could not open a view: {}", e));
+        }
 
         editor
     }
 
-    fn new_view<P: Into<PathBuf>>(&mut self, path: Option<P>) -> io::Result<()> {
-        let view_id = self.core.new_view(path).wait().unwrap();
-        let bounds = self.layout.add_view(&view_id);
+    /// Opens a new view in the active tab.
+    fn new_view<P: Into<PathBuf>>(&mut self, path: Option<P>) -> Result<(), CoreError> {
+        let path = path.map(Into::into);
+        let view_id = self.core.new_view(path.clone()).wait()?;
+        self.add_view(view_id, path)?;
+        Ok(())
+    }
+
+    /// Adds a view that was opened by the core to the active tab.
+    fn add_view(&mut self, view_id: ViewId, path: Option<PathBuf>) -> io::Result<()> {
+        let tab = self.tabs.active_mut();
+        let bounds = tab.layout.add_view(&view_id);
+
+        self.core
+            .scroll(view_id.clone(), (0, bounds.size.height as u16))?;
 
-        self.core.scroll(
-            view_id.clone(),
-            (bounds.min_y() as u16, bounds.max_y() as u16),
-        )?;
-        self.windows.insert(view_id.clone(), Window::new());
-        self.active_view = Some(view_id);
+        let mut window = Window::new();
+        window.path = path;
+        window.options = self.window_defaults.clone();
+        self.windows.insert(view_id.clone(), window);
+        tab.active_view = Some(view_id);
 
         Ok(())
     }
 
     fn update(&mut self, view_id: ViewId, update: Update) {
         let window = self.windows.get_mut(&view_id).unwrap();
+        window.pristine = update.pristine;
         window.line_cache.update(update);
+
+        // The tab line displays file names, which are not known until the view's first update.
+        self.tabs
+            .render(&self.windows, &self.styles, &mut self.screen);
+        self.render_window(&view_id);
+        self.needs_refresh = true;
+    }
+
+    fn scroll_to(&mut self, view_id: ViewId, line: usize, col: usize) {
+        let bounds = match self.tabs.find_view(&view_id) {
+            Some(tab) => tab.layout.of_view(&view_id),
+            None => return,
+        };
+
+        let window = self.windows.get_mut(&view_id).unwrap();
+        window.scroll_to(&bounds, Coordinate::new(col, line));
+        self.render_window(&view_id);
+        self.needs_refresh = true;
+    }
+
+    fn language_changed(&mut self, view_id: ViewId, language_id: String) {
+        self.windows.get_mut(&view_id).unwrap().language = Some(language_id);
+        self.render_window(&view_id);
+        self.needs_refresh = true;
+    }
+
+    /// Renders a window and its status line to the screen buffer, if it is part of the active
+    /// tab.
+    fn render_window(&mut self, view_id: &ViewId) {
+        let tab = self.tabs.active();
+        if !tab.layout.contains(view_id) {
+            return;
+        }
+
+        let window = &self.windows[view_id];
+        let active = tab.active_view.as_ref() == Some(view_id);
+
+        // In command mode, the terminal cursor is on the command line.
+        let has_cursor = match self.mode {
+            Mode::Command(_) => false,
+            _ => active,
+        };
         window
             .render(
                 &self.styles,
-                &self.layout.of_view(&view_id),
+                &tab.layout.of_view(view_id),
+                has_cursor,
                 &mut self.screen,
             )
             .unwrap();
+
+        let mode = if active {
+            self.screen.set_cursor_shape(self.mode.cursor_shape());
+            Some(self.mode.name())
+        } else {
+            None
+        };
+        window.render_status(
+            &self.options.statusline,
+            mode,
+            &self.styles,
+            &tab.layout.of_status_line(view_id),
+            &mut self.screen,
+        );
+    }
+
+    /// Renders the active window, which must be updated when the mode changes.
+    fn render_active_window(&mut self) {
+        if let Some(view_id) = self.tabs.active().active_view.clone() {
+            self.render_window(&view_id);
+        }
+    }
+
+    /// Redraws the entire screen from scratch.
+    fn redraw(&mut self) {
+        self.screen.erase();
+        self.tabs
+            .render(&self.windows, &self.styles, &mut self.screen);
+
+        let views = self
+            .tabs
+            .active()
+            .layout
+            .views()
+            .cloned()
+            .collect::<Vec<_>>();
+        for view_id in &views {
+            self.render_window(view_id);
+        }
+
         self.screen.refresh().unwrap();
     }
 
-    fn scroll_to(&mut self, view_id: ViewId, line: usize, col: usize) {
-        let window = self.windows.get_mut(&view_id).unwrap();
-        let bounds = self.layout.of_view(&view_id);
+    /// Informs every window and the core of changes to window bounds.
+    ///
+    /// This must be called whenever the tab line is shown or hidden.
+    fn relayout(&mut self) {
+        for tab in self.tabs.iter() {
+            for view_id in tab.layout.views() {
+                let bounds = &tab.layout.of_view(view_id);
+                let window = self.windows.get_mut(view_id).unwrap();
+                let cursor = window.cursor;
+                window.scroll_to(bounds, cursor);
+                self.core
+                    .scroll(view_id.clone(), (0, bounds.size.height as u16))
+                    .unwrap();
+            }
+        }
+    }
 
-        window.scroll_to(&bounds, Coordinate::new(col, line));
-        window
-            .render(&self.styles, &bounds, &mut self.screen)
+    fn tab_new(&mut self, path: Option<PathBuf>) {
+        let view_id = match self.core.new_view(path.clone()).wait() {
+            Ok(view_id) => view_id,
+            Err(e) => return self.show_error(&format!("could not open a view: {}", e)),
+        };
+        self.tabs.open();
+        self.add_view(view_id, path).unwrap();
+        self.relayout();
+        self.redraw();
+    }
+
+    fn tab_close(&mut self) {
+        let tab = match self.tabs.close() {
+            Some(tab) => tab,
+            None => {
+                self.show_error("E784: Cannot close last tab page");
+                return;
+            }
+        };
+
+        for view_id in tab.layout.views() {
+            self.windows.remove(view_id);
+            self.core.close_view(view_id.clone()).unwrap();
+        }
+
+        self.relayout();
+        self.redraw();
+    }
+
+    fn run_command(&mut self, command: Command) -> Option<ExitRequest> {
+        match command {
+            Command::Quit => {
+                if self.tabs.len() == 1 {
+                    return Some(ExitRequest);
+                }
+                self.tab_close();
+            }
+            Command::Colorscheme(name) => self.colorscheme(name),
+            Command::Set(args) => self.set(&args, false),
+            Command::SetLocal(args) => self.set(&args, true),
+            Command::TabNew(path) => self.tab_new(path),
+            Command::TabClose => self.tab_close(),
+            Command::TabNext => {
+                self.tabs.next();
+                self.redraw();
+            }
+            Command::TabPrevious => {
+                self.tabs.previous();
+                self.redraw();
+            }
+            Command::Suspend => self.suspend(),
+            Command::Shell(command) => self.shell(command.as_ref().map(String::as_str)),
+            Command::Read { range, command } => self.read_command(range, &command),
+            Command::Filter { range, command } => self.filter(range, &command),
+        }
+
+        None
+    }
+
+    /// Stops the editor, like a shell job, until it is continued.
+    fn suspend(&mut self) {
+        // Reading input is paused, so that no input is read while the shell owns the terminal.
+        let pause = self.input.pause();
+        self.screen.suspend().unwrap();
+
+        // Like a shell, the whole process group is stopped, including the core. This returns once
+        // the editor is continued.
+        unsafe {
+            libc::kill(0, libc::SIGTSTP);
+        }
+
+        self.screen.resume().unwrap();
+        drop(pause);
+        self.redraw();
+    }
+
+    /// Runs a shell command on the terminal and waits for enter to be pressed, or runs an
+    /// interactive shell.
+    fn shell(&mut self, command: Option<&str>) {
+        let pause = self.input.pause();
+        self.screen.suspend().unwrap();
+
+        let mut stdout = io::stdout();
+        if let Some(command) = command {
+            writeln!(stdout, ":!{}", command).unwrap();
+        }
+
+        match shell::run(command) {
+            Ok(status) => {
+                if let Some(code) = status.code().filter(|&code| code != 0) {
+                    writeln!(stdout, "\nshell returned {}", code).unwrap();
+                }
+            }
+            Err(e) => writeln!(stdout, "could not run shell: {}", e).unwrap(),
+        }
+
+        let waited = match command {
+            Some(_) => shell::wait_for_enter(),
+            None => Ok(()),
+        };
+
+        self.screen.resume().unwrap();
+        drop(pause);
+        self.redraw();
+
+        if let Err(e) = waited {
+            self.show_error(&format!("could not read the terminal: {}", e));
+        }
+    }
+
+    /// Inserts the output of a shell command as new lines after the last line of a range.
+    fn read_command(&mut self, range: Option<Range>, command: &str) {
+        let id = match &self.tabs.active().active_view {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        let window = &self.windows[&id];
+        let line = match range {
+            Some(range) => match range.lines(window.cursor.y, window.buffer_len()) {
+                Some((_, end)) => end,
+                None => return self.show_error("Invalid range"),
+            },
+            None => window.cursor.y,
+        };
+
+        let output = match self.run_filter(command, "") {
+            Some(output) => output,
+            None => return,
+        };
+        if output.is_empty() {
+            return;
+        }
+
+        // The core places the cursor at the end of the line if the column is past it.
+        let text = if output.ends_with('\n') {
+            &output[..output.len() - 1]
+        } else {
+            &output
+        };
+        self.core
+            .gesture(id.clone(), line, std::usize::MAX)
             .unwrap();
-        self.screen.refresh().unwrap();
+        self.core.insert(id.clone(), format!("\n{}", text)).unwrap();
+        self.core.gesture(id, line + 1, 0).unwrap();
+    }
+
+    /// Replaces a range of lines with the output of a shell command that reads them.
+    fn filter(&mut self, range: Range, command: &str) {
+        let id = match &self.tabs.active().active_view {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        let window = &self.windows[&id];
+        let (start, end) = match range.lines(window.cursor.y, window.buffer_len()) {
+            Some(lines) => lines,
+            None => return self.show_error("Invalid range"),
+        };
+
+        // The lines may not be cached by the window, so they are copied from the core. The
+        // selection includes the newline at the end of the range.
+        self.core.gesture(id.clone(), start, 0).unwrap();
+        self.core.drag(id.clone(), end + 1, 0).unwrap();
+        let input = match self.core.copy(id.clone()).wait() {
+            Ok(input) => input.unwrap_or_default(),
+            Err(e) => {
+                self.core.gesture(id, start, 0).unwrap();
+                return self.show_error(&format!("could not copy the lines: {}", e));
+            }
+        };
+
+        if let Some(mut output) = self.run_filter(command, &input) {
+            // The last line of the buffer may not end with a newline.
+            if !input.ends_with('\n') && output.ends_with('\n') {
+                output.pop();
+            }
+            self.core.insert(id.clone(), output).unwrap();
+        }
+        self.core.gesture(id, start, 0).unwrap();
+    }
+
+    /// Runs a shell command that reads `input`, returning its output. If the command fails, its
+    /// error output is displayed instead.
+    fn run_filter(&mut self, command: &str, input: &str) -> Option<String> {
+        match shell::filter(command, input) {
+            Ok(output) => {
+                if output.status.success() {
+                    return Some(String::from_utf8_lossy(&output.stdout).into_owned());
+                }
+
+                let mut lines = String::from_utf8_lossy(&output.stderr)
+                    .lines()
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                if let Some(code) = output.status.code() {
+                    lines.push(format!("shell returned {}", code));
+                }
+                self.show_message(&lines);
+            }
+            Err(e) => self.show_error(&format!("could not run shell: {}", e)),
+        }
+
+        None
+    }
+
+    /// Changes the theme, or displays the name of the current theme.
+    fn colorscheme(&mut self, name: Option<String>) {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                let current = self.theme.clone().unwrap_or_default();
+                self.show_message(&[current]);
+                return;
+            }
+        };
+
+        // The core ignores unknown themes, so report them like vim does.
+        if !self.themes.is_empty() && !self.themes.contains(&name) {
+            self.show_error(&format!("E185: Cannot find color scheme '{}'", name));
+            return;
+        }
+
+        self.core.set_theme(&name).unwrap();
+    }
+
+    /// Sets options, or displays their values.
+    ///
+    /// If `local` is true, options that have a value for each window are only changed for the
+    /// active window, and options owned by the core are only changed for the active view.
+    fn set(&mut self, args: &[String], local: bool) {
+        let view_id = match &self.tabs.active().active_view {
+            Some(view_id) => view_id.clone(),
+            None => return,
+        };
+
+        let mut message = vec![];
+        let mut result = Ok(());
+        for arg in args {
+            match self.set_option(&view_id, Argument::parse(arg), local) {
+                Ok(lines) => message.extend(lines),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // The gutter, wrapping and scrolling options affect scrolling.
+        let bounds = self.tabs.active().layout.of_view(&view_id);
+        let window = self.windows.get_mut(&view_id).unwrap();
+        let cursor = window.cursor;
+        window.scroll_to(&bounds, cursor);
+        self.redraw();
+
+        match result {
+            Ok(()) if !message.is_empty() => self.show_message(&message),
+            Ok(()) => (),
+            Err(e) => self.show_error(&e.to_string()),
+        }
     }
 
-    fn config_changed(&mut self, changes: ConfigChanges) {
-        // This will likely break in the future. `theme` is a nonstandard configuration option.
-        // See https://github.com/google/xi-editor/issues/722 for the motivation.
-        if let Some(Value::String(theme)) = changes.other.get("theme") {
-            self.core.set_theme(&theme).unwrap();
+    /// Applies a single argument to `:set`, returning the lines of any message that should be
+    /// displayed.
+    fn set_option(
+        &mut self,
+        view_id: &ViewId,
+        argument: Argument<'_>,
+        local: bool,
+    ) -> Result<Vec<String>, OptionError> {
+        let window = &self.windows[view_id];
+
+        if argument == Argument::All {
+            let items = options::OPTIONS
+                .iter()
+                .map(|option| option.format(&option.value(&self.options, window)))
+                .collect::<Vec<_>>();
+            let width = self.tabs.active().layout.of_command_line().size.width;
+
+            let mut lines = vec![String::from("--- Options ---")];
+            lines.extend(options::columns(&items, width));
+            return Ok(lines);
+        }
+
+        let option = options::find(argument.name())
+            .ok_or_else(|| OptionError::Unknown(argument.to_string()))?;
+        let current = option.value(&self.options, window);
+        let value = match option.evaluate(argument, &current)? {
+            Some(value) => value,
+            None => return Ok(vec![option.format(&current)]),
+        };
+
+        info!("setting {} to {:?}", option.name, value);
+
+        match option.scope {
+            Scope::Global => {
+                self.options.set(option.name, value)?;
+                if option.name == "mouse" {
+                    self.screen
+                        .set_mouse(!self.options.mouse.is_empty())
+                        .unwrap();
+                }
+            }
+            Scope::Window => {
+                let window = self.windows.get_mut(view_id).unwrap();
+                window.options.set(option.name, value.clone())?;
+                if !local {
+                    self.window_defaults.set(option.name, value)?;
+                }
+            }
+            Scope::Core(key) => {
+                // The core notifies each affected view of the new configuration.
+                let changes = options::core_changes(key, &value)?;
+                let domain = if local {
+                    ConfigDomain::UserOverride(view_id.clone())
+                } else {
+                    ConfigDomain::General
+                };
+                self.core.modify_user_config(domain, changes).unwrap();
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    /// Displays a message.
+    ///
+    /// Messages longer than one line are displayed above the command line, covering the windows
+    /// until the next key is pressed.
+    fn show_message(&mut self, lines: &[String]) {
+        let bounds = self.tabs.active().layout.of_command_line();
+
+        if let [line] = lines {
+            self.screen.erase_line(bounds.origin.y);
+            self.screen.write_str(bounds.origin, line);
+            self.screen.refresh().unwrap();
+            return;
         }
+
+        let height = lines.len().min(bounds.origin.y);
+        let top = bounds.origin.y - height;
+        for (i, line) in lines.iter().take(height).enumerate() {
+            self.screen.erase_line(top + i);
+            self.screen
+                .write_str(Coordinate::new(bounds.origin.x, top + i), line);
+        }
+
+        self.screen.erase_line(bounds.origin.y);
+        self.screen
+            .write_str(bounds.origin, "Press ENTER or type command to continue");
+        self.screen.refresh().unwrap();
+        self.prompting = true;
+    }
+
+    /// Displays an error message on the command line.
+    fn show_error(&mut self, message: &str) {
+        error!("{}", message);
+
+        let bounds = self.tabs.active().layout.of_command_line();
+        let message = message.chars().take(bounds.size.width).collect::<String>();
+        self.screen.erase_line(bounds.origin.y);
+        self.screen.write_str(bounds.origin, &message);
+        self.screen.refresh().unwrap();
+    }
+
+    fn config_changed(&mut self, view_id: ViewId, changes: ConfigChanges) {
+        let bounds = match self.tabs.find_view(&view_id) {
+            Some(tab) => tab.layout.of_view(&view_id),
+            None => return,
+        };
+
+        // Settings such as the tab size affect the layout of the window.
+        let window = self.windows.get_mut(&view_id).unwrap();
+        window.config.apply(changes);
+        let cursor = window.cursor;
+        window.scroll_to(&bounds, cursor);
+        self.render_window(&view_id);
+        self.needs_refresh = true;
     }
 
     fn theme_changed(&mut self, name: String, theme: ThemeSettings) {
         info!("theme changed to {}", name);
-        self.styles.fg = theme.foreground.map(Into::into);
-        self.styles.bg = theme.background.map(Into::into);
+        self.styles.set_theme(&theme);
+        self.theme = Some(name);
+
+        // Every cell may have changed color.
+        self.redraw();
     }
 
     fn move_up(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             // If the `move_up` RPC is sent while the cursor is in the top row, the cursor will move to
             // the beginning of the line. vim will keep the cursor at the same position.
             if self.windows[&id].cursor.y == 0 {
@@ -142,7 +757,7 @@ impl Editor {
     }
 
     fn move_down(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             let window = &self.windows[&id];
 
             // If the `move_down` RPC is sent while the cursor is on the bottom row, the cursor will
@@ -156,7 +771,7 @@ impl Editor {
     }
 
     fn move_left(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             if self.windows[&id].cursor.x > 0 {
                 self.core.move_left(id.clone()).unwrap();
             }
@@ -164,7 +779,7 @@ impl Editor {
     }
 
     fn move_right(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             let window = &self.windows[id];
             if !window.line_cache.is_eol(&window.cursor) {
                 self.core.move_right(id.clone()).unwrap();
@@ -172,14 +787,30 @@ impl Editor {
         }
     }
 
+    /// Moves the cursor by a screen row instead of a buffer line, if the window is wrapping
+    /// lines.
+    fn move_visual(&mut self, down: bool) {
+        let id = match &self.tabs.active().active_view {
+            Some(id) => id.clone(),
+            None => return,
+        };
+
+        let bounds = self.tabs.active().layout.of_view(&id);
+        match self.windows[&id].visual_move(&bounds, down) {
+            Some(target) => self.core.gesture(id, target.y, target.x).unwrap(),
+            None if down => self.move_down(),
+            None => self.move_up(),
+        }
+    }
+
     fn move_word_left(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             self.core.move_word_left(id.clone()).unwrap();
         }
     }
 
     fn move_word_right(&mut self) {
-        if let Some(id) = &self.active_view {
+        if let Some(id) = &self.tabs.active().active_view {
             self.core.move_word_right(id.clone()).unwrap();
 
             // vim moves to the first letter of each word, while xi will move to the space between.
@@ -188,20 +819,49 @@ impl Editor {
     }
 
     fn handle_normal_key(&mut self, key: Key) {
+        if let Some(pending) = self.pending.take() {
+            match (pending, key) {
+                (Key::Char('g'), Key::Char('t')) => {
+                    self.tabs.next();
+                    self.redraw();
+                }
+                (Key::Char('g'), Key::Char('T')) => {
+                    self.tabs.previous();
+                    self.redraw();
+                }
+                (Key::Char('g'), Key::Char('j')) => self.move_visual(true),
+                (Key::Char('g'), Key::Char('k')) => self.move_visual(false),
+                _ => warn!("unhandled key sequence: {:?} {:?}", pending, key),
+            }
+            return;
+        }
+
         match key {
             Key::Char('b') => {
                 self.move_word_left();
             }
+            Key::Char('g') => {
+                self.pending = Some(key);
+            }
             Key::Char('h') => {
                 self.move_left();
             }
             Key::Char('i') => {
                 info!("entering insert mode");
                 self.mode = Mode::Insert;
+                self.render_active_window();
+                self.screen.refresh().unwrap();
             }
             Key::Char('j') => {
                 self.move_down();
             }
+            Key::Char('R') => {
+                info!("entering replace mode");
+                self.mode = Mode::Replace;
+                self.replaced.clear();
+                self.render_active_window();
+                self.screen.refresh().unwrap();
+            }
             Key::Char('k') => {
                 self.move_up();
             }
@@ -211,15 +871,18 @@ impl Editor {
             Key::Char('w') => {
                 self.move_word_right();
             }
+            Key::Ctrl('z') => self.suspend(),
             Key::Char(':') => {
                 info!("entering command mode");
-                let line = CommandLine::new();
-                line.render(
-                    &self.styles,
-                    self.layout.of_command_line(),
-                    &mut self.screen,
-                );
-                self.mode = Mode::Command(line);
+                self.mode = Mode::Command(CommandLine::new());
+                self.render_active_window();
+                if let Mode::Command(line) = &self.mode {
+                    line.render(
+                        &self.styles,
+                        self.tabs.active().layout.of_command_line(),
+                        &mut self.screen,
+                    );
+                }
             }
             _ => warn!("unhandled key: {:?}", key),
         }
@@ -227,24 +890,300 @@ impl Editor {
 
     fn handle_insert_key(&mut self, key: Key) {
         match key {
+            Key::Char('\t') => {
+                if let Some(id) = &self.tabs.active().active_view {
+                    let text = self.windows[id].tab_text();
+                    self.core.insert(id.clone(), text).unwrap();
+                }
+            }
             Key::Char(c) => {
-                if let Some(id) = &self.active_view {
+                if let Some(id) = &self.tabs.active().active_view {
                     self.core.insert(id.clone(), c.to_string()).unwrap();
                 }
             }
             Key::Backspace => {
-                if let Some(id) = &self.active_view {
+                if let Some(id) = &self.tabs.active().active_view {
                     self.core.delete_backward(id.clone()).unwrap();
                 }
             }
             Key::Esc => {
                 info!("entering normal mode");
                 self.mode = Mode::Normal;
+                self.render_active_window();
+                self.screen.refresh().unwrap();
             }
             _ => warn!("unhandled key: {:?}", key),
         }
     }
 
+    fn handle_replace_key(&mut self, key: Key) {
+        let id = match &self.tabs.active().active_view {
+            Some(id) => id.clone(),
+            None => return,
+        };
+
+        match key {
+            // Characters replace the text under the cursor. Past the end of the line, they are
+            // appended instead.
+            Key::Char(c) if c != '\n' => {
+                let window = &self.windows[&id];
+                let overwritten = window.line_cache.grapheme_at(&window.cursor);
+                if overwritten.is_some() {
+                    self.core.delete_forward(id.clone()).unwrap();
+                }
+                self.replaced.push(overwritten);
+                self.core.insert(id, c.to_string()).unwrap();
+            }
+            Key::Char('\n') => {
+                self.replaced.push(None);
+                self.core.insert(id, String::from("\n")).unwrap();
+            }
+            // Backspace undoes the characters typed since entering replace mode.
+            Key::Backspace => match self.replaced.pop() {
+                Restore::Text(text) => {
+                    self.core.delete_backward(id.clone()).unwrap();
+                    self.core.insert(id.clone(), text).unwrap();
+                    self.core.move_left(id).unwrap();
+                }
+                Restore::Delete => self.core.delete_backward(id).unwrap(),
+                Restore::MoveLeft => self.move_left(),
+            },
+            Key::Esc => {
+                info!("entering normal mode");
+                self.mode = Mode::Normal;
+                self.render_active_window();
+                self.screen.refresh().unwrap();
+            }
+            _ => warn!("unhandled key: {:?}", key),
+        }
+    }
+
+    fn handle_mouse(&mut self, event: MouseEvent) {
+        let mode = match self.mode {
+            Mode::Normal => 'n',
+            Mode::Insert | Mode::Replace => 'i',
+            // The command line doesn't support the mouse.
+            Mode::Command(_) => return,
+        };
+        if self.prompting || !self.options.mouse_enabled(mode) {
+            return;
+        }
+
+        match event {
+            MouseEvent::Press(button, x, y) => {
+                let point = mouse_point(x, y);
+                let view_id = match self.tabs.active().layout.view_at(point) {
+                    Some(view_id) => view_id.clone(),
+                    None => return,
+                };
+
+                match button {
+                    MouseButton::Left => {
+                        // Backspace can't restore the text before the cursor once it moves.
+                        self.replaced.clear();
+                        self.focus(&view_id);
+                        if let Some(position) = self.position_at(&view_id, point) {
+                            self.core
+                                .gesture(view_id.clone(), position.y, position.x)
+                                .unwrap();
+                        }
+                        self.dragging = Some(view_id);
+                    }
+                    MouseButton::WheelUp => self.scroll_window(&view_id, -WHEEL_ROWS),
+                    MouseButton::WheelDown => self.scroll_window(&view_id, WHEEL_ROWS),
+                    _ => (),
+                }
+            }
+            MouseEvent::Hold(x, y) => {
+                let point = mouse_point(x, y);
+                if let Some(view_id) = self.dragging.clone() {
+                    if let Some(position) = self.position_at(&view_id, point) {
+                        self.core.drag(view_id, position.y, position.x).unwrap();
+                    }
+                }
+            }
+            MouseEvent::Release(..) => self.dragging = None,
+        }
+    }
+
+    /// Returns the buffer position in a window of the active tab that is displayed at a point on
+    /// the screen. Points outside of the window select the nearest position inside it.
+    fn position_at(&self, view_id: &ViewId, point: Coordinate) -> Option<Coordinate> {
+        let bounds = self.tabs.active().layout.of_view(view_id);
+        let point = Coordinate::new(
+            point.x.max(bounds.min_x()).min(bounds.max_x() - 1),
+            point.y.max(bounds.min_y()).min(bounds.max_y() - 1),
+        );
+        self.windows[view_id].position_at(&bounds, point)
+    }
+
+    /// Makes a window of the active tab the active window.
+    fn focus(&mut self, view_id: &ViewId) {
+        let tab = self.tabs.active_mut();
+        if tab.active_view.as_ref() == Some(view_id) {
+            return;
+        }
+
+        // The status lines of both windows change.
+        let previous = tab.active_view.replace(view_id.clone());
+        if let Some(previous) = previous {
+            self.render_window(&previous);
+        }
+        self.render_window(view_id);
+        self.screen.refresh().unwrap();
+    }
+
+    /// Scrolls a window of the active tab without moving the cursor, unless it would no longer be
+    /// visible.
+    fn scroll_window(&mut self, view_id: &ViewId, rows: isize) {
+        let bounds = self.tabs.active().layout.of_view(view_id);
+        let window = self.windows.get_mut(view_id).unwrap();
+        let cursor = window.scroll_by(&bounds, rows);
+        let top = window.top_line();
+
+        // The core only sends the lines that are visible.
+        let visible = (top as u16, (top + bounds.size.height) as u16);
+        self.core.scroll(view_id.clone(), visible).unwrap();
+        if let Some(cursor) = cursor {
+            self.replaced.clear();
+            self.core
+                .gesture(view_id.clone(), cursor.y, cursor.x)
+                .unwrap();
+        }
+
+        self.render_window(view_id);
+        self.screen.refresh().unwrap();
+    }
+
+    /// Inserts pasted text all at once, rather than handling it as typed keys.
+    fn handle_paste(&mut self, text: String) {
+        if self.prompting {
+            self.prompting = false;
+            self.redraw();
+        }
+
+        match &mut self.mode {
+            // Like vim, pasting in normal mode inserts the text without entering insert mode.
+            // Unlike vim, pasting in replace mode doesn't replace any text.
+            Mode::Normal | Mode::Insert | Mode::Replace => {
+                if let Some(id) = &self.tabs.active().active_view {
+                    self.core.insert(id.clone(), text).unwrap();
+                }
+            }
+            Mode::Command(line) => {
+                // The command line has a single line.
+                for c in text.chars().filter(|c| !c.is_control()) {
+                    line.insert(c);
+                }
+                line.render(
+                    &self.styles,
+                    self.tabs.active().layout.of_command_line(),
+                    &mut self.screen,
+                );
+                self.screen.refresh().unwrap();
+            }
+        }
+    }
+
+    /// Asks the user whether to restart the core, which exited unexpectedly.
+    fn core_exited(&mut self) {
+        let status = match self.core.exit_status() {
+            Some(status) => status.to_string(),
+            None => String::from("closed its output"),
+        };
+        self.show_error(&format!(
+            "xi-core exited unexpectedly ({}). Restart it? (y/n)",
+            status
+        ));
+        self.confirming_restart = true;
+    }
+
+    /// Restarts the core and reopens the view of every window.
+    ///
+    /// Views with unsaved changes are reopened with the text in their line caches, if every line
+    /// is cached. Options that are stored by the core are reset.
+    fn restart_core(&mut self) {
+        if let Err(e) = self.core.restart() {
+            self.show_error(&format!("could not restart xi-core: {}", e));
+            return;
+        }
+        start_core(&mut self.core);
+        if let Some(theme) = &self.theme {
+            self.core.set_theme(theme).unwrap();
+        }
+
+        let mut errors = vec![];
+        let mut lost = vec![];
+
+        // Every window is given a new ID, since the new core may give a view the old ID of another
+        // view. The views are reopened in the order of their tabs.
+        let mut renamed = HashMap::new();
+        let mut reopened = HashSet::new();
+        let old_ids: Vec<_> = self
+            .tabs
+            .iter()
+            .flat_map(|tab| tab.layout.views())
+            .cloned()
+            .collect();
+        for old_id in old_ids {
+            let window = &self.windows[&old_id];
+            let text = if window.pristine {
+                None
+            } else {
+                let text = window.line_cache.text();
+                if text.is_none() {
+                    lost.push(window.display_name());
+                }
+                text
+            };
+
+            let new_id = match self.core.new_view(window.path.clone()).wait() {
+                Ok(view_id) => view_id,
+                // The window is kept, so that its text can still be copied, with an ID that the
+                // core doesn't give to views.
+                Err(e) => {
+                    errors.push(format!("could not reopen {}: {}", window.display_name(), e));
+                    let closed_id = ViewId(format!("closed-{}", old_id));
+                    renamed.insert(old_id, closed_id);
+                    continue;
+                }
+            };
+            if let Some(text) = text {
+                self.core.select_all(new_id.clone()).unwrap();
+                self.core.insert(new_id.clone(), text).unwrap();
+            }
+            reopened.insert(new_id.clone());
+            renamed.insert(old_id, new_id);
+        }
+
+        let windows: Vec<_> = self.windows.drain().collect();
+        for (old_id, mut window) in windows {
+            if let Some(new_id) = renamed.get(&old_id) {
+                if reopened.contains(new_id) {
+                    window.line_cache = LineCache::new();
+                }
+                self.windows.insert(new_id.clone(), window);
+            }
+        }
+        self.tabs.replace_views(&renamed);
+
+        self.relayout();
+        self.redraw();
+
+        if !lost.is_empty() {
+            errors.push(format!(
+                "could not recover unsaved changes to {}",
+                lost.join(", ")
+            ));
+        }
+        for message in &errors {
+            error!("{}", message);
+        }
+        if !errors.is_empty() {
+            self.show_message(&errors);
+        }
+    }
+
     fn handle_notification(&mut self, notification: Notification) {
         match notification {
             Notification::Update { view_id, update } => self.update(view_id, update),
@@ -261,23 +1200,34 @@ impl Editor {
                     Style {
                         fg: fg_color.map(Color::from_argb),
                         bg: bg_color.map(Color::from_argb),
+                        // Weights are like CSS font weights, where normal text is 400.
                         bold: weight.map(|weight| weight >= 700).unwrap_or_default(),
+                        dim: weight.map(|weight| weight < 400).unwrap_or_default(),
                         underline: underline.unwrap_or_default(),
                         italic: italic.unwrap_or_default(),
+                        ..Default::default()
                     },
                 );
             }
             Notification::ScrollTo { view_id, line, col } => self.scroll_to(view_id, line, col),
-            Notification::ConfigChanged { changes, .. } => {
-                // TODO: Handle view_id
-                self.config_changed(changes);
+            Notification::ConfigChanged { view_id, changes } => {
+                self.config_changed(view_id, changes)
             }
             Notification::ThemeChanged { name, theme } => self.theme_changed(name, theme),
+            Notification::CoreExited => self.core_exited(),
+            Notification::InvalidMessage(message) => {
+                self.show_error(&format!("xi-core sent an invalid message: {}", message));
+            }
+            Notification::LanguageChanged {
+                view_id,
+                language_id,
+            } => self.language_changed(view_id, language_id),
             Notification::PluginStarted { view_id, plugin } => {
                 info!("{} started on {:?}", plugin, view_id);
             }
             Notification::AvailableThemes { themes } => {
                 info!("available themes: {:?}", themes);
+                self.themes = themes;
             }
             Notification::AvailablePlugins { view_id, plugins } => {
                 info!("available plugins for view {}: {:?}", view_id, plugins);
@@ -287,60 +1237,628 @@ impl Editor {
     }
 
     fn handle_input(&mut self, key: Key) -> Option<ExitRequest> {
+        if self.confirming_restart {
+            self.confirming_restart = false;
+            self.screen
+                .erase_line(self.tabs.active().layout.of_command_line().origin.y);
+            self.screen.refresh().unwrap();
+
+            if key == Key::Char('y') {
+                self.restart_core();
+            }
+            return None;
+        }
+
+        if self.prompting {
+            self.prompting = false;
+            self.redraw();
+
+            // Like vim, keys other than those that dismiss the prompt are handled as usual.
+            if let Key::Char('\n') | Key::Char(' ') | Key::Esc = key {
+                return None;
+            }
+        }
+
         match self.mode {
             Mode::Normal => self.handle_normal_key(key),
             Mode::Insert => self.handle_insert_key(key),
-            Mode::Command(ref mut line) => {
-                match key {
-                    Key::Char('\n') | Key::Esc => {
-                        if key == Key::Char('\n') {
-                            // TODO: do some parsing here
-                            match line.command() {
-                                "q" => {
-                                    return Some(ExitRequest);
-                                }
-                                _ => (),
+            Mode::Replace => self.handle_replace_key(key),
+            Mode::Command(ref mut line) => match key {
+                Key::Char('\n') | Key::Esc => {
+                    let command = if key == Key::Char('\n') && !line.command().trim().is_empty() {
+                        Some(line.command().parse::<Command>())
+                    } else {
+                        None
+                    };
+
+                    self.screen
+                        .erase_line(self.tabs.active().layout.of_command_line().origin.y);
+                    info!("entering normal mode");
+                    self.mode = Mode::Normal;
+                    self.render_active_window();
+
+                    match command {
+                        Some(Ok(command)) => {
+                            if let Some(exit) = self.run_command(command) {
+                                return Some(exit);
                             }
                         }
-
-                        self.screen
-                            .erase_line(self.layout.of_command_line().origin.y);
-                        self.screen.refresh().unwrap();
-                        info!("entering normal mode");
-                        self.mode = Mode::Normal;
+                        Some(Err(e)) => self.show_error(&e.to_string()),
+                        None => (),
                     }
-                    Key::Char(c) => {
-                        line.insert(c);
-                        line.render(
-                            &self.styles,
-                            self.layout.of_command_line(),
-                            &mut self.screen,
-                        );
-                    }
-                    Key::Backspace => {
-                        line.delete();
-                        line.render(
-                            &self.styles,
-                            self.layout.of_command_line(),
-                            &mut self.screen,
-                        );
-                    }
-                    _ => warn!("unhandled key: {:?}", key),
+
+                    self.screen.refresh().unwrap();
                 }
-            }
+                Key::Char('\t') => {
+                    line.complete(&self.themes);
+                    line.render(
+                        &self.styles,
+                        self.tabs.active().layout.of_command_line(),
+                        &mut self.screen,
+                    );
+                }
+                Key::Char(c) => {
+                    line.insert(c);
+                    line.render(
+                        &self.styles,
+                        self.tabs.active().layout.of_command_line(),
+                        &mut self.screen,
+                    );
+                }
+                Key::Backspace => {
+                    line.delete();
+                    line.render(
+                        &self.styles,
+                        self.tabs.active().layout.of_command_line(),
+                        &mut self.screen,
+                    );
+                }
+                _ => warn!("unhandled key: {:?}", key),
+            },
         }
 
         None
     }
 
-    pub fn run(mut self, input: Receiver<Key>, notifications: Receiver<Notification>) {
+    pub fn run(mut self, input: Receiver<Event>, notifications: Receiver<Notification>) {
         loop {
             select! {
-                recv(input, key) => if let Some(ExitRequest) = self.handle_input(key.unwrap()) {
-                    break
+                recv(input, event) => match event.unwrap() {
+                    Event::Key(key) => if let Some(ExitRequest) = self.handle_input(key) {
+                        break
+                    },
+                    Event::Mouse(event) => self.handle_mouse(event),
+                    Event::Paste(text) => self.handle_paste(text),
+                    Event::Unsupported(bytes) => warn!("unsupported event: {:?}", bytes),
+                },
+                recv(notifications, notification) => {
+                    self.handle_notification(notification.unwrap());
+
+                    // Edits usually cause an update followed by a scroll. Drawing notifications
+                    // that arrive together as a single frame avoids displaying the states between
+                    // them.
+                    for _ in 0..MAX_COALESCED_NOTIFICATIONS {
+                        match notifications.try_recv() {
+                            Some(notification) => self.handle_notification(notification),
+                            None => break,
+                        }
+                    }
+
+                    if self.needs_refresh {
+                        self.needs_refresh = false;
+                        self.screen.refresh().unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use channel::{self, Receiver};
+    use euclid::{Point2D, Rect, Size2D};
+    use termion::event::{Key, MouseButton, MouseEvent};
+
+    use super::{Editor, LineCache, Settings, Window};
+    use crate::core::FakeCore;
+    use crate::input::PauseHandle;
+    use crate::protocol::{
+        EditMethod, GestureType, Line, Message, Notification, Op, OpKind, Request, Update, ViewId,
+    };
+    use crate::screen::{Attr, Coordinate, Screen};
+
+    /// An editor that is connected to a fake core and draws to a screen in memory.
+    struct Harness {
+        editor: Editor<Vec<u8>>,
+        core: FakeCore,
+        notifications: Receiver<Notification>,
+    }
+
+    impl Harness {
+        fn new(core: FakeCore, path: Option<&str>) -> Self {
+            let (tx, notifications) = channel::bounded(64);
+            let screen = Screen::new_from_write(Size2D::new(30, 6), Vec::new()).unwrap();
+            let editor = Editor::with_screen(
+                core.connect(tx),
+                PauseHandle::default(),
+                path,
+                screen,
+                Settings::default(),
+            );
+
+            let mut harness = Harness {
+                editor,
+                core,
+                notifications,
+            };
+            harness.handle_notifications();
+            harness
+        }
+
+        /// Handles the notifications that have been received, like the event loop.
+        fn handle_notifications(&mut self) {
+            while let Some(notification) = self.notifications.try_recv() {
+                self.editor.handle_notification(notification);
+            }
+            self.refresh();
+        }
+
+        /// Types keys, and handles the notifications that the core sent in response.
+        fn input(&mut self, keys: &str) {
+            for c in keys.chars() {
+                let key = match c {
+                    '\x1b' => Key::Esc,
+                    c => Key::Char(c),
+                };
+                self.editor.handle_input(key);
+            }
+            self.handle_notifications();
+        }
+
+        /// Waits for a notification from the core, and handles it.
+        fn wait(&mut self) {
+            let notification = self.notifications.recv().unwrap();
+            self.editor.handle_notification(notification);
+            self.refresh();
+        }
+
+        /// Draws the notifications that have been handled, like the event loop.
+        fn refresh(&mut self) {
+            if self.editor.needs_refresh {
+                self.editor.needs_refresh = false;
+                self.editor.screen.refresh().unwrap();
+            }
+        }
+
+        /// Replaces the text of a view, and waits for the editor to handle the update.
+        fn update(&mut self, view_id: &str, text: &str) {
+            self.core.update(&ViewId(String::from(view_id)), text, 0);
+            self.wait();
+        }
+
+        /// Returns the lines of the screen.
+        fn lines(&self) -> Vec<String> {
+            let size = self.editor.screen.size();
+            (0..size.height)
+                .map(|y| self.editor.screen.line(y))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn open_file() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\nworld\n");
+        let harness = Harness::new(core, Some("/test/file.txt"));
+
+        assert_eq!(
+            harness.lines(),
+            vec![
+                "hello",
+                "world",
+                "~",
+                "~",
+                " NORMAL  file.txt    1:1  50%",
+                ""
+            ]
+        );
+
+        let received = harness.core.received();
+        match &received[..] {
+            [Message::Notification(Notification::ClientStarted { .. }), Message::Request {
+                req: Request::NewView { file_path },
+                ..
+            }, Message::Notification(Notification::Edit {
+                method: EditMethod::Scroll(0, 4),
+                view_id,
+            })] => {
+                assert_eq!(file_path.as_ref().unwrap(), Path::new("/test/file.txt"));
+                assert_eq!(view_id, &ViewId(String::from("view-id-1")));
+            }
+            _ => panic!("unexpected messages: {:#?}", received),
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.core.received();
+
+        harness.input("ihi");
+        assert_eq!(
+            harness.core.edits(),
+            vec![
+                EditMethod::Insert {
+                    chars: String::from("h")
+                },
+                EditMethod::Insert {
+                    chars: String::from("i")
+                },
+            ]
+        );
+
+        harness.update("view-id-1", "hihello\n");
+        let lines = harness.lines();
+        assert_eq!(lines[0], "hihello");
+        assert!(lines[4].starts_with(" INSERT "), "{:?}", lines[4]);
+
+        harness.input("\x1b");
+        assert!(harness.lines()[4].starts_with(" NORMAL "));
+    }
+
+    #[test]
+    fn commands() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.core.received();
+
+        harness.input(":foo\n");
+        assert_eq!(harness.lines()[5], "Not an editor command: foo");
+
+        harness.input(":tabnew\n");
+        assert_eq!(harness.lines()[0], " file.txt  [No Name]");
+        match &harness.core.received()[0] {
+            Message::Request {
+                req: Request::NewView { file_path: None },
+                ..
+            } => (),
+            message => panic!("expected a new view, got {:?}", message),
+        }
+    }
+
+    #[test]
+    fn replace() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.core.received();
+
+        harness.input("Rx");
+        assert_eq!(
+            harness.core.edits(),
+            vec![
+                EditMethod::DeleteForward,
+                EditMethod::Insert {
+                    chars: String::from("x")
+                },
+            ]
+        );
+        harness.update("view-id-1", "xello\n");
+        assert!(harness.lines()[4].starts_with(" REPLACE "));
+
+        // Backspace deletes inserted newlines, and restores overwritten text.
+        harness.input("\n");
+        harness.editor.handle_input(Key::Backspace);
+        harness.editor.handle_input(Key::Backspace);
+        assert_eq!(
+            harness.core.edits(),
+            vec![
+                EditMethod::Insert {
+                    chars: String::from("\n")
+                },
+                EditMethod::DeleteBackward,
+                EditMethod::DeleteBackward,
+                EditMethod::Insert {
+                    chars: String::from("h")
                 },
-                recv(notifications, notification) => self.handle_notification(notification.unwrap()),
+                EditMethod::MoveLeft,
+            ]
+        );
+
+        // Before the text that was typed in replace mode, it only moves the cursor, which is
+        // already at the start of the line.
+        harness.editor.handle_input(Key::Backspace);
+        assert!(harness.core.edits().is_empty());
+
+        harness.input("\x1b");
+        assert!(harness.lines()[4].starts_with(" NORMAL "));
+    }
+
+    #[test]
+    fn paste() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.core.received();
+
+        // The text is inserted with a single edit, without entering insert mode.
+        harness.editor.handle_paste(String::from("a\n\tb"));
+        assert_eq!(
+            harness.core.edits(),
+            vec![EditMethod::Insert {
+                chars: String::from("a\n\tb")
+            }]
+        );
+        harness.update("view-id-1", "a\n\tbhello\n");
+        let lines = harness.lines();
+        assert_eq!(&lines[..2], ["a", "    bhello"]);
+        assert!(lines[4].starts_with(" NORMAL "), "{:?}", lines[4]);
+
+        // Control characters are dropped from text pasted on the command line.
+        harness.input(":");
+        harness.editor.handle_paste(String::from("tab\nnew\n"));
+        assert_eq!(harness.lines()[5], ":tabnew");
+        assert!(harness.core.received().is_empty());
+
+        harness.input("\n");
+        assert_eq!(harness.lines()[0], " file.txt  [No Name]");
+    }
+
+    #[test]
+    fn invalid_message() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+
+        harness.core.send_line("{\"method\":\"update\"");
+        harness.wait();
+        let lines = harness.lines();
+        assert_eq!(lines[5], "xi-core sent an invalid messag");
+        assert_eq!(lines[0], "hello");
+
+        // Later messages are still handled.
+        harness.update("view-id-1", "world\n");
+        assert_eq!(harness.lines()[0], "world");
+    }
+
+    #[test]
+    fn styles() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.editor.screen.take_output();
+
+        // "world" has the style that the core defined when the view was opened.
+        let line = Line {
+            text: Some(String::from("hello world\n")),
+            cursor: Some(vec![0]),
+            styles: Some(vec![6, 5, 2]),
+        };
+        harness.core.notify(&Notification::Update {
+            view_id: ViewId(String::from("view-id-1")),
+            update: Update {
+                rev: None,
+                ops: vec![Op {
+                    op: OpKind::Ins,
+                    n: 1,
+                    lines: Some(vec![line]),
+                }],
+                pristine: true,
+            },
+        });
+        harness.wait();
+
+        let screen = &mut harness.editor.screen;
+        assert_eq!(screen.line(0), "hello world");
+        assert!(!screen.attr(Coordinate::new(5, 0)).contains(Attr::ITALIC));
+        assert!(screen.attr(Coordinate::new(6, 0)).contains(Attr::ITALIC));
+        assert!(screen.attr(Coordinate::new(10, 0)).contains(Attr::ITALIC));
+
+        let output = String::from_utf8(screen.take_output()).unwrap();
+        let styled = output.find("\x1b[3m").expect("italics were not enabled");
+        let world = output.find("world").unwrap();
+        assert!(styled < world, "{:?}", output);
+        assert!(
+            output[..world].contains("\x1b[38;2;0;0;255m"),
+            "{:?}",
+            output
+        );
+    }
+
+    #[test]
+    fn restart_core() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.input(":tabnew\n");
+        harness.update("view-id-2", "unsaved\n");
+        harness.core.received();
+
+        harness.core.exit();
+        harness.wait();
+        assert!(harness.lines()[5].starts_with("xi-core exited unexpectedly"));
+
+        harness.input("y");
+        let received = harness.core.received();
+        let mut opened = received
+            .iter()
+            .filter_map(|message| match message {
+                Message::Request {
+                    req: Request::NewView { file_path },
+                    ..
+                } => Some(file_path.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        opened.sort();
+        assert_eq!(opened, vec![None, Some(PathBuf::from("/test/file.txt"))]);
+
+        // The unsaved text is restored in whichever view replaced the new tab's view.
+        let restored = received
+            .iter()
+            .filter_map(|message| match message {
+                Message::Notification(Notification::Edit {
+                    method: EditMethod::Insert { chars },
+                    view_id,
+                }) => Some((view_id.clone(), chars.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let view_id = match &restored[..] {
+            [(view_id, chars)] if chars == "unsaved\n" => view_id.clone(),
+            _ => panic!("unexpected edits: {:#?}", received),
+        };
+        harness.update(&view_id.0, "unsaved\n");
+        assert_eq!(harness.lines()[0], " file.txt  [No Name]");
+        assert_eq!(harness.lines()[1], "unsaved");
+        assert_eq!(harness.lines()[5], "");
+
+        harness.input("gT");
+        assert_eq!(harness.lines()[1], "hello");
+    }
+
+    #[test]
+    fn restart_core_reused_ids() {
+        let core = FakeCore::default()
+            .file("/test/a.txt", "apple\n")
+            .file("/test/b.txt", "banana\n");
+        let mut harness = Harness::new(core, Some("/test/a.txt"));
+        harness.input(":tabnew /test/b.txt\n");
+
+        // The views are reopened in the order of their tabs, so swapping them makes view-id-2
+        // reopen first. It can't be opened, but uses up view-id-1, so view-id-1 reopens as
+        // view-id-2.
+        let first = ViewId(String::from("view-id-1"));
+        let second = ViewId(String::from("view-id-2"));
+        let swap = vec![(first.clone(), second.clone()), (second.clone(), first)];
+        harness
+            .editor
+            .tabs
+            .replace_views(&swap.into_iter().collect());
+        harness.core.make_unreadable("/test/b.txt");
+        harness.core.exit();
+        harness.wait();
+        harness.input("y");
+        assert_eq!(harness.lines()[5], "could not reopen b.txt: core r");
+
+        // The window that couldn't be reopened keeps its text, and doesn't receive the updates of
+        // the view that now has its old ID.
+        harness.update("view-id-2", "apricot\n");
+        assert_eq!(harness.lines()[0], " b.txt  a.txt");
+        assert_eq!(harness.lines()[1], "apricot");
+        harness.input("gT");
+        assert_eq!(harness.lines()[1], "banana");
+        assert_eq!(
+            harness.editor.tabs.active().active_view,
+            Some(ViewId(String::from("closed-view-id-2")))
+        );
+    }
+
+    fn click(x: u16, y: u16) -> MouseEvent {
+        MouseEvent::Press(MouseButton::Left, x, y)
+    }
+
+    fn gesture(line: usize, col: usize) -> EditMethod {
+        EditMethod::Gesture {
+            line,
+            col,
+            ty: GestureType::PointSelect,
+        }
+    }
+
+    #[test]
+    fn mouse_clicks() {
+        let core = FakeCore::default().file("/test/file.txt", "0\n1\n2\n3\n4\n5\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.core.received();
+
+        // The mouse is disabled by default.
+        harness.editor.handle_mouse(click(1, 1));
+        assert!(harness.core.received().is_empty());
+
+        harness.input(":set mouse=a\n");
+        harness.core.received();
+
+        // Mouse coordinates are 1-based, and clicks past the end of a line select its last
+        // character.
+        harness.editor.handle_mouse(click(1, 2));
+        harness.editor.handle_mouse(click(20, 3));
+        assert_eq!(harness.core.edits(), vec![gesture(1, 0), gesture(2, 0)]);
+
+        // Clicks on the status line select the last row of the window, and clicks on the command
+        // line are ignored.
+        harness.editor.handle_mouse(click(1, 5));
+        harness.editor.handle_mouse(click(1, 6));
+        assert_eq!(harness.core.edits(), vec![gesture(3, 0)]);
+
+        // Invalid coordinates of 0 are treated as 1.
+        harness.editor.handle_mouse(click(0, 0));
+        assert_eq!(harness.core.edits(), vec![gesture(0, 0)]);
+    }
+
+    #[test]
+    fn mouse_focus() {
+        let core = FakeCore::default().file("/test/file.txt", "hello\n");
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.input(":set mouse=a\n:set statusline=%f%=%{mode}\n");
+
+        // Split the window, with a separator column between the windows.
+        let first = ViewId(String::from("view-id-1"));
+        let second = ViewId(String::from("view-id-2"));
+        let layout = &mut harness.editor.tabs.active_mut().layout;
+        layout.place_view(&first, Rect::from_size(Size2D::new(14, 5)));
+        layout.place_view(&second, Rect::new(Point2D::new(15, 0), Size2D::new(15, 5)));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["world"]);
+        harness.editor.windows.insert(second.clone(), window);
+        harness.editor.redraw();
+        harness.core.received();
+
+        harness.editor.handle_mouse(click(16, 1));
+        assert_eq!(
+            harness.editor.tabs.active().active_view,
+            Some(second.clone())
+        );
+        assert_eq!(harness.lines()[4], "file.txt       [No Name]NORMAL");
+        match &harness.core.received()[..] {
+            [Message::Notification(Notification::Edit { method, view_id })] => {
+                assert_eq!(method, &gesture(0, 0));
+                assert_eq!(view_id, &second);
             }
+            received => panic!("unexpected messages: {:#?}", received),
         }
+
+        // Clicks on the separator are ignored.
+        harness.editor.handle_mouse(click(15, 1));
+        assert_eq!(harness.editor.tabs.active().active_view, Some(second));
+        assert!(harness.core.received().is_empty());
+
+        harness.editor.handle_mouse(click(1, 5));
+        assert_eq!(harness.editor.tabs.active().active_view, Some(first));
+    }
+
+    #[test]
+    fn mouse_wheel() {
+        let text = (0..10).map(|i| format!("{}\n", i)).collect::<String>();
+        let core = FakeCore::default().file("/test/file.txt", &text);
+        let mut harness = Harness::new(core, Some("/test/file.txt"));
+        harness.input(":set mouse=a\n:set scrolloff=1\n");
+        harness.core.received();
+
+        // The cursor is moved when it would be scrolled out of the window.
+        let wheel_down = MouseEvent::Press(MouseButton::WheelDown, 1, 1);
+        harness.editor.handle_mouse(wheel_down);
+        assert_eq!(
+            harness.core.edits(),
+            vec![EditMethod::Scroll(3, 7), gesture(4, 0)]
+        );
+        assert_eq!(harness.lines()[0], "3");
+
+        let wheel_up = MouseEvent::Press(MouseButton::WheelUp, 1, 1);
+        harness
+            .core
+            .update(&ViewId(String::from("view-id-1")), &text, 4);
+        harness.wait();
+        harness.editor.handle_mouse(wheel_up);
+        assert_eq!(harness.core.edits(), vec![EditMethod::Scroll(0, 4)]);
+        assert_eq!(harness.lines()[0], "0");
     }
 }
@@ -1,11 +1,32 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use euclid::Rect;
+use failure::Fail;
 
 use super::styles::{Style, Styles};
-use crate::screen::{Coordinate, Screen};
+use crate::screen::{str_width, Coordinate, Screen};
 
 #[derive(Debug, Default)]
 pub struct CommandLine {
     buf: String,
+
+    /// The state of tab completion, if tab was the last key pressed.
+    completion: Option<Completion>,
+}
+
+/// The candidates for completing the argument of a command.
+///
+/// Like vim, pressing tab repeatedly cycles through each candidate, and then back to the original
+/// text.
+#[derive(Debug)]
+struct Completion {
+    /// The offset of the argument in the command line.
+    start: usize,
+    original: String,
+    candidates: Vec<String>,
+    index: Option<usize>,
 }
 
 impl CommandLine {
@@ -14,35 +35,510 @@ impl CommandLine {
     }
 
     pub fn insert(&mut self, c: char) {
+        self.completion = None;
         self.buf.push(c);
     }
 
     pub fn delete(&mut self) {
+        self.completion = None;
         self.buf.pop();
     }
 
+    /// Completes the argument of the command with the next candidate.
+    ///
+    /// Only `:colorscheme` has candidates, which are the names of the available themes. Theme
+    /// names are matched case-insensitively.
+    pub fn complete(&mut self, themes: &[String]) {
+        if self.completion.is_none() {
+            let start = match self.buf.find(char::is_whitespace) {
+                Some(idx) if ["colo", "colorscheme"].contains(&&self.buf[..idx]) => {
+                    idx + self.buf[idx..].len() - self.buf[idx..].trim_start().len()
+                }
+                _ => return,
+            };
+
+            let original = String::from(&self.buf[start..]);
+            let prefix = original.to_lowercase();
+            let candidates = themes
+                .iter()
+                .filter(|theme| theme.to_lowercase().starts_with(&prefix))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if candidates.is_empty() {
+                return;
+            }
+
+            self.completion = Some(Completion {
+                start,
+                original,
+                candidates,
+                index: None,
+            });
+        }
+
+        let completion = self.completion.as_mut().unwrap();
+        completion.index = match completion.index {
+            None => Some(0),
+            Some(i) if i + 1 < completion.candidates.len() => Some(i + 1),
+            Some(_) => None,
+        };
+
+        let text = match completion.index {
+            Some(i) => &completion.candidates[i],
+            None => &completion.original,
+        };
+        self.buf.truncate(completion.start);
+        self.buf.push_str(text);
+    }
+
     pub fn command(&self) -> &str {
         &self.buf
     }
 
-    pub fn render(&self, styles: &Styles, bounds: Rect<usize>, screen: &mut Screen) {
+    pub fn render<W: Write>(&self, styles: &Styles, bounds: Rect<usize>, screen: &mut Screen<W>) {
         let mut line = String::from(":");
         line.push_str(&self.buf);
         screen.erase_line(bounds.origin.y);
         screen.write_str(bounds.origin, &line);
-        screen.draw_cursor(Coordinate {
-            x: bounds.origin.x + line.chars().count(),
-            ..bounds.origin
-        });
         screen.apply_style(
             bounds.origin,
-            line.chars().count(),
+            str_width(&line),
             &Style {
                 fg: styles.fg,
                 bg: styles.bg,
                 ..Default::default()
             },
         );
+        screen.set_cursor(Some(Coordinate {
+            x: bounds.origin.x + str_width(&line),
+            ..bounds.origin
+        }));
         screen.refresh().unwrap();
     }
 }
+
+/// An ex command entered on the command line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    Colorscheme(Option<String>),
+    Set(Vec<String>),
+    SetLocal(Vec<String>),
+    TabNew(Option<PathBuf>),
+    TabClose,
+    TabNext,
+    TabPrevious,
+    Suspend,
+
+    /// Runs a shell command, or an interactive shell, displaying its output on the terminal.
+    Shell(Option<String>),
+
+    /// Inserts the output of a shell command after the last line of the range, or after the line
+    /// of the cursor.
+    Read {
+        range: Option<Range>,
+        command: String,
+    },
+
+    /// Replaces a range of lines with the output of a shell command that reads them.
+    Filter {
+        range: Range,
+        command: String,
+    },
+}
+
+/// A range of lines, such as `%` or `.,$`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Address,
+    pub end: Address,
+}
+
+impl Range {
+    /// Returns the 0-based, inclusive lines of the range, given the line of the cursor and the
+    /// number of lines in the buffer. Returns `None` if the range isn't within the buffer.
+    pub fn lines(self, current: usize, len: usize) -> Option<(usize, usize)> {
+        let start = self.start.line(current, len)?;
+        let end = self.end.line(current, len)?;
+
+        // Like vim, backwards ranges are swapped.
+        Some((start.min(end), start.max(end)))
+    }
+}
+
+/// A line in a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    /// A line number, starting from 1.
+    Line(usize),
+
+    /// The line of the cursor (`.`), plus an offset.
+    Current(isize),
+
+    /// The last line (`$`), plus an offset.
+    Last(isize),
+}
+
+impl Address {
+    fn line(self, current: usize, len: usize) -> Option<usize> {
+        let line = match self {
+            Address::Line(line) => line as isize - 1,
+            Address::Current(offset) => current as isize + offset,
+            Address::Last(offset) => len as isize - 1 + offset,
+        };
+
+        if line >= 0 && (line as usize) < len {
+            Some(line as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum CommandError {
+    #[fail(display = "Not an editor command: {}", _0)]
+    Unknown(String),
+
+    #[fail(display = "Trailing characters: {}", _0)]
+    TrailingCharacters(String),
+
+    #[fail(display = "Argument required")]
+    ArgumentRequired,
+
+    #[fail(display = "No range allowed")]
+    NoRangeAllowed,
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, s) = parse_range(s.trim());
+        let s = s.trim();
+
+        if let Some(command) = strip_prefix(s, '!') {
+            let command = shell_command(command)?;
+            return Ok(match range {
+                Some(range) => Command::Filter { range, command },
+                None => Command::Shell(Some(command)),
+            });
+        }
+
+        // Like vim, `:r!cmd` doesn't need a space before the `!`.
+        let (name, arg) = match s.find(|c: char| c.is_whitespace() || c == '!') {
+            Some(idx) => (&s[..idx], Some(s[idx..].trim_start())),
+            None => (s, None),
+        };
+
+        if let "r" | "read" = name {
+            return match arg.map(|arg| strip_prefix(arg, '!')) {
+                Some(Some(command)) => Ok(Command::Read {
+                    range,
+                    command: shell_command(command)?,
+                }),
+                // Reading files isn't supported.
+                Some(None) => Err(CommandError::Unknown(String::from(s))),
+                None => Err(CommandError::ArgumentRequired),
+            };
+        }
+
+        if range.is_some() {
+            return Err(CommandError::NoRangeAllowed);
+        }
+
+        let command = match name {
+            "q" | "quit" => Command::Quit,
+            "colo" | "colorscheme" => return Ok(Command::Colorscheme(arg.map(String::from))),
+            "se" | "set" => return Ok(Command::Set(arg.map(split_args).unwrap_or_default())),
+            "setl" | "setlocal" => {
+                return Ok(Command::SetLocal(arg.map(split_args).unwrap_or_default()));
+            }
+            "tabnew" | "tabe" | "tabedit" => return Ok(Command::TabNew(arg.map(PathBuf::from))),
+            "tabc" | "tabclose" => Command::TabClose,
+            "tabn" | "tabnext" => Command::TabNext,
+            "tabp" | "tabprevious" | "tabN" | "tabNext" => Command::TabPrevious,
+            "sus" | "suspend" | "st" | "stop" => Command::Suspend,
+            "sh" | "shell" => Command::Shell(None),
+            _ => return Err(CommandError::Unknown(String::from(s))),
+        };
+
+        match arg {
+            Some(arg) => Err(CommandError::TrailingCharacters(String::from(arg))),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Returns the rest of a string after a leading character, if it starts with it.
+fn strip_prefix(s: &str, prefix: char) -> Option<&str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len_utf8()..])
+    } else {
+        None
+    }
+}
+
+/// Parses the range at the start of a command line, returning the range and the rest of the line.
+fn parse_range(s: &str) -> (Option<Range>, &str) {
+    if let Some(rest) = strip_prefix(s, '%') {
+        let range = Range {
+            start: Address::Line(1),
+            end: Address::Last(0),
+        };
+        return (Some(range), rest);
+    }
+
+    let (start, rest) = match parse_address(s) {
+        Some(parsed) => parsed,
+        None => return (None, s),
+    };
+
+    match strip_prefix(rest, ',').and_then(|rest| parse_address(rest.trim_start())) {
+        Some((end, rest)) => (Some(Range { start, end }), rest),
+        None => (Some(Range { start, end: start }), rest),
+    }
+}
+
+/// Parses a line address, such as `12`, `.+1` or `$`, followed by any number of offsets.
+fn parse_address(s: &str) -> Option<(Address, &str)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+    let (mut address, mut rest) = match s.chars().next()? {
+        '.' => (Address::Current(0), &s[1..]),
+        '$' => (Address::Last(0), &s[1..]),
+        '0'..='9' => {
+            let len = digits(s);
+            (Address::Line(s[..len].parse().ok()?), &s[len..])
+        }
+        // An offset on its own is relative to the cursor.
+        '+' | '-' => (Address::Current(0), s),
+        _ => return None,
+    };
+
+    while let Some(sign) = rest.chars().next().filter(|&c| c == '+' || c == '-') {
+        let len = digits(&rest[1..]);
+        let offset = if len == 0 {
+            1
+        } else {
+            rest[1..=len].parse().ok()?
+        };
+        let offset = if sign == '-' { -offset } else { offset };
+        rest = &rest[1 + len..];
+
+        address = match address {
+            Address::Line(line) => Address::Line((line as isize + offset).max(0) as usize),
+            Address::Current(n) => Address::Current(n + offset),
+            Address::Last(n) => Address::Last(n + offset),
+        };
+    }
+
+    Some((address, rest))
+}
+
+fn shell_command(command: &str) -> Result<String, CommandError> {
+    match command.trim() {
+        "" => Err(CommandError::ArgumentRequired),
+        command => Ok(String::from(command)),
+    }
+}
+
+/// Splits the arguments of a command at whitespace. Like vim, whitespace (or a backslash) can be
+/// included in an argument by preceding it with a backslash.
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars
+                .peek()
+                .map_or(false, |&c| c.is_whitespace() || c == '\\') =>
+            {
+                arg.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if !arg.is_empty() {
+                    args.push(arg);
+                    arg = String::new();
+                }
+            }
+            c => arg.push(c),
+        }
+    }
+
+    if !arg.is_empty() {
+        args.push(arg);
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Address, Command, CommandError, CommandLine, Range};
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("q".parse(), Ok(Command::Quit));
+        assert_eq!("tabnew".parse(), Ok(Command::TabNew(None)));
+        assert_eq!(
+            "tabnew  src/main.rs".parse(),
+            Ok(Command::TabNew(Some(PathBuf::from("src/main.rs"))))
+        );
+        assert_eq!(" tabclose ".parse(), Ok(Command::TabClose));
+        assert_eq!(
+            "colo Solarized (dark)".parse(),
+            Ok(Command::Colorscheme(Some(String::from("Solarized (dark)"))))
+        );
+        assert_eq!(
+            "set nu  rnu".parse(),
+            Ok(Command::Set(vec![String::from("nu"), String::from("rnu")]))
+        );
+        assert_eq!(
+            r"setl sbr=>\  ts=4".parse(),
+            Ok(Command::SetLocal(vec![
+                String::from("sbr=> "),
+                String::from("ts=4")
+            ]))
+        );
+        assert_eq!(
+            r"set path=C:\\x".parse(),
+            Ok(Command::Set(vec![String::from(r"path=C:\x")]))
+        );
+    }
+
+    #[test]
+    fn parse_shell_commands() {
+        assert_eq!("sus".parse(), Ok(Command::Suspend));
+        assert_eq!("sh".parse(), Ok(Command::Shell(None)));
+        assert_eq!(
+            "!ls -l".parse(),
+            Ok(Command::Shell(Some(String::from("ls -l"))))
+        );
+        assert_eq!(
+            "r !date".parse(),
+            Ok(Command::Read {
+                range: None,
+                command: String::from("date"),
+            })
+        );
+        assert_eq!(
+            "$r!date".parse(),
+            Ok(Command::Read {
+                range: Some(Range {
+                    start: Address::Last(0),
+                    end: Address::Last(0),
+                }),
+                command: String::from("date"),
+            })
+        );
+        assert_eq!(
+            "%!sort".parse(),
+            Ok(Command::Filter {
+                range: Range {
+                    start: Address::Line(1),
+                    end: Address::Last(0),
+                },
+                command: String::from("sort"),
+            })
+        );
+        assert_eq!(
+            "2, .+3 ! sort -r".parse(),
+            Ok(Command::Filter {
+                range: Range {
+                    start: Address::Line(2),
+                    end: Address::Current(3),
+                },
+                command: String::from("sort -r"),
+            })
+        );
+        assert_eq!(
+            "-,$--!fmt".parse(),
+            Ok(Command::Filter {
+                range: Range {
+                    start: Address::Current(-1),
+                    end: Address::Last(-2),
+                },
+                command: String::from("fmt"),
+            })
+        );
+    }
+
+    #[test]
+    fn range_lines() {
+        let range = |start, end| Range { start, end };
+        assert_eq!(
+            range(Address::Line(1), Address::Last(0)).lines(4, 10),
+            Some((0, 9))
+        );
+        assert_eq!(
+            range(Address::Current(2), Address::Current(-1)).lines(4, 10),
+            Some((3, 6))
+        );
+        assert_eq!(range(Address::Line(0), Address::Line(2)).lines(4, 10), None);
+        assert_eq!(
+            range(Address::Current(0), Address::Last(1)).lines(4, 10),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "foo".parse::<Command>(),
+            Err(CommandError::Unknown(String::from("foo")))
+        );
+        assert_eq!(
+            "tabclose 1".parse::<Command>(),
+            Err(CommandError::TrailingCharacters(String::from("1")))
+        );
+        assert_eq!(
+            "1,2tabclose".parse::<Command>(),
+            Err(CommandError::NoRangeAllowed)
+        );
+        assert_eq!("!".parse::<Command>(), Err(CommandError::ArgumentRequired));
+        assert_eq!("r".parse::<Command>(), Err(CommandError::ArgumentRequired));
+    }
+
+    #[test]
+    fn complete() {
+        let themes = ["InspiredGitHub", "Solarized (dark)", "Solarized (light)"]
+            .iter()
+            .map(|&theme| String::from(theme))
+            .collect::<Vec<_>>();
+
+        let mut line = CommandLine::new();
+        "colo sol".chars().for_each(|c| line.insert(c));
+
+        line.complete(&themes);
+        assert_eq!(line.command(), "colo Solarized (dark)");
+        line.complete(&themes);
+        assert_eq!(line.command(), "colo Solarized (light)");
+        line.complete(&themes);
+        assert_eq!(line.command(), "colo sol");
+        line.complete(&themes);
+        assert_eq!(line.command(), "colo Solarized (dark)");
+
+        // Typing starts a new completion from the edited text.
+        line.delete();
+        line.delete();
+        line.delete();
+        line.complete(&themes);
+        assert_eq!(line.command(), "colo Solarized (dark)");
+
+        let mut line = CommandLine::new();
+        "colorscheme  ".chars().for_each(|c| line.insert(c));
+        line.complete(&themes);
+        assert_eq!(line.command(), "colorscheme  InspiredGitHub");
+
+        let mut line = CommandLine::new();
+        "set sol".chars().for_each(|c| line.insert(c));
+        line.complete(&themes);
+        assert_eq!(line.command(), "set sol");
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::protocol::ConfigChanges;
+
+/// The configuration of a view, as determined by the core.
+///
+/// The core sends the full configuration when a view is opened, and only the settings that changed
+/// afterwards, so changes are merged into the existing configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// The number of columns between tabstops.
+    pub tab_size: usize,
+
+    /// Whether inserting a tab inserts spaces instead.
+    pub translate_tabs_to_spaces: bool,
+
+    /// The column at which the core wraps lines, or zero if the core doesn't wrap lines.
+    pub wrap_width: usize,
+
+    /// Whether new lines are indented to match the previous line.
+    pub auto_indent: bool,
+
+    /// The line ending used when the buffer is saved.
+    pub line_ending: String,
+
+    /// Whether the last line of the buffer may be scrolled to the top of the window.
+    pub scroll_past_end: bool,
+
+    /// Settings that aren't interpreted by the frontend.
+    pub other: BTreeMap<String, Value>,
+}
+
+impl Default for Config {
+    /// The defaults used by the core.
+    fn default() -> Self {
+        Config {
+            tab_size: 4,
+            translate_tabs_to_spaces: true,
+            wrap_width: 0,
+            auto_indent: true,
+            line_ending: String::from("\n"),
+            scroll_past_end: false,
+            other: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Merges a set of changes into the configuration.
+    pub fn apply(&mut self, changes: ConfigChanges) {
+        if let Some(tab_size) = changes.tab_size {
+            self.tab_size = tab_size.max(1);
+        }
+
+        if let Some(translate_tabs_to_spaces) = changes.translate_tabs_to_spaces {
+            self.translate_tabs_to_spaces = translate_tabs_to_spaces;
+        }
+
+        if let Some(wrap_width) = changes.wrap_width {
+            self.wrap_width = wrap_width;
+        }
+
+        if let Some(auto_indent) = changes.auto_indent {
+            self.auto_indent = auto_indent;
+        }
+
+        if let Some(line_ending) = changes.line_ending {
+            self.line_ending = line_ending;
+        }
+
+        if let Some(scroll_past_end) = changes.scroll_past_end {
+            self.scroll_past_end = scroll_past_end;
+        }
+
+        self.other.extend(changes.other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, json_internal};
+
+    use super::Config;
+    use crate::protocol::ConfigChanges;
+
+    #[test]
+    fn apply() {
+        let mut config = Config::default();
+
+        let changes = serde_json::from_value::<ConfigChanges>(json!({
+            "tab_size": 8,
+            "translate_tabs_to_spaces": false,
+            "line_ending": "\r\n",
+            "font_face": "InconsolataGo",
+            "font_size": 14,
+        }))
+        .unwrap();
+        config.apply(changes);
+
+        assert_eq!(config.tab_size, 8);
+        assert!(!config.translate_tabs_to_spaces);
+        assert_eq!(config.line_ending, "\r\n");
+        assert!(config.auto_indent);
+        assert_eq!(config.other["font_size"], json!(14));
+
+        // Settings that are not part of the change are left alone.
+        let changes = serde_json::from_value::<ConfigChanges>(json!({
+            "tab_size": 2,
+        }))
+        .unwrap();
+        config.apply(changes);
+
+        assert_eq!(config.tab_size, 2);
+        assert!(!config.translate_tabs_to_spaces);
+        assert_eq!(config.other.len(), 2);
+    }
+}
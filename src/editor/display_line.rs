@@ -0,0 +1,193 @@
+//! Layout of buffer lines into terminal columns.
+//!
+//! The core describes positions within a line (such as cursors and style spans) as UTF-8 offsets,
+//! but the terminal operates in columns. A single column may contain several characters (such as a
+//! letter followed by combining marks), and some characters occupy two columns. Tabs are expanded
+//! to the next tabstop.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::screen::grapheme_width;
+
+/// A grapheme cluster within a line, and its position on the screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster<'a> {
+    pub text: &'a str,
+
+    /// The UTF-8 offset of the cluster within the line.
+    pub offset: usize,
+
+    /// The column at which the cluster starts.
+    pub column: usize,
+
+    /// The number of columns occupied by the cluster.
+    pub width: usize,
+}
+
+impl<'a> Cluster<'a> {
+    pub fn is_whitespace(&self) -> bool {
+        self.text.chars().all(char::is_whitespace)
+    }
+
+    pub fn is_tab(&self) -> bool {
+        self.text == "\t"
+    }
+}
+
+/// A line of text, split into grapheme clusters.
+#[derive(Debug)]
+pub struct DisplayLine<'a> {
+    clusters: Vec<Cluster<'a>>,
+    len: usize,
+    width: usize,
+}
+
+impl<'a> DisplayLine<'a> {
+    /// Lays out a line of text, with tabstops every `tabstop` columns. The text should not contain
+    /// a trailing newline.
+    pub fn new(text: &'a str, tabstop: usize) -> Self {
+        let tabstop = tabstop.max(1);
+        let mut column = 0;
+        let clusters = text
+            .grapheme_indices(true)
+            .map(|(offset, text)| {
+                let width = if text == "\t" {
+                    tabstop - column % tabstop
+                } else {
+                    grapheme_width(text)
+                };
+                let cluster = Cluster {
+                    text,
+                    offset,
+                    column,
+                    width,
+                };
+                column += cluster.width;
+                cluster
+            })
+            .collect();
+
+        DisplayLine {
+            clusters,
+            len: text.len(),
+            width: column,
+        }
+    }
+
+    pub fn clusters(&self) -> &[Cluster<'a>] {
+        &self.clusters
+    }
+
+    /// The number of columns occupied by the line.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the column of the cluster containing a UTF-8 offset.
+    ///
+    /// Offsets past the end of the line (such as the trailing newline) occupy a column each.
+    pub fn column_of(&self, offset: usize) -> usize {
+        if offset >= self.len {
+            return self.width + (offset - self.len);
+        }
+
+        match self
+            .clusters
+            .binary_search_by_key(&offset, |cluster| cluster.offset)
+        {
+            Ok(idx) => self.clusters[idx].column,
+            Err(idx) => self.clusters[idx - 1].column,
+        }
+    }
+
+    /// Returns the column of a UTF-8 offset that marks the end of a range.
+    ///
+    /// Unlike `column_of`, offsets inside a cluster are rounded up to the end of the cluster.
+    pub fn end_column_of(&self, offset: usize) -> usize {
+        if offset >= self.len {
+            return self.width + (offset - self.len);
+        }
+
+        match self
+            .clusters
+            .binary_search_by_key(&offset, |cluster| cluster.offset)
+        {
+            Ok(idx) => self.clusters[idx].column,
+            Err(idx) => self.clusters[idx - 1].column + self.clusters[idx - 1].width,
+        }
+    }
+
+    /// Returns the UTF-8 offset of the cluster that occupies a column.
+    ///
+    /// Columns past the end of the line return the length of the line.
+    pub fn offset_at(&self, column: usize) -> usize {
+        self.clusters
+            .iter()
+            .find(|cluster| column < cluster.column + cluster.width)
+            .map(|cluster| cluster.offset)
+            .unwrap_or(self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DisplayLine;
+
+    #[test]
+    fn ascii() {
+        let line = DisplayLine::new("hello", 8);
+        assert_eq!(line.clusters().len(), 5);
+        assert_eq!(line.column_of(3), 3);
+        assert_eq!(line.column_of(5), 5);
+        assert_eq!(line.column_of(6), 6);
+        assert_eq!(line.offset_at(2), 2);
+        assert_eq!(line.offset_at(10), 5);
+    }
+
+    #[test]
+    fn cjk() {
+        // Each character is three bytes and two columns wide.
+        let line = DisplayLine::new("a日本b", 8);
+        assert_eq!(line.column_of(1), 1);
+        assert_eq!(line.column_of(4), 3);
+        assert_eq!(line.column_of(7), 5);
+        assert_eq!(line.column_of(8), 6);
+        assert_eq!(line.end_column_of(2), 3);
+        assert_eq!(line.offset_at(2), 1);
+        assert_eq!(line.offset_at(3), 4);
+    }
+
+    #[test]
+    fn combining() {
+        // "é" is written as "e" followed by U+0301, which is two bytes.
+        let line = DisplayLine::new("e\u{301}x", 8);
+        assert_eq!(line.clusters().len(), 2);
+        assert_eq!(line.column_of(0), 0);
+        assert_eq!(line.column_of(1), 0);
+        assert_eq!(line.column_of(3), 1);
+        assert_eq!(line.offset_at(1), 3);
+    }
+
+    #[test]
+    fn tabs() {
+        let line = DisplayLine::new("\tab\tc", 4);
+        assert_eq!(line.width(), 9);
+        assert_eq!(line.clusters()[0].width, 4);
+        assert_eq!(line.clusters()[3].width, 2);
+        assert_eq!(line.column_of(1), 4);
+        assert_eq!(line.column_of(4), 8);
+        assert_eq!(line.offset_at(2), 0);
+        assert_eq!(line.offset_at(7), 3);
+    }
+
+    #[test]
+    fn emoji() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let text = format!("{}!", family);
+        let line = DisplayLine::new(&text, 8);
+        assert_eq!(line.clusters().len(), 2);
+        assert_eq!(line.column_of(family.len()), 2);
+        assert_eq!(line.end_column_of(4), 2);
+        assert_eq!(line.offset_at(1), 0);
+    }
+}
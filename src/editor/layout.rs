@@ -5,45 +5,164 @@ use log::*;
 
 use crate::protocol::ViewId;
 
+/// Arranges the windows of a single tab page within an area of the screen.
+///
+/// The last row of the area is always reserved for the command line, and the last row of each
+/// window is reserved for its status line.
 #[derive(Debug)]
 pub struct Layout {
-    screen: Size2D<usize>,
+    bounds: Rect<usize>,
     windows: HashMap<ViewId, Rect<usize>>,
 }
 
 impl Layout {
-    pub fn new(screen_size: Size2D<usize>) -> Self {
+    pub fn new(bounds: Rect<usize>) -> Self {
         Layout {
-            screen: screen_size,
+            bounds,
             windows: Default::default(),
         }
     }
 
+    /// Adds a window for a view to the layout, returning the bounds of its text area.
     pub fn add_view(&mut self, view_id: &ViewId) -> Rect<usize> {
         if self.windows.len() >= 1 {
             unimplemented!("only one view is supported");
         }
 
         // TODO: implement some fancy placement logic here.
-        let rect = Rect::from_size(Size2D { height: self.screen.height - 1, ..self.screen });
+        let rect = self.window_area();
 
         self.windows.insert(view_id.clone(), rect);
 
         info!("created window at {:?}", rect);
 
-        rect
+        self.of_view(view_id)
     }
 
-    /// Returns a bounding rectangle for the given view.
+    /// Places a window at a position on the screen, since `add_view` can't split windows yet.
+    #[cfg(test)]
+    pub fn place_view(&mut self, view_id: &ViewId, rect: Rect<usize>) {
+        self.windows.insert(view_id.clone(), rect);
+    }
+
+    /// Replaces views with others, keeping their windows. The views are replaced at once, so a
+    /// view can be replaced by one that another view is replaced with.
+    pub fn replace_views(&mut self, replacements: &HashMap<ViewId, ViewId>) {
+        self.windows = self
+            .windows
+            .drain()
+            .map(|(view_id, rect)| match replacements.get(&view_id) {
+                Some(new) => (new.clone(), rect),
+                None => (view_id, rect),
+            })
+            .collect();
+    }
+
+    /// Changes the area of the screen that the layout occupies, and recomputes the bounds of each
+    /// window.
+    pub fn resize(&mut self, bounds: Rect<usize>) {
+        self.bounds = bounds;
+
+        let area = self.window_area();
+        for rect in self.windows.values_mut() {
+            *rect = area;
+        }
+
+        info!("resized layout to {:?}", bounds);
+    }
+
+    /// Returns true if the layout contains a window for the given view.
+    pub fn contains(&self, view_id: &ViewId) -> bool {
+        self.windows.contains_key(view_id)
+    }
+
+    /// Returns an iterator over the views in the layout.
+    pub fn views(&self) -> impl Iterator<Item = &ViewId> {
+        self.windows.keys()
+    }
+
+    /// Returns the view whose window, including its status line, contains a point on the screen.
+    pub fn view_at(&self, point: Point2D<usize>) -> Option<&ViewId> {
+        self.windows
+            .iter()
+            .find(|(_, rect)| rect.contains(&point))
+            .map(|(view_id, _)| view_id)
+    }
+
+    /// Returns a bounding rectangle for the text area of the given view.
     ///
     /// # Panics
     ///
     /// Panics if the view id is not contained in the layout.
     pub fn of_view(&self, view_id: &ViewId) -> Rect<usize> {
-        self.windows[view_id]
+        let window = self.windows[view_id];
+        Rect::new(
+            window.origin,
+            Size2D {
+                height: window.size.height - 1,
+                ..window.size
+            },
+        )
+    }
+
+    /// Returns a bounding rectangle for the status line of the given view.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the view id is not contained in the layout.
+    pub fn of_status_line(&self, view_id: &ViewId) -> Rect<usize> {
+        let window = self.windows[view_id];
+        Rect::new(
+            Point2D::new(window.min_x(), window.max_y() - 1),
+            Size2D::new(window.size.width, 1),
+        )
     }
 
     pub fn of_command_line(&self) -> Rect<usize> {
-        Rect::new(Point2D::new(0, self.screen.height - 1), Size2D::new(self.screen.width, 1))
+        Rect::new(
+            Point2D::new(self.bounds.min_x(), self.bounds.max_y() - 1),
+            Size2D::new(self.bounds.size.width, 1),
+        )
+    }
+
+    /// The area available to windows, which excludes the command line.
+    fn window_area(&self) -> Rect<usize> {
+        Rect::new(
+            self.bounds.origin,
+            Size2D {
+                height: self.bounds.size.height - 1,
+                ..self.bounds.size
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::{Point2D, Rect, Size2D};
+
+    use super::Layout;
+    use crate::protocol::ViewId;
+
+    #[test]
+    fn view_at() {
+        let mut layout = Layout::new(Rect::from_size(Size2D::new(20, 6)));
+        let left = ViewId(String::from("view-id-1"));
+        let right = ViewId(String::from("view-id-2"));
+        layout.add_view(&left);
+
+        // The status line is part of the window, but the command line isn't.
+        assert_eq!(layout.view_at(Point2D::new(19, 0)), Some(&left));
+        assert_eq!(layout.view_at(Point2D::new(3, 4)), Some(&left));
+        assert_eq!(layout.view_at(Point2D::new(3, 5)), None);
+
+        // Side by side windows, with a separator column between them.
+        layout.place_view(&left, Rect::from_size(Size2D::new(10, 5)));
+        layout.place_view(&right, Rect::new(Point2D::new(11, 0), Size2D::new(9, 5)));
+        assert_eq!(layout.view_at(Point2D::new(9, 2)), Some(&left));
+        assert_eq!(layout.view_at(Point2D::new(10, 2)), None);
+        assert_eq!(layout.view_at(Point2D::new(11, 2)), Some(&right));
+        assert_eq!(layout.view_at(Point2D::new(19, 4)), Some(&right));
+        assert_eq!(layout.view_at(Point2D::new(20, 2)), None);
     }
 }
@@ -3,6 +3,7 @@ use std::ops::{Bound, RangeBounds};
 use std::slice::Chunks;
 
 use log::*;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::protocol::{self, OpKind, Update};
 use crate::screen::Coordinate;
@@ -96,16 +97,29 @@ impl LineCache {
         }
     }
 
-    /// True if a given terminal coordinate is at the end of a line in the cache.
+    /// True if a given buffer coordinate is at the end of a line in the cache.
+    ///
+    /// The x coordinate is a UTF-8 offset into the line, like the cursors sent by the core.
     pub fn is_eol(&self, coordinate: &Coordinate) -> bool {
         self.lines
             .iter()
             .nth(usize::from(coordinate.y))
-            .and_then(|line| line.text.chars().nth(usize::from(coordinate.x)))
-            .map(|c| c == '\n')
+            .and_then(|line| line.text.get(usize::from(coordinate.x)..))
+            .map(|rest| rest.starts_with('\n'))
             .unwrap_or_default()
     }
 
+    /// Returns the grapheme cluster at a buffer coordinate, or `None` at the end of a line.
+    pub fn grapheme_at(&self, coordinate: &Coordinate) -> Option<&str> {
+        self.lines
+            .get(usize::from(coordinate.y))?
+            .text
+            .get(usize::from(coordinate.x)..)?
+            .graphemes(true)
+            .next()
+            .filter(|grapheme| !grapheme.starts_with('\n'))
+    }
+
     fn ins(&mut self, lines: Vec<protocol::Line>) {
         debug!("inserting {} lines", lines.len());
         self.lines.extend(lines.into_iter().map(|line| Line {
@@ -237,6 +251,21 @@ impl LineCache {
         Some(self.lines.iter().skip(start).take(num))
     }
 
+    /// Returns the line at an index, or `None` if the line is invalid or out of bounds.
+    pub fn get(&self, idx: usize) -> Option<&Line> {
+        let idx = idx.checked_sub(self.invalid_before as usize)?;
+        self.lines.get(idx)
+    }
+
+    /// Returns the text of every line, or `None` if any lines are invalid.
+    pub fn text(&self) -> Option<String> {
+        if self.invalid_before > 0 || self.invalid_after > 0 {
+            return None;
+        }
+
+        Some(self.lines.iter().map(|line| line.text.as_str()).collect())
+    }
+
     /// Returns the total number of lines in the cache, including invalid lines.
     pub fn len(&self) -> usize {
         self.invalid_before as usize + self.lines.len() + self.invalid_after as usize
@@ -376,6 +405,51 @@ mod tests {
 
         assert!(cache.is_eol(&Coordinate::new(13, 0)));
         assert!(!cache.is_eol(&Coordinate::new(10, 0)));
+
+        cache.lines[0].text = String::from("Grüße\n");
+        assert!(cache.is_eol(&Coordinate::new(7, 0)));
+        assert!(!cache.is_eol(&Coordinate::new(5, 0)));
+    }
+
+    #[test]
+    fn grapheme_at() {
+        let mut cache = LineCache::new();
+        cache.lines = vec![
+            super::Line {
+                text: String::from("cafe\u{301}!\n"),
+                ..Default::default()
+            },
+            super::Line {
+                text: String::from("end"),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(cache.grapheme_at(&Coordinate::new(0, 0)), Some("c"));
+        assert_eq!(cache.grapheme_at(&Coordinate::new(3, 0)), Some("e\u{301}"));
+        assert_eq!(cache.grapheme_at(&Coordinate::new(6, 0)), Some("!"));
+        assert_eq!(cache.grapheme_at(&Coordinate::new(7, 0)), None);
+        assert_eq!(cache.grapheme_at(&Coordinate::new(3, 1)), None);
+        assert_eq!(cache.grapheme_at(&Coordinate::new(0, 2)), None);
+    }
+
+    #[test]
+    fn text() {
+        let mut cache = LineCache::new();
+        cache.lines = vec![
+            super::Line {
+                text: String::from("Hello, world!\n"),
+                ..Default::default()
+            },
+            super::Line {
+                text: String::from("Goodbye, world!"),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(cache.text().unwrap(), "Hello, world!\nGoodbye, world!");
+
+        cache.invalid_after = 1;
+        assert_eq!(cache.text(), None);
     }
 
     #[test]
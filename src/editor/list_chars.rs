@@ -0,0 +1,167 @@
+//! The characters used to display whitespace when the `list` option is set.
+//!
+//! The format is the same as vim's `listchars` option: a comma-separated list of `name:value`
+//! items. The following items are supported:
+//!
+//! | Item        | Meaning                                                                  |
+//! |-------------|--------------------------------------------------------------------------|
+//! | `tab:xy`    | A tab is shown as `x`, followed by as many `y` as needed to fill the tab |
+//! | `trail:c`   | Trailing spaces are shown as `c`                                         |
+//! | `eol:c`     | The end of each line is marked with `c`                                  |
+//!
+//! If `tab` is omitted, tabs are displayed as spaces.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use failure::Fail;
+
+/// An error encountered while parsing a `listchars` string.
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum ParseError {
+    #[fail(display = "unknown listchars item: {}", _0)]
+    UnknownItem(String),
+
+    #[fail(display = "invalid value for listchars item: {}", _0)]
+    InvalidValue(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListChars {
+    pub tab: Option<(char, char)>,
+    pub trail: Option<char>,
+    pub eol: Option<char>,
+}
+
+impl ListChars {
+    /// Returns the text displayed in place of a tab that is `width` columns wide.
+    pub fn tab(&self, width: usize) -> String {
+        match self.tab {
+            Some((first, fill)) => {
+                let mut text = first.to_string();
+                text.extend((1..width).map(|_| fill));
+                text
+            }
+            None => (0..width).map(|_| ' ').collect(),
+        }
+    }
+}
+
+impl Default for ListChars {
+    /// Like vim, only the end of the line is marked by default.
+    fn default() -> Self {
+        ListChars {
+            tab: None,
+            trail: None,
+            eol: Some('$'),
+        }
+    }
+}
+
+impl Display for ListChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut items = vec![];
+        if let Some((first, fill)) = self.tab {
+            items.push(format!("tab:{}{}", first, fill));
+        }
+        if let Some(trail) = self.trail {
+            items.push(format!("trail:{}", trail));
+        }
+        if let Some(eol) = self.eol {
+            items.push(format!("eol:{}", eol));
+        }
+        write!(f, "{}", items.join(","))
+    }
+}
+
+impl FromStr for ListChars {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut list_chars = ListChars {
+            tab: None,
+            trail: None,
+            eol: None,
+        };
+
+        for item in s.split(',').filter(|item| !item.is_empty()) {
+            let idx = item
+                .find(':')
+                .ok_or_else(|| ParseError::UnknownItem(item.to_string()))?;
+            let (name, value) = (&item[..idx], &item[idx + 1..]);
+            let chars = value.chars().collect::<Vec<_>>();
+
+            match (name, chars.as_slice()) {
+                ("tab", &[first, fill]) => list_chars.tab = Some((first, fill)),
+                ("trail", &[c]) => list_chars.trail = Some(c),
+                ("eol", &[c]) => list_chars.eol = Some(c),
+                ("tab", _) | ("trail", _) | ("eol", _) => {
+                    return Err(ParseError::InvalidValue(item.to_string()));
+                }
+                _ => return Err(ParseError::UnknownItem(item.to_string())),
+            }
+        }
+
+        Ok(list_chars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListChars, ParseError};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "tab:>-,trail:~,eol:$".parse(),
+            Ok(ListChars {
+                tab: Some(('>', '-')),
+                trail: Some('~'),
+                eol: Some('$'),
+            })
+        );
+        assert_eq!(
+            "".parse(),
+            Ok(ListChars {
+                tab: None,
+                trail: None,
+                eol: None,
+            })
+        );
+        assert_eq!(
+            "tab:»·".parse::<ListChars>().map(|lcs| lcs.tab),
+            Ok(Some(('»', '·')))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "space:.".parse::<ListChars>(),
+            Err(ParseError::UnknownItem(String::from("space:.")))
+        );
+        assert_eq!(
+            "eol".parse::<ListChars>(),
+            Err(ParseError::UnknownItem(String::from("eol")))
+        );
+        assert_eq!(
+            "tab:>".parse::<ListChars>(),
+            Err(ParseError::InvalidValue(String::from("tab:>")))
+        );
+    }
+
+    #[test]
+    fn display() {
+        let list_chars = "eol:$,tab:>-".parse::<ListChars>().unwrap();
+        assert_eq!(list_chars.to_string(), "tab:>-,eol:$");
+        assert_eq!(ListChars::default().to_string(), "eol:$");
+    }
+
+    #[test]
+    fn tab() {
+        let list_chars = "tab:>-".parse::<ListChars>().unwrap();
+        assert_eq!(list_chars.tab(4), ">---");
+        assert_eq!(list_chars.tab(1), ">");
+        assert_eq!(ListChars::default().tab(2), "  ");
+    }
+}
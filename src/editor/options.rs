@@ -0,0 +1,636 @@
+//! Options that change the behavior of the editor, which are set with `:set` and `:setlocal`.
+//!
+//! Each option is described by a [`Descriptor`], which determines the type of its value and where
+//! the value is stored. Options that only affect the frontend are stored in [`GlobalOptions`] or in
+//! the [`WindowOptions`] of each window. Options that correspond to the core's configuration (such
+//! as `tabstop`) are forwarded to the core, which sends the new configuration to each view that it
+//! affects.
+
+use std::fmt::{self, Display};
+
+use failure::Fail;
+
+use super::list_chars::ListChars;
+use super::status_line::StatusLine;
+use super::window::Window;
+use crate::protocol::ConfigChanges;
+
+/// An error encountered while setting an option.
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum OptionError {
+    #[fail(display = "E518: Unknown option: {}", _0)]
+    Unknown(String),
+
+    #[fail(display = "E474: Invalid argument: {}", _0)]
+    InvalidArgument(String),
+
+    #[fail(display = "E521: Number required after =: {}", _0)]
+    NumberRequired(String),
+
+    #[fail(display = "E487: Argument must be positive: {}", _0)]
+    NotPositive(String),
+}
+
+/// The type of an option's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Boolean,
+    Number,
+    String,
+
+    /// A comma-separated list of strings.
+    List,
+}
+
+/// Where the value of an option is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// The option has a single value for the whole editor.
+    Global,
+
+    /// Each window has its own value. `:set` changes the value for the current window and for
+    /// windows that are opened later, while `:setlocal` only changes the current window.
+    Window,
+
+    /// The option is part of the core's configuration, under the given key. `:set` changes the
+    /// configuration of every view, while `:setlocal` only changes the current view.
+    Core(&'static str),
+}
+
+/// The name, type and scope of an option.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Descriptor {
+    pub name: &'static str,
+    pub abbreviation: &'static str,
+    pub kind: Kind,
+    pub scope: Scope,
+}
+
+/// Every option supported by the editor, in alphabetical order.
+pub const OPTIONS: &[Descriptor] = &[
+    Descriptor {
+        name: "autoindent",
+        abbreviation: "ai",
+        kind: Kind::Boolean,
+        scope: Scope::Core("auto_indent"),
+    },
+    Descriptor {
+        name: "cursorline",
+        abbreviation: "cul",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "expandtab",
+        abbreviation: "et",
+        kind: Kind::Boolean,
+        scope: Scope::Core("translate_tabs_to_spaces"),
+    },
+    Descriptor {
+        name: "linebreak",
+        abbreviation: "lbr",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "list",
+        abbreviation: "list",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "listchars",
+        abbreviation: "lcs",
+        kind: Kind::List,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "mouse",
+        abbreviation: "mouse",
+        kind: Kind::String,
+        scope: Scope::Global,
+    },
+    Descriptor {
+        name: "number",
+        abbreviation: "nu",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "relativenumber",
+        abbreviation: "rnu",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "scrolloff",
+        abbreviation: "so",
+        kind: Kind::Number,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "showbreak",
+        abbreviation: "sbr",
+        kind: Kind::String,
+        scope: Scope::Window,
+    },
+    Descriptor {
+        name: "statusline",
+        abbreviation: "stl",
+        kind: Kind::String,
+        scope: Scope::Global,
+    },
+    Descriptor {
+        name: "tabstop",
+        abbreviation: "ts",
+        kind: Kind::Number,
+        scope: Scope::Core("tab_size"),
+    },
+    Descriptor {
+        name: "textwidth",
+        abbreviation: "tw",
+        kind: Kind::Number,
+        scope: Scope::Core("wrap_width"),
+    },
+    Descriptor {
+        name: "wrap",
+        abbreviation: "wrap",
+        kind: Kind::Boolean,
+        scope: Scope::Window,
+    },
+];
+
+/// Finds an option by its name or abbreviation.
+pub fn find(name: &str) -> Option<&'static Descriptor> {
+    OPTIONS
+        .iter()
+        .find(|option| option.name == name || option.abbreviation == name)
+}
+
+/// The value of an option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Boolean(bool),
+    Number(usize),
+    String(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A single argument to `:set`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Argument<'a> {
+    /// `all`: show the value of every option.
+    All,
+
+    /// `opt?`: show the value of an option.
+    Show(&'a str),
+
+    /// `opt`: enable a boolean option, or show the value of any other option.
+    Enable(&'a str),
+
+    /// `noopt`: disable a boolean option.
+    Disable(&'a str),
+
+    /// `opt=val`: set the value of an option.
+    Assign(&'a str, &'a str),
+
+    /// `opt+=val`: add to a number, append to a string, or add an item to a list.
+    Append(&'a str, &'a str),
+}
+
+impl<'a> Argument<'a> {
+    pub fn parse(arg: &'a str) -> Self {
+        if arg == "all" {
+            return Argument::All;
+        }
+
+        if arg.ends_with('?') {
+            return Argument::Show(&arg[..arg.len() - 1]);
+        }
+
+        if let Some(idx) = arg.find('=') {
+            let (name, value) = (&arg[..idx], &arg[idx + 1..]);
+            return if name.ends_with('+') {
+                Argument::Append(&name[..name.len() - 1], value)
+            } else {
+                Argument::Assign(name, value)
+            };
+        }
+
+        if arg.starts_with("no") && find(arg).is_none() {
+            Argument::Disable(&arg[2..])
+        } else {
+            Argument::Enable(arg)
+        }
+    }
+
+    /// The name of the option that the argument refers to, as it was typed.
+    pub fn name(&self) -> &'a str {
+        match *self {
+            Argument::All => "all",
+            Argument::Show(name)
+            | Argument::Enable(name)
+            | Argument::Disable(name)
+            | Argument::Assign(name, _)
+            | Argument::Append(name, _) => name,
+        }
+    }
+}
+
+impl<'a> Display for Argument<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Argument::All => write!(f, "all"),
+            Argument::Show(name) => write!(f, "{}?", name),
+            Argument::Enable(name) => write!(f, "{}", name),
+            Argument::Disable(name) => write!(f, "no{}", name),
+            Argument::Assign(name, value) => write!(f, "{}={}", name, value),
+            Argument::Append(name, value) => write!(f, "{}+={}", name, value),
+        }
+    }
+}
+
+impl Descriptor {
+    /// Returns the value of the option, as seen from a window.
+    pub fn value(&self, global: &GlobalOptions, window: &Window) -> Value {
+        let value = match self.scope {
+            Scope::Global => global.get(self.name),
+            Scope::Window => window.options.get(self.name),
+            Scope::Core(key) => {
+                let config = &window.config;
+                match key {
+                    "auto_indent" => Some(Value::Boolean(config.auto_indent)),
+                    "tab_size" => Some(Value::Number(config.tab_size)),
+                    "translate_tabs_to_spaces" => {
+                        Some(Value::Boolean(config.translate_tabs_to_spaces))
+                    }
+                    "wrap_width" => Some(Value::Number(config.wrap_width)),
+                    _ => None,
+                }
+            }
+        };
+
+        value.unwrap_or_else(|| panic!("no value for option {}", self.name))
+    }
+
+    /// Computes the new value of the option from an argument to `:set` and the current value.
+    ///
+    /// Returns `None` if the argument only shows the value of the option.
+    pub fn evaluate(
+        &self,
+        argument: Argument<'_>,
+        current: &Value,
+    ) -> Result<Option<Value>, OptionError> {
+        let value = match (argument, self.kind, current) {
+            (Argument::All, _, _) | (Argument::Show(_), _, _) => return Ok(None),
+            (Argument::Enable(_), Kind::Boolean, _) => Value::Boolean(true),
+            (Argument::Enable(_), _, _) => return Ok(None),
+            (Argument::Disable(_), Kind::Boolean, _) => Value::Boolean(false),
+            (Argument::Assign(_, value), Kind::Number, _) => {
+                Value::Number(parse_number(argument, value)?)
+            }
+            (Argument::Append(_, value), Kind::Number, Value::Number(current)) => {
+                Value::Number(current + parse_number(argument, value)?)
+            }
+            (Argument::Assign(_, value), Kind::String, _)
+            | (Argument::Assign(_, value), Kind::List, _) => Value::String(String::from(value)),
+            (Argument::Append(_, value), Kind::String, Value::String(current)) => {
+                Value::String(format!("{}{}", current, value))
+            }
+            (Argument::Append(_, value), Kind::List, Value::String(current)) => {
+                if current.is_empty() {
+                    Value::String(String::from(value))
+                } else {
+                    Value::String(format!("{},{}", current, value))
+                }
+            }
+            _ => return Err(OptionError::InvalidArgument(argument.to_string())),
+        };
+
+        Ok(Some(value))
+    }
+
+    /// Formats the option and its value the way that `:set` displays it.
+    pub fn format(&self, value: &Value) -> String {
+        match value {
+            Value::Boolean(true) => format!("  {}", self.name),
+            Value::Boolean(false) => format!("no{}", self.name),
+            value => format!("  {}={}", self.name, value),
+        }
+    }
+}
+
+fn parse_number(argument: Argument<'_>, value: &str) -> Result<usize, OptionError> {
+    value
+        .parse()
+        .map_err(|_| OptionError::NumberRequired(argument.to_string()))
+}
+
+/// Returns the changes to the core's configuration that set an option owned by the core.
+pub fn core_changes(key: &str, value: &Value) -> Result<ConfigChanges, OptionError> {
+    let json = match value {
+        Value::Number(0) if key == "tab_size" => {
+            return Err(OptionError::NotPositive(format!("tabstop={}", value)));
+        }
+        Value::Boolean(value) => serde_json::Value::from(*value),
+        Value::Number(value) => serde_json::Value::from(*value),
+        Value::String(value) => serde_json::Value::from(value.as_str()),
+    };
+
+    let mut changes = serde_json::Map::new();
+    changes.insert(String::from(key), json);
+    Ok(serde_json::from_value(serde_json::Value::Object(changes))
+        .expect("option values are valid configuration values"))
+}
+
+/// Arranges items into columns that fit within a width, in the order that `:set all` displays
+/// them: down each column, then across.
+pub fn columns(items: &[String], width: usize) -> Vec<String> {
+    let column_width = items.iter().map(|item| item.len()).max().unwrap_or(0) + 2;
+    let columns = (width / column_width).max(1);
+    let rows = (items.len() + columns - 1) / columns;
+
+    (0..rows)
+        .map(|row| {
+            let line = items
+                .iter()
+                .skip(row)
+                .step_by(rows)
+                .map(|item| format!("{:width$}", item, width = column_width))
+                .collect::<String>();
+            String::from(line.trim_end())
+        })
+        .collect()
+}
+
+/// The flags of the `mouse` option, like vim's: normal, visual, insert and command-line modes,
+/// all of them, and the hit-enter and more prompts.
+const MOUSE_FLAGS: &str = "nvicahr";
+
+/// The values of options with global scope.
+#[derive(Debug, Default)]
+pub struct GlobalOptions {
+    /// The format of each window's status line.
+    pub statusline: StatusLine,
+
+    /// The modes in which the mouse is enabled, as flags.
+    pub mouse: String,
+}
+
+impl GlobalOptions {
+    fn get(&self, name: &str) -> Option<Value> {
+        match name {
+            "statusline" => Some(Value::String(self.statusline.to_string())),
+            "mouse" => Some(Value::String(self.mouse.clone())),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the mouse is enabled in the mode with the given flag.
+    pub fn mouse_enabled(&self, mode: char) -> bool {
+        self.mouse.contains('a') || self.mouse.contains(mode)
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), OptionError> {
+        match (name, value) {
+            ("statusline", Value::String(value)) => {
+                self.statusline = value
+                    .parse()
+                    .map_err(|_| OptionError::InvalidArgument(format!("statusline={}", value)))?;
+            }
+            ("mouse", Value::String(value)) => {
+                if !value.chars().all(|c| MOUSE_FLAGS.contains(c)) {
+                    return Err(OptionError::InvalidArgument(format!("mouse={}", value)));
+                }
+                self.mouse = value;
+            }
+            (name, _) => return Err(OptionError::Unknown(String::from(name))),
+        }
+
+        Ok(())
+    }
+}
+
+/// The values of options that have a separate value in each window.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WindowOptions {
+    /// Whether absolute line numbers are displayed in the gutter.
+    pub number: bool,
+
+    /// Whether line numbers relative to the cursor are displayed in the gutter.
+    ///
+    /// If `number` is also set, the line containing the cursor displays its absolute line number.
+    pub relative_number: bool,
+
+    /// Whether lines longer than the width of the window are continued on the next row.
+    pub wrap: bool,
+
+    /// Whether wrapped lines are broken at whitespace instead of in the middle of a word.
+    pub linebreak: bool,
+
+    /// Displayed at the start of each continuation row of a wrapped line.
+    pub showbreak: String,
+
+    /// Whether whitespace is made visible using `listchars`.
+    pub list: bool,
+
+    pub listchars: ListChars,
+
+    /// The minimum number of rows to keep above and below the cursor when scrolling.
+    pub scrolloff: usize,
+
+    /// Whether the line containing the cursor is highlighted.
+    pub cursor_line: bool,
+}
+
+impl WindowOptions {
+    fn get(&self, name: &str) -> Option<Value> {
+        let value = match name {
+            "number" => Value::Boolean(self.number),
+            "relativenumber" => Value::Boolean(self.relative_number),
+            "wrap" => Value::Boolean(self.wrap),
+            "linebreak" => Value::Boolean(self.linebreak),
+            "showbreak" => Value::String(self.showbreak.clone()),
+            "list" => Value::Boolean(self.list),
+            "listchars" => Value::String(self.listchars.to_string()),
+            "scrolloff" => Value::Number(self.scrolloff),
+            "cursorline" => Value::Boolean(self.cursor_line),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), OptionError> {
+        match (name, value) {
+            ("number", Value::Boolean(value)) => self.number = value,
+            ("relativenumber", Value::Boolean(value)) => self.relative_number = value,
+            ("wrap", Value::Boolean(value)) => self.wrap = value,
+            ("linebreak", Value::Boolean(value)) => self.linebreak = value,
+            ("showbreak", Value::String(value)) => self.showbreak = value,
+            ("list", Value::Boolean(value)) => self.list = value,
+            ("listchars", Value::String(value)) => {
+                self.listchars = value
+                    .parse()
+                    .map_err(|_| OptionError::InvalidArgument(format!("listchars={}", value)))?;
+            }
+            ("scrolloff", Value::Number(value)) => self.scrolloff = value,
+            ("cursorline", Value::Boolean(value)) => self.cursor_line = value,
+            (name, _) => return Err(OptionError::Unknown(String::from(name))),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        columns, core_changes, find, Argument, GlobalOptions, OptionError, Value, OPTIONS,
+    };
+    use crate::protocol::ConfigChanges;
+
+    #[test]
+    fn options_are_sorted() {
+        let names = OPTIONS.iter().map(|option| option.name).collect::<Vec<_>>();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn parse_arguments() {
+        assert_eq!(Argument::parse("all"), Argument::All);
+        assert_eq!(Argument::parse("nu"), Argument::Enable("nu"));
+        assert_eq!(Argument::parse("nonu"), Argument::Disable("nu"));
+        assert_eq!(Argument::parse("ts?"), Argument::Show("ts"));
+        assert_eq!(Argument::parse("ts=4"), Argument::Assign("ts", "4"));
+        assert_eq!(Argument::parse("sbr=> "), Argument::Assign("sbr", "> "));
+        assert_eq!(
+            Argument::parse("lcs+=tab:>-"),
+            Argument::Append("lcs", "tab:>-")
+        );
+    }
+
+    #[test]
+    fn evaluate() {
+        let number = find("nu").unwrap();
+        assert_eq!(
+            number.evaluate(Argument::Enable("nu"), &Value::Boolean(false)),
+            Ok(Some(Value::Boolean(true)))
+        );
+        assert_eq!(
+            number.evaluate(Argument::Show("nu"), &Value::Boolean(false)),
+            Ok(None)
+        );
+        assert_eq!(
+            number.evaluate(Argument::Assign("nu", "1"), &Value::Boolean(false)),
+            Err(OptionError::InvalidArgument(String::from("nu=1")))
+        );
+
+        let tabstop = find("tabstop").unwrap();
+        assert_eq!(
+            tabstop.evaluate(Argument::Enable("tabstop"), &Value::Number(4)),
+            Ok(None)
+        );
+        assert_eq!(
+            tabstop.evaluate(Argument::Append("ts", "2"), &Value::Number(4)),
+            Ok(Some(Value::Number(6)))
+        );
+        assert_eq!(
+            tabstop.evaluate(Argument::Assign("ts", "x"), &Value::Number(4)),
+            Err(OptionError::NumberRequired(String::from("ts=x")))
+        );
+        assert_eq!(
+            tabstop.evaluate(Argument::Disable("ts"), &Value::Number(4)),
+            Err(OptionError::InvalidArgument(String::from("nots")))
+        );
+
+        let listchars = find("lcs").unwrap();
+        assert_eq!(
+            listchars.evaluate(
+                Argument::Append("lcs", "trail:-"),
+                &Value::String(String::from("eol:$"))
+            ),
+            Ok(Some(Value::String(String::from("eol:$,trail:-"))))
+        );
+
+        let showbreak = find("sbr").unwrap();
+        assert_eq!(
+            showbreak.evaluate(
+                Argument::Append("sbr", " "),
+                &Value::String(String::from(">"))
+            ),
+            Ok(Some(Value::String(String::from("> "))))
+        );
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            find("nu").unwrap().format(&Value::Boolean(true)),
+            "  number"
+        );
+        assert_eq!(
+            find("nu").unwrap().format(&Value::Boolean(false)),
+            "nonumber"
+        );
+        assert_eq!(find("ts").unwrap().format(&Value::Number(8)), "  tabstop=8");
+    }
+
+    #[test]
+    fn core_options() {
+        assert_eq!(
+            core_changes("tab_size", &Value::Number(2)),
+            Ok(ConfigChanges {
+                tab_size: Some(2),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            core_changes("tab_size", &Value::Number(0)),
+            Err(OptionError::NotPositive(String::from("tabstop=0")))
+        );
+    }
+
+    #[test]
+    fn mouse() {
+        let mut options = GlobalOptions::default();
+        assert!(!options.mouse_enabled('n'));
+
+        options
+            .set("mouse", Value::String(String::from("n")))
+            .unwrap();
+        assert!(options.mouse_enabled('n'));
+        assert!(!options.mouse_enabled('i'));
+
+        options
+            .set("mouse", Value::String(String::from("a")))
+            .unwrap();
+        assert!(options.mouse_enabled('i'));
+
+        assert_eq!(
+            options.set("mouse", Value::String(String::from("x"))),
+            Err(OptionError::InvalidArgument(String::from("mouse=x")))
+        );
+    }
+
+    #[test]
+    fn set_all_columns() {
+        let items = ["a", "bb", "ccc", "d", "e"]
+            .iter()
+            .map(|&item| String::from(item))
+            .collect::<Vec<_>>();
+        assert_eq!(columns(&items, 10), vec!["a    d", "bb   e", "ccc"]);
+        assert_eq!(columns(&items, 3), vec!["a", "bb", "ccc", "d", "e"]);
+    }
+}
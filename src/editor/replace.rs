@@ -0,0 +1,62 @@
+//! Undoing replacements with backspace in replace mode.
+
+/// The text that has been overwritten since replace mode was entered, so that backspace can
+/// restore it, like in vim.
+#[derive(Debug, Default)]
+pub struct Replaced(Vec<Option<String>>);
+
+/// What backspace does in replace mode.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Restore {
+    /// Replace the previous character with the text that it overwrote.
+    Text(String),
+
+    /// Delete the previous character, which was inserted rather than overwriting anything, such
+    /// as a newline or a character typed past the end of the line.
+    Delete,
+
+    /// Move left, since the text before the cursor wasn't typed in replace mode.
+    MoveLeft,
+}
+
+impl Replaced {
+    /// Records that a character was typed over `overwritten`, or inserted if it is `None`.
+    pub fn push(&mut self, overwritten: Option<&str>) {
+        self.0.push(overwritten.map(String::from));
+    }
+
+    /// Returns how to undo the last character that was typed.
+    pub fn pop(&mut self) -> Restore {
+        match self.0.pop() {
+            Some(Some(text)) => Restore::Text(text),
+            Some(None) => Restore::Delete,
+            None => Restore::MoveLeft,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replaced, Restore};
+
+    #[test]
+    fn restore() {
+        let mut replaced = Replaced::default();
+        replaced.push(Some("a"));
+        replaced.push(Some("e\u{301}"));
+        replaced.push(None);
+
+        assert_eq!(replaced.pop(), Restore::Delete);
+        assert_eq!(replaced.pop(), Restore::Text(String::from("e\u{301}")));
+        assert_eq!(replaced.pop(), Restore::Text(String::from("a")));
+        assert_eq!(replaced.pop(), Restore::MoveLeft);
+
+        replaced.push(Some("b"));
+        replaced.clear();
+        assert_eq!(replaced.pop(), Restore::MoveLeft);
+    }
+}
@@ -0,0 +1,81 @@
+//! Settings for seventeen itself, as opposed to the configuration of the core.
+//!
+//! Settings are read from `config.toml` in the seventeen configuration directory (usually
+//! `~/.config/seventeen`). For example:
+//!
+//! ```toml
+//! # The theme used when the editor starts.
+//! theme = "Solarized (dark)"
+//!
+//! # The initial value of the `mouse` option.
+//! mouse = "a"
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use failure::Fail;
+use serde_derive::Deserialize;
+use xdg::BaseDirectories;
+
+/// The name of the settings file within the configuration directory.
+const FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Fail)]
+pub enum SettingsError {
+    #[fail(display = "could not read {}: {}", _0, _1)]
+    Io(String, #[cause] io::Error),
+
+    #[fail(display = "invalid settings in {}: {}", _0, _1)]
+    Parse(String, #[cause] toml::de::Error),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// The name of the theme that is used when the editor starts. If this is not set, the core's
+    /// default theme is used.
+    pub theme: Option<String>,
+
+    /// The initial value of the `mouse` option. The mouse is disabled by default, so that the
+    /// terminal's own selection works.
+    pub mouse: Option<String>,
+}
+
+impl Settings {
+    /// Loads the settings file from the configuration directory.
+    ///
+    /// If there is no settings file, the default settings are returned.
+    pub fn load(xdg_dirs: &BaseDirectories) -> Result<Self, SettingsError> {
+        match xdg_dirs.find_config_file(FILE_NAME) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    fn from_file(path: PathBuf) -> Result<Self, SettingsError> {
+        let display = path.display().to_string();
+        let contents =
+            fs::read_to_string(&path).map_err(|e| SettingsError::Io(display.clone(), e))?;
+        toml::from_str(&contents).map_err(|e| SettingsError::Parse(display, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            toml::from_str::<Settings>(r#"theme = "Solarized (dark)""#).unwrap(),
+            Settings {
+                theme: Some(String::from("Solarized (dark)")),
+                mouse: None,
+            }
+        );
+        assert_eq!(toml::from_str::<Settings>("").unwrap(), Settings::default());
+        assert!(toml::from_str::<Settings>("colorscheme = 1").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Running shell commands.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, prelude::*, BufReader};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::thread;
+
+/// The shell that runs commands. Like vim, this is `$SHELL`, or `sh` if it isn't set.
+fn shell() -> Command {
+    let shell = env::var_os("SHELL")
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| OsString::from("sh"));
+    Command::new(shell)
+}
+
+/// Runs a command, or an interactive shell, on the terminal.
+pub fn run(command: Option<&str>) -> io::Result<ExitStatus> {
+    let mut shell = shell();
+    if let Some(command) = command {
+        shell.arg("-c").arg(command);
+    }
+    shell.status()
+}
+
+/// Waits for enter to be pressed on the terminal.
+///
+/// The terminal is read directly, so reading input must be paused meanwhile.
+pub fn wait_for_enter() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\nPress ENTER to continue")?;
+    stdout.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(termion::get_tty()?).read_line(&mut line)?;
+    Ok(())
+}
+
+/// Runs a command that reads `input`, returning its output.
+pub fn filter(command: &str, input: &str) -> io::Result<Output> {
+    let mut child = shell()
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // The input is written by another thread, so that a command that writes its output before
+    // reading all of its input doesn't block on a full pipe.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    match writer.join().unwrap() {
+        // The command doesn't have to read its input.
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+        result => result?,
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::filter;
+
+    #[test]
+    fn filter_input() {
+        let output = filter("tr a-z A-Z", "abc\ndef\n").unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"ABC\nDEF\n");
+
+        let output = filter("echo unread", &"x".repeat(1 << 20)).unwrap();
+        assert_eq!(output.stdout, b"unread\n");
+
+        let output = filter("echo error >&2; exit 3", "").unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stderr, b"error\n");
+    }
+}
@@ -0,0 +1,520 @@
+//! Parsing and evaluation of `statusline` format strings.
+//!
+//! The format is a subset of vim's `'statusline'` option. Text is displayed literally, and the
+//! following items are replaced with information about the window:
+//!
+//! | Item       | Meaning                                                        |
+//! |------------|----------------------------------------------------------------|
+//! | `%f`       | File name                                                      |
+//! | `%m`       | Modified flag, `[+]` if the buffer has unsaved changes         |
+//! | `%l`       | Line number of the cursor                                      |
+//! | `%c`       | Column number of the cursor                                    |
+//! | `%p`       | Percentage through the file                                    |
+//! | `%y`       | Language of the buffer, such as `[Rust]`                       |
+//! | `%{mode}`  | The current mode, only displayed in the active window          |
+//! | `%=`       | Separation point between alignment sections                    |
+//! | `%<`       | Where to truncate the line if it is too long                   |
+//! | `%#Name#`  | Switch to the highlight group `Name`                           |
+//! | `%*`       | Restore the default highlight group                            |
+//! | `%(...%)`  | Item group, omitted entirely if every item inside it is empty  |
+//! | `%%`       | A literal `%`                                                  |
+
+use std::fmt::{self, Display};
+use std::iter;
+use std::str::{Chars, FromStr};
+
+use failure::Fail;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::screen::grapheme_width;
+
+/// The format used when the `statusline` option is not set.
+pub const DEFAULT_FORMAT: &str = "%( %{mode} %) %<%f%( %m%)%=%( %y%)  %l:%c  %p%% ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Text(String),
+    FileName,
+    Modified,
+    Line,
+    Column,
+    Percentage,
+    Language,
+    Mode,
+    Separator,
+    Truncate,
+    Highlight(Option<String>),
+    Group(Vec<Item>),
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum ParseError {
+    #[fail(display = "unknown status line item: %{}", _0)]
+    UnknownItem(char),
+
+    #[fail(display = "unknown status line variable: %{{{}}}", _0)]
+    UnknownVariable(String),
+
+    #[fail(display = "unterminated status line item")]
+    Unterminated,
+
+    #[fail(display = "unmatched `%)` in status line")]
+    UnmatchedGroup,
+}
+
+/// The information about a window that is displayed in its status line.
+#[derive(Debug, Default)]
+pub struct Context<'a> {
+    /// The current mode, if the window is active.
+    pub mode: Option<&'a str>,
+    pub file_name: &'a str,
+    pub modified: bool,
+    pub line: usize,
+    pub column: usize,
+    pub percentage: usize,
+    pub language: Option<&'a str>,
+}
+
+/// A grapheme cluster of the evaluated status line, and the highlight group it is displayed with.
+///
+/// A cell occupies one or two columns, depending on the width of the cluster. A highlight group of
+/// `None` indicates the default group for the status line.
+pub type Cell<'a> = (String, Option<&'a str>);
+
+/// A parsed status line format string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusLine {
+    format: String,
+    items: Vec<Item>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        DEFAULT_FORMAT.parse().unwrap()
+    }
+}
+
+impl FromStr for StatusLine {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let items = parse_items(&mut chars, false)?;
+        Ok(StatusLine {
+            format: String::from(s),
+            items,
+        })
+    }
+}
+
+impl Display for StatusLine {
+    /// Displays the format string that the status line was parsed from.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)
+    }
+}
+
+impl StatusLine {
+    /// Evaluates the status line for a window, returning cells that occupy exactly `width`
+    /// columns.
+    pub fn layout<'a>(&'a self, context: &Context<'_>, width: usize) -> Vec<Cell<'a>> {
+        let mut evaluated = Evaluated::default();
+        let mut group = None;
+        evaluated.push_items(&self.items, context, &mut group);
+
+        let Evaluated {
+            mut cells,
+            separators,
+            truncate,
+        } = evaluated;
+
+        if width == 0 {
+            return vec![];
+        }
+
+        let cells_width = cells
+            .iter()
+            .map(|(text, _)| grapheme_width(text))
+            .sum::<usize>();
+        if cells_width > width {
+            // Remove enough cells at the truncation point to fit the line, and leave room for a
+            // marker that indicates where the truncation occurred. If there aren't enough cells
+            // after the truncation point, cells before it are removed too.
+            let excess = cells_width - width + 1;
+            let mut start = truncate.unwrap_or(0).min(cells.len());
+            let mut end = start;
+            let mut removed = 0;
+            while removed < excess {
+                if end < cells.len() {
+                    end += 1;
+                    removed += grapheme_width(&cells[end - 1].0);
+                } else {
+                    start -= 1;
+                    removed += grapheme_width(&cells[start].0);
+                }
+            }
+
+            // Removing a double-width cell may free one column more than needed.
+            let group = cells[start].1;
+            let marker = iter::once((String::from("<"), group));
+            let filler = iter::repeat((String::from(" "), group)).take(removed - excess);
+            cells.splice(start..end, marker.chain(filler));
+        } else {
+            let padding = width - cells_width;
+
+            if separators.is_empty() {
+                let group = cells.last().and_then(|&(_, group)| group);
+                cells.extend((0..padding).map(|_| (String::from(" "), group)));
+            } else {
+                // Distribute the padding evenly between each separator, inserting from the end so
+                // that the positions of earlier separators remain valid.
+                let n = separators.len();
+                for (i, &(pos, group)) in separators.iter().enumerate().rev() {
+                    let count = padding / n + if i < padding % n { 1 } else { 0 };
+                    cells.splice(pos..pos, (0..count).map(|_| (String::from(" "), group)));
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+/// The result of evaluating the status line items, before alignment and truncation.
+#[derive(Debug, Default)]
+struct Evaluated<'a> {
+    cells: Vec<Cell<'a>>,
+    separators: Vec<(usize, Option<&'a str>)>,
+    truncate: Option<usize>,
+}
+
+impl<'a> Evaluated<'a> {
+    /// Evaluates a list of items, returning true if any non-literal item was non-empty.
+    fn push_items(
+        &mut self,
+        items: &'a [Item],
+        context: &Context<'_>,
+        group: &mut Option<&'a str>,
+    ) -> bool {
+        let mut non_empty = false;
+
+        for item in items {
+            let text = match item {
+                Item::Text(text) => {
+                    self.push_str(text, *group);
+                    continue;
+                }
+                Item::FileName => String::from(context.file_name),
+                Item::Modified if context.modified => String::from("[+]"),
+                Item::Modified => String::new(),
+                Item::Line => context.line.to_string(),
+                Item::Column => context.column.to_string(),
+                Item::Percentage => context.percentage.to_string(),
+                Item::Language => context
+                    .language
+                    .map(|language| format!("[{}]", language))
+                    .unwrap_or_default(),
+                Item::Mode => context.mode.map(String::from).unwrap_or_default(),
+                Item::Separator => {
+                    self.separators.push((self.cells.len(), *group));
+                    continue;
+                }
+                Item::Truncate => {
+                    self.truncate = Some(self.cells.len());
+                    continue;
+                }
+                Item::Highlight(name) => {
+                    *group = name.as_ref().map(String::as_str);
+                    continue;
+                }
+                Item::Group(items) => {
+                    let cells = self.cells.len();
+                    let separators = self.separators.len();
+                    let truncate = self.truncate;
+
+                    if self.push_items(items, context, group) {
+                        non_empty = true;
+                    } else {
+                        self.cells.truncate(cells);
+                        self.separators.truncate(separators);
+                        self.truncate = truncate;
+                    }
+                    continue;
+                }
+            };
+
+            if !text.is_empty() {
+                non_empty = true;
+                self.push_str(&text, *group);
+            }
+        }
+
+        non_empty
+    }
+
+    fn push_str(&mut self, s: &str, group: Option<&'a str>) {
+        self.cells.extend(
+            s.graphemes(true)
+                .map(|grapheme| (String::from(grapheme), group)),
+        );
+    }
+}
+
+fn parse_items(chars: &mut Chars<'_>, in_group: bool) -> Result<Vec<Item>, ParseError> {
+    let mut items = vec![];
+    let mut text = String::new();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        let item = match chars.next().ok_or(ParseError::Unterminated)? {
+            '%' => {
+                text.push('%');
+                continue;
+            }
+            'f' => Item::FileName,
+            'm' => Item::Modified,
+            'l' => Item::Line,
+            'c' => Item::Column,
+            'p' => Item::Percentage,
+            'y' => Item::Language,
+            '=' => Item::Separator,
+            '<' => Item::Truncate,
+            '*' => Item::Highlight(None),
+            '#' => Item::Highlight(Some(take_until(chars, '#')?)),
+            '{' => match take_until(chars, '}')?.as_str() {
+                "mode" => Item::Mode,
+                name => return Err(ParseError::UnknownVariable(String::from(name))),
+            },
+            '(' => Item::Group(parse_items(chars, true)?),
+            ')' if in_group => {
+                if !text.is_empty() {
+                    items.push(Item::Text(text));
+                }
+                return Ok(items);
+            }
+            ')' => return Err(ParseError::UnmatchedGroup),
+            c => return Err(ParseError::UnknownItem(c)),
+        };
+
+        if !text.is_empty() {
+            items.push(Item::Text(text.split_off(0)));
+        }
+        items.push(item);
+    }
+
+    if in_group {
+        return Err(ParseError::Unterminated);
+    }
+
+    if !text.is_empty() {
+        items.push(Item::Text(text));
+    }
+
+    Ok(items)
+}
+
+fn take_until(chars: &mut Chars<'_>, end: char) -> Result<String, ParseError> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == end => return Ok(s),
+            Some(c) => s.push(c),
+            None => return Err(ParseError::Unterminated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, Context, Item, ParseError, StatusLine};
+
+    fn text(cells: &[Cell<'_>]) -> String {
+        cells.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    fn groups<'a>(cells: &'a [Cell<'_>]) -> Vec<(&'a str, Option<&'a str>)> {
+        cells
+            .iter()
+            .map(|(text, group)| (text.as_str(), *group))
+            .collect()
+    }
+
+    fn context() -> Context<'static> {
+        Context {
+            mode: Some("NORMAL"),
+            file_name: "main.rs",
+            modified: true,
+            line: 12,
+            column: 4,
+            percentage: 50,
+            language: Some("Rust"),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let status_line = "%f %m%=%l,%c%%".parse::<StatusLine>().unwrap();
+        assert_eq!(
+            status_line.items,
+            vec![
+                Item::FileName,
+                Item::Text(String::from(" ")),
+                Item::Modified,
+                Item::Separator,
+                Item::Line,
+                Item::Text(String::from(",")),
+                Item::Column,
+                Item::Text(String::from("%")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_groups() {
+        let status_line = "%#Error#%(x %{mode}%)%*".parse::<StatusLine>().unwrap();
+        assert_eq!(
+            status_line.items,
+            vec![
+                Item::Highlight(Some(String::from("Error"))),
+                Item::Group(vec![Item::Text(String::from("x ")), Item::Mode]),
+                Item::Highlight(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "%z".parse::<StatusLine>(),
+            Err(ParseError::UnknownItem('z'))
+        );
+        assert_eq!("abc%".parse::<StatusLine>(), Err(ParseError::Unterminated));
+        assert_eq!(
+            "%#Error".parse::<StatusLine>(),
+            Err(ParseError::Unterminated)
+        );
+        assert_eq!("%(%f".parse::<StatusLine>(), Err(ParseError::Unterminated));
+        assert_eq!(
+            "%f%)".parse::<StatusLine>(),
+            Err(ParseError::UnmatchedGroup)
+        );
+        assert_eq!(
+            "%{foo}".parse::<StatusLine>(),
+            Err(ParseError::UnknownVariable(String::from("foo")))
+        );
+    }
+
+    #[test]
+    fn default_format() {
+        let status_line = StatusLine::default();
+        assert_eq!(
+            text(&status_line.layout(&context(), 40)),
+            " NORMAL  main.rs [+]  [Rust]  12:4  50% "
+        );
+
+        let inactive = Context {
+            mode: None,
+            modified: false,
+            language: None,
+            ..context()
+        };
+        assert_eq!(
+            text(&status_line.layout(&inactive, 40)),
+            " main.rs                      12:4  50% "
+        );
+    }
+
+    #[test]
+    fn alignment() {
+        let status_line = "%f%=%l%=%c".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&context(), 15)), "main.rs   12  4");
+        assert_eq!(
+            text(&status_line.layout(&context(), 16)),
+            "main.rs   12   4"
+        );
+
+        let status_line = "%f".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&context(), 10)), "main.rs   ");
+    }
+
+    #[test]
+    fn truncation() {
+        let status_line = "%f %l".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&context(), 6)), "<rs 12");
+
+        let status_line = "abc%<%f %l".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&context(), 10)), "abc<.rs 12");
+        assert_eq!(text(&status_line.layout(&context(), 0)), "");
+
+        // If the truncation point is too close to the end, truncate the end instead.
+        let status_line = "%f %l%<".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&context(), 6)), "main.<");
+    }
+
+    #[test]
+    fn highlight_groups() {
+        let status_line = "%#A#a%(%#B#b%m%)c%*d".parse::<StatusLine>().unwrap();
+        assert_eq!(
+            groups(&status_line.layout(&context(), 8)),
+            vec![
+                ("a", Some("A")),
+                ("b", Some("B")),
+                ("[", Some("B")),
+                ("+", Some("B")),
+                ("]", Some("B")),
+                ("c", Some("B")),
+                ("d", None),
+                (" ", None),
+            ]
+        );
+
+        // Double-width characters are a single cell.
+        let status_line = "%#A#日%*x".parse::<StatusLine>().unwrap();
+        assert_eq!(
+            groups(&status_line.layout(&context(), 4)),
+            vec![("日", Some("A")), ("x", None), (" ", None)]
+        );
+    }
+
+    #[test]
+    fn wide_characters() {
+        let wide = Context {
+            file_name: "日本語😀.rs",
+            ..context()
+        };
+        let status_line = "%f%=%l".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&wide, 16)), "日本語😀.rs   12");
+
+        // A double-width character is removed as a whole, leaving a space if it frees a column
+        // more than needed.
+        let status_line = "%f %l".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&wide, 10)), "< 😀.rs 12");
+        assert_eq!(text(&status_line.layout(&wide, 9)), "<😀.rs 12");
+        let status_line = "%l %f%<".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&wide, 9)), "12 日本< ");
+    }
+
+    #[test]
+    fn combining_marks() {
+        let combining = Context {
+            file_name: "cafe\u{301}.rs",
+            ..context()
+        };
+        let status_line = "%f%=%l".parse::<StatusLine>().unwrap();
+        assert_eq!(
+            text(&status_line.layout(&combining, 12)),
+            "cafe\u{301}.rs   12"
+        );
+
+        let status_line = "%f %l".parse::<StatusLine>().unwrap();
+        assert_eq!(text(&status_line.layout(&combining, 7)), "<.rs 12");
+        assert_eq!(
+            groups(&status_line.layout(&combining, 10))[3],
+            ("e\u{301}", None)
+        );
+    }
+}
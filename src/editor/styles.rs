@@ -2,15 +2,58 @@ use std::ops::Index;
 
 use log::*;
 
-use crate::screen::Color;
+use crate::protocol::{self, ThemeSettings};
+use crate::screen::{Attr, Color};
+
+/// The style used by the core for selections.
+const SELECTION_STYLE: u64 = 0;
+
+/// The style used by the core for search results.
+const FIND_STYLE: u64 = 1;
 
 #[derive(Debug, Default, Clone)]
 pub struct Style {
     pub fg: Option<Color>,
     pub bg: Option<Color>,
     pub bold: bool,
+    pub dim: bool,
     pub underline: bool,
     pub italic: bool,
+    pub reverse: bool,
+    pub blink: bool,
+    pub strikethrough: bool,
+
+    /// Styled underlines, named like vim's. Terminals that don't support them use a single
+    /// underline.
+    pub underdouble: bool,
+    pub undercurl: bool,
+    pub underdotted: bool,
+
+    /// The color of underlines. Like vim's `guisp`, this defaults to the foreground color.
+    pub underline_color: Option<Color>,
+}
+
+impl Style {
+    /// Returns the terminal attributes of the style.
+    pub fn attr(&self) -> Attr {
+        let flags = [
+            (self.bold, Attr::BOLD),
+            (self.dim, Attr::DIM),
+            (self.underline, Attr::UNDERLINE),
+            (self.italic, Attr::ITALIC),
+            (self.reverse, Attr::REVERSE),
+            (self.blink, Attr::BLINK),
+            (self.strikethrough, Attr::STRIKETHROUGH),
+            (self.underdouble, Attr::DOUBLE_UNDERLINE),
+            (self.undercurl, Attr::CURLY_UNDERLINE),
+            (self.underdotted, Attr::DOTTED_UNDERLINE),
+        ];
+
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(Attr::empty(), |attr, &(_, flag)| attr | flag)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -21,17 +64,65 @@ pub struct Styles {
     /// Default background color for text.
     pub bg: Option<Color>,
 
+    /// Foreground color of the line number gutter.
+    pub gutter_fg: Option<Color>,
+
+    /// Background color of the line number gutter.
+    pub gutter_bg: Option<Color>,
+
+    /// Color of the cursor.
+    pub caret: Option<Color>,
+
+    /// Background color of the line containing the cursor.
+    pub line_highlight: Option<Color>,
+
+    /// Color of the underline of misspelled words.
+    pub misspelling: Option<Color>,
+
     styles: Vec<Style>,
 }
 
 impl Styles {
     pub fn new() -> Self {
-        Styles::default()
+        let mut styles = Styles::default();
+        styles.set_theme(&ThemeSettings::default());
+        styles
+    }
+
+    /// Uses the colors of a theme.
+    ///
+    /// The core reserves the first two styles for selections and search results, but leaves their
+    /// definitions to the frontend, so they're defined here from the theme. Like vim, they fall
+    /// back to reverse video if the theme doesn't specify a color.
+    pub fn set_theme(&mut self, theme: &ThemeSettings) {
+        self.fg = theme.foreground.clone().map(Into::into);
+        self.bg = theme.background.clone().map(Into::into);
+        self.gutter_fg = theme.gutter_foreground.clone().map(Into::into);
+        self.gutter_bg = theme.gutter.clone().map(Into::into);
+        self.caret = theme.caret.clone().map(Into::into);
+        self.line_highlight = theme.line_highlight.clone().map(Into::into);
+        self.misspelling = theme.misspelling.clone().map(Into::into);
+
+        let selection_bg = theme
+            .selection
+            .clone()
+            .or_else(|| theme.selection_background.clone());
+        self.define(
+            SELECTION_STYLE,
+            highlight(theme.selection_foreground.clone(), selection_bg),
+        );
+        self.define(
+            FIND_STYLE,
+            highlight(
+                theme.find_highlight_foreground.clone(),
+                theme.find_highlight.clone(),
+            ),
+        );
     }
 
     pub fn define(&mut self, id: u64, style: Style) {
         info!(
-            "defined style {}: fg={} bg={} bold={} underline={} italic={}",
+            "defined style {}: fg={} bg={} attr={:?}",
             id,
             style
                 .fg
@@ -43,9 +134,7 @@ impl Styles {
                 .as_ref()
                 .map(ToString::to_string)
                 .unwrap_or_else(|| String::from("none")),
-            style.bold,
-            style.underline,
-            style.italic,
+            style.attr(),
         );
 
         if id as usize >= self.styles.len() {
@@ -54,6 +143,81 @@ impl Styles {
 
         self.styles[id as usize] = style;
     }
+
+    /// Returns the style of a named highlight group, such as `StatusLine`.
+    ///
+    /// Returns `None` if the group is unknown.
+    pub fn group(&self, name: &str) -> Option<Style> {
+        let normal = Style {
+            fg: self.fg,
+            bg: self.bg,
+            ..Default::default()
+        };
+        let reverse = Style {
+            fg: self.bg,
+            bg: self.fg,
+            ..Default::default()
+        };
+
+        let style = match name {
+            "Normal" => normal,
+            "LineNr" => Style {
+                fg: self.gutter_fg.or(self.fg),
+                bg: self.gutter_bg.or(self.bg),
+                ..Default::default()
+            },
+            "StatusLine" => Style {
+                bold: true,
+                ..reverse
+            },
+            "TabLineSel" => Style {
+                bold: true,
+                ..normal
+            },
+            "StatusLineNC" | "TabLine" | "TabLineFill" => reverse,
+            "Cursor" => match self.caret {
+                Some(caret) => Style {
+                    fg: self.bg,
+                    bg: Some(caret),
+                    ..Default::default()
+                },
+                None => Style {
+                    reverse: true,
+                    ..Default::default()
+                },
+            },
+            "CursorLine" => match self.line_highlight {
+                Some(line_highlight) => Style {
+                    bg: Some(line_highlight),
+                    ..Default::default()
+                },
+                None => Style {
+                    underline: true,
+                    ..Default::default()
+                },
+            },
+            "SpellBad" => Style {
+                undercurl: true,
+                underline_color: self.misspelling,
+                ..Default::default()
+            },
+            "Visual" => self[SELECTION_STYLE].clone(),
+            "Search" => self[FIND_STYLE].clone(),
+            _ => return None,
+        };
+
+        Some(style)
+    }
+}
+
+/// Returns a style that highlights text with the given colors, or reverse video if there are none.
+fn highlight(fg: Option<protocol::Color>, bg: Option<protocol::Color>) -> Style {
+    Style {
+        reverse: fg.is_none() && bg.is_none(),
+        fg: fg.map(Into::into),
+        bg: bg.map(Into::into),
+        ..Default::default()
+    }
 }
 
 impl Index<u64> for Styles {
@@ -63,3 +227,65 @@ impl Index<u64> for Styles {
         &self.styles[idx as usize]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{Color, ThemeSettings};
+    use crate::screen;
+
+    use super::Styles;
+
+    #[test]
+    fn reserved_styles() {
+        let mut styles = Styles::new();
+        assert!(styles[0].reverse);
+        assert!(styles[1].reverse);
+        assert!(styles.group("Cursor").unwrap().reverse);
+
+        let theme = ThemeSettings {
+            background: Some(Color { r: 0, g: 0, b: 0 }),
+            caret: Some(Color {
+                r: 255,
+                g: 255,
+                b: 255,
+            }),
+            selection: Some(Color { r: 0, g: 0, b: 128 }),
+            find_highlight: Some(Color {
+                r: 255,
+                g: 255,
+                b: 0,
+            }),
+            find_highlight_foreground: Some(Color { r: 0, g: 0, b: 0 }),
+            ..Default::default()
+        };
+        styles.set_theme(&theme);
+
+        let black = screen::Color { r: 0, g: 0, b: 0 };
+        let selection = styles.group("Visual").unwrap();
+        assert_eq!(selection.fg, None);
+        assert_eq!(selection.bg, Some(screen::Color { r: 0, g: 0, b: 128 }));
+        assert!(!selection.reverse);
+
+        let find = styles.group("Search").unwrap();
+        assert_eq!(find.fg, Some(black));
+        assert_eq!(
+            find.bg,
+            Some(screen::Color {
+                r: 255,
+                g: 255,
+                b: 0,
+            })
+        );
+
+        let cursor = styles.group("Cursor").unwrap();
+        assert_eq!(cursor.fg, Some(black));
+        assert!(!cursor.reverse);
+
+        // The theme has no line highlight, so the line is underlined instead.
+        assert!(styles.group("CursorLine").unwrap().underline);
+
+        let spell_bad = styles.group("SpellBad").unwrap();
+        assert_eq!(spell_bad.attr(), screen::Attr::CURLY_UNDERLINE);
+        assert_eq!(spell_bad.underline_color, None);
+    }
+}
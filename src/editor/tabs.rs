@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use euclid::{Point2D, Rect, Size2D};
+use log::*;
+
+use super::layout::Layout;
+use super::styles::Styles;
+use super::window::Window;
+use crate::protocol::ViewId;
+use crate::screen::{Coordinate, Screen};
+
+/// A tab page: a collection of windows with its own layout.
+#[derive(Debug)]
+pub struct Tab {
+    pub layout: Layout,
+
+    /// The view that has focus within this tab.
+    pub active_view: Option<ViewId>,
+}
+
+/// The list of tab pages, and the tab line that is displayed when there is more than one.
+#[derive(Debug)]
+pub struct Tabs {
+    screen: Size2D<usize>,
+    tabs: Vec<Tab>,
+    active: usize,
+}
+
+impl Tabs {
+    pub fn new(screen_size: Size2D<usize>) -> Self {
+        let mut tabs = Tabs {
+            screen: screen_size,
+            tabs: vec![],
+            active: 0,
+        };
+        tabs.tabs.push(Tab {
+            layout: Layout::new(tabs.layout_bounds()),
+            active_view: None,
+        });
+        tabs
+    }
+
+    pub fn active(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tab> {
+        self.tabs.iter()
+    }
+
+    /// Replaces views with others in the tabs that contain them, keeping their windows.
+    pub fn replace_views(&mut self, replacements: &HashMap<ViewId, ViewId>) {
+        for tab in &mut self.tabs {
+            tab.layout.replace_views(replacements);
+            if let Some(new) = tab.active_view.as_ref().and_then(|id| replacements.get(id)) {
+                tab.active_view = Some(new.clone());
+            }
+        }
+    }
+
+    /// Returns the tab that contains a window for the given view.
+    pub fn find_view(&self, view_id: &ViewId) -> Option<&Tab> {
+        self.tabs.iter().find(|tab| tab.layout.contains(view_id))
+    }
+
+    /// Opens a new, empty tab after the active tab and makes it active.
+    pub fn open(&mut self) {
+        self.tabs.insert(
+            self.active + 1,
+            Tab {
+                layout: Layout::new(self.layout_bounds()),
+                active_view: None,
+            },
+        );
+        self.active += 1;
+        self.resize_layouts();
+
+        info!("opened tab {} of {}", self.active + 1, self.tabs.len());
+    }
+
+    /// Closes the active tab, returning it so that its views can be cleaned up.
+    ///
+    /// Returns `None` if the active tab is the last one.
+    pub fn close(&mut self) -> Option<Tab> {
+        if self.tabs.len() == 1 {
+            return None;
+        }
+
+        let tab = self.tabs.remove(self.active);
+        if self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+        self.resize_layouts();
+
+        Some(tab)
+    }
+
+    /// Makes the next tab active, wrapping around to the first tab.
+    pub fn next(&mut self) {
+        self.active = (self.active + 1) % self.tabs.len();
+    }
+
+    /// Makes the previous tab active, wrapping around to the last tab.
+    pub fn previous(&mut self) {
+        self.active = (self.active + self.tabs.len() - 1) % self.tabs.len();
+    }
+
+    /// The bounding rectangle of the tab line, if it is visible.
+    ///
+    /// Like vim, the tab line is only shown when there are at least two tabs.
+    pub fn of_tab_line(&self) -> Option<Rect<usize>> {
+        if self.tabs.len() > 1 {
+            Some(Rect::new(
+                Point2D::zero(),
+                Size2D::new(self.screen.width, 1),
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn render<W: Write>(
+        &self,
+        windows: &HashMap<ViewId, Window>,
+        styles: &Styles,
+        screen: &mut Screen<W>,
+    ) {
+        let bounds = match self.of_tab_line() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        screen.erase_line(bounds.origin.y);
+
+        let inactive = styles.group("TabLine").unwrap_or_default();
+        let active = styles.group("TabLineSel").unwrap_or_default();
+        let fill = styles.group("TabLineFill").unwrap_or_default();
+
+        screen.apply_style(bounds.origin, bounds.size.width, &fill);
+
+        let mut x = bounds.min_x();
+        for (i, tab) in self.tabs.iter().enumerate() {
+            let name = tab
+                .active_view
+                .as_ref()
+                .and_then(|id| windows.get(id))
+                .map(Window::display_name)
+                .unwrap_or_else(|| String::from(Window::NO_NAME));
+            let label = format!(" {} ", name);
+
+            // The label is clipped at the edge of the screen.
+            let len = screen.write_str(Coordinate::new(x, bounds.origin.y), &label);
+            screen.apply_style(
+                Coordinate::new(x, bounds.origin.y),
+                len,
+                if i == self.active { &active } else { &inactive },
+            );
+
+            x += len;
+            if x >= bounds.max_x() {
+                break;
+            }
+        }
+    }
+
+    /// The area of the screen available to the layout of each tab.
+    fn layout_bounds(&self) -> Rect<usize> {
+        match self.of_tab_line() {
+            Some(tab_line) => Rect::new(
+                Point2D::new(0, tab_line.max_y()),
+                Size2D::new(self.screen.width, self.screen.height - tab_line.size.height),
+            ),
+            None => Rect::from_size(self.screen),
+        }
+    }
+
+    fn resize_layouts(&mut self) {
+        let bounds = self.layout_bounds();
+        for tab in &mut self.tabs {
+            tab.layout.resize(bounds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::{Point2D, Rect, Size2D};
+
+    use super::Tabs;
+    use crate::protocol::ViewId;
+
+    #[test]
+    fn tab_line_shrinks_layout() {
+        let mut tabs = Tabs::new(Size2D::new(80, 24));
+        let view_id = ViewId(String::from("view-id-1"));
+        tabs.active_mut().layout.add_view(&view_id);
+        assert!(tabs.of_tab_line().is_none());
+        assert_eq!(
+            tabs.active().layout.of_view(&view_id),
+            Rect::from_size(Size2D::new(80, 22))
+        );
+
+        tabs.open();
+        assert_eq!(
+            tabs.of_tab_line(),
+            Some(Rect::from_size(Size2D::new(80, 1)))
+        );
+        tabs.previous();
+        assert_eq!(
+            tabs.active().layout.of_view(&view_id),
+            Rect::new(Point2D::new(0, 1), Size2D::new(80, 21))
+        );
+
+        tabs.next();
+        assert!(tabs.close().is_some());
+        assert!(tabs.of_tab_line().is_none());
+        assert_eq!(
+            tabs.active().layout.of_view(&view_id),
+            Rect::from_size(Size2D::new(80, 22))
+        );
+    }
+
+    #[test]
+    fn replace_views() {
+        let mut tabs = Tabs::new(Size2D::new(80, 24));
+        let first = ViewId(String::from("view-id-1"));
+        let second = ViewId(String::from("view-id-2"));
+        let third = ViewId(String::from("view-id-3"));
+        tabs.active_mut().layout.add_view(&first);
+        tabs.active_mut().active_view = Some(first.clone());
+        tabs.open();
+        tabs.active_mut().layout.add_view(&second);
+        tabs.active_mut().active_view = Some(second.clone());
+
+        // The views are swapped, and the second view's ID is reused.
+        let replacements = vec![
+            (first.clone(), second.clone()),
+            (second.clone(), third.clone()),
+        ];
+        tabs.replace_views(&replacements.into_iter().collect());
+        assert!(!tabs.active().layout.contains(&second));
+        assert!(tabs.active().layout.contains(&third));
+        assert_eq!(tabs.active().active_view, Some(third));
+        tabs.previous();
+        assert!(!tabs.active().layout.contains(&first));
+        assert!(tabs.active().layout.contains(&second));
+        assert_eq!(tabs.active().active_view, Some(second));
+    }
+
+    #[test]
+    fn close_last_tab() {
+        let mut tabs = Tabs::new(Size2D::new(80, 24));
+        assert!(tabs.close().is_none());
+        assert_eq!(tabs.len(), 1);
+    }
+
+    #[test]
+    fn cycle_tabs() {
+        let mut tabs = Tabs::new(Size2D::new(80, 24));
+        tabs.open();
+        tabs.open();
+        assert_eq!(tabs.active, 2);
+
+        tabs.next();
+        assert_eq!(tabs.active, 0);
+
+        tabs.previous();
+        assert_eq!(tabs.active, 2);
+
+        tabs.active = 1;
+        tabs.close();
+        assert_eq!(tabs.active, 1);
+        tabs.close();
+        assert_eq!(tabs.active, 0);
+    }
+}
@@ -1,20 +1,47 @@
 use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
 
 use euclid::{Rect, SideOffsets2D};
 use log::*;
 
+use super::config::Config;
+use super::display_line::DisplayLine;
 use super::line_cache::LineCache;
+use super::list_chars::ListChars;
+use super::options::WindowOptions;
+use super::status_line::{Context, StatusLine};
 use super::styles::Styles;
-use crate::screen::{Coordinate, Screen};
+use super::wrap;
+use crate::screen::{str_width, Coordinate, Screen};
 
 #[derive(Debug)]
 pub struct Window {
     pub cursor: Coordinate,
     pub line_cache: LineCache,
 
+    /// The path of the file being edited in the window, if any.
+    pub path: Option<PathBuf>,
+
+    /// Whether the buffer is unchanged since it was last saved.
+    pub pristine: bool,
+
+    /// The language of the buffer, as detected by the core.
+    pub language: Option<String>,
+
+    /// The configuration of the view, as sent by the core.
+    pub config: Config,
+
+    /// The values of options that are local to the window.
+    pub options: WindowOptions,
+
     /// The offsets of the window compared to the contents of the cache. Used for scrolling the
     /// window.
     offsets: SideOffsets2D<usize>,
+
+    /// When wrapping, the first row of the top line that is displayed in the window. Along with
+    /// `offsets.top`, this allows the window to be scrolled by visual rows.
+    top_row: usize,
 }
 
 impl Window {
@@ -23,13 +50,36 @@ impl Window {
             offsets: SideOffsets2D::zero(),
             line_cache: LineCache::new(),
             cursor: Coordinate::zero(),
+            path: None,
+            pristine: true,
+            language: None,
+            config: Config::default(),
+            options: WindowOptions::default(),
+            top_row: 0,
         }
     }
 
+    /// Displayed in place of the file name for windows that aren't editing a file.
+    pub const NO_NAME: &'static str = "[No Name]";
+
+    /// Returns the name of the file being edited, suitable for display.
+    pub fn display_name(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from(Self::NO_NAME))
+    }
+
+    /// Renders the window to the screen buffer.
+    ///
+    /// If the window is active, its primary cursor is displayed as the terminal cursor. All other
+    /// cursors are drawn with the cursor style.
     pub fn render<W: Write>(
         &self,
         styles: &Styles,
         bounds: &Rect<usize>,
+        active: bool,
         screen: &mut Screen<W>,
     ) -> io::Result<()> {
         let start = self.offsets.top;
@@ -42,62 +92,144 @@ impl Window {
             None => return Ok(()),
         };
 
-        screen.erase();
+        screen.erase_rect(bounds);
 
-        for (i, line) in lines.enumerate() {
+        let gutter_width = self.gutter_width().min(bounds.size.width);
+        let text_width = self.text_width(bounds);
+        let gutter_style = styles.group("LineNr").unwrap_or_default();
+        let cursor_line_style = styles.group("CursorLine").unwrap_or_default();
+        let cursor_style = styles.group("Cursor").unwrap_or_default();
+
+        let origin = Coordinate::new(bounds.origin.x + gutter_width, bounds.origin.y);
+        let mut row = 0;
+        let mut primary_cursor = None;
+
+        'lines: for (i, line) in lines.enumerate() {
             // There might be a newline at the end of the current line, but the terminal already
             // operates linewise.
-            //
-            // TODO: We also need to convert tabs to spaces here, because terminals don't really
-            // play nice with tabs (for instance, we can't draw a cursor over a tab). However, it's
-            // more subtle than just a replace, as we'll need to update the style offsets as well.
-            let text = line
-                .text
-                .trim_right_matches('\n')
-                .chars()
-                .skip(self.offsets.left)
-                .take(bounds.size.width) // FIXME: this width check is bogus for non-ASCII
-                .collect::<String>();
-            screen.write_str(Coordinate::new(0, i), &text);
-
-            for mut style_span in line.iter_style_spans() {
-                // Skip any spans that end before the column offset or start after the end of the
-                // screen.
-                if style_span.start + style_span.length <= self.offsets.left
-                    || self.offsets.left + bounds.size.width <= style_span.start
-                {
-                    continue;
+            let line_text = line.text.trim_end_matches('\n');
+            let text = self.display_line(line_text);
+            let trailing_spaces = line_text.trim_end_matches(' ').len();
+            let skip = if i == 0 { self.top_row } else { 0 };
+
+            for (segment_idx, segment) in self
+                .segments(&text, text_width)
+                .into_iter()
+                .enumerate()
+                .skip(skip)
+            {
+                if row >= bounds.size.height {
+                    break 'lines;
                 }
 
-                if style_span.start < self.offsets.left {
-                    style_span.length -= self.offsets.left - style_span.start;
-                    style_span.start = 0;
-                } else {
-                    style_span.start -= self.offsets.left;
+                let y = origin.y + row;
+                row += 1;
+
+                if gutter_width > 0 {
+                    let gutter_origin = Coordinate::new(bounds.origin.x, y);
+                    if segment_idx == 0 {
+                        let number = format!(
+                            "{:>width$} ",
+                            self.line_number(start + i),
+                            width = gutter_width - 1
+                        );
+                        screen.write_str(gutter_origin, &number);
+                    }
+                    screen.apply_style(gutter_origin, gutter_width, &gutter_style);
                 }
 
-                screen.apply_style(
-                    Coordinate::new(style_span.start, i),
-                    style_span.length,
-                    &styles[style_span.id],
-                );
-            }
+                let prefix = self.break_prefix(segment_idx, text_width);
+                screen.write_str(Coordinate::new(origin.x, y), prefix);
+                let x = origin.x + str_width(prefix);
+
+                // Clusters that are only partially visible (such as a double-width character at
+                // the edge of a horizontally scrolled window) are not drawn.
+                for cluster in text.clusters() {
+                    if cluster.column < segment.start
+                        || cluster.column + cluster.width > segment.end
+                    {
+                        continue;
+                    }
+
+                    let coordinate = Coordinate::new(x + cluster.column - segment.start, y);
+
+                    // Terminals don't play nice with tabs (for instance, we can't draw a cursor over
+                    // a tab), so they are expanded to spaces.
+                    if cluster.is_tab() {
+                        let tab = if self.options.list {
+                            self.options.listchars.tab(cluster.width)
+                        } else {
+                            ListChars::default().tab(cluster.width)
+                        };
+                        screen.write_str(coordinate, &tab);
+                        continue;
+                    }
+
+                    match self.options.listchars.trail {
+                        Some(trail)
+                            if self.options.list
+                                && cluster.text == " "
+                                && cluster.offset >= trailing_spaces =>
+                        {
+                            screen.write_str(coordinate, &trail.to_string());
+                        }
+                        _ => {
+                            screen.write_str(coordinate, cluster.text);
+                        }
+                    }
+                }
 
-            for offset in line.iter_cursors() {
-                // Skip any cursors that aren't on the screen.
-                if offset < self.offsets.left.into()
-                    || self.offsets.left + bounds.size.width <= offset
-                {
-                    continue;
+                if let Some(eol) = self.options.listchars.eol.filter(|_| self.options.list) {
+                    let column = text.width();
+                    if segment.start <= column && column < segment.end {
+                        screen.write_str(
+                            Coordinate::new(x + column - segment.start, y),
+                            &eol.to_string(),
+                        );
+                    }
                 }
 
-                screen.draw_cursor(Coordinate::new(offset - self.offsets.left, i));
+                if self.options.cursor_line && start + i == self.cursor.y {
+                    screen.apply_style(
+                        Coordinate::new(origin.x, y),
+                        text_width,
+                        &cursor_line_style,
+                    );
+                }
+
+                for style_span in line.iter_style_spans() {
+                    // Only style the part of the span that is visible in this row.
+                    let span_start = text.column_of(style_span.start).max(segment.start);
+                    let span_end = text
+                        .end_column_of(style_span.start + style_span.length)
+                        .min(segment.end);
+                    if span_end <= span_start {
+                        continue;
+                    }
+
+                    screen.apply_style(
+                        Coordinate::new(x + span_start - segment.start, y),
+                        span_end - span_start,
+                        &styles[style_span.id],
+                    );
+                }
+
+                for offset in line.iter_cursors() {
+                    let column = text.column_of(offset);
+                    if segment.start <= column && column < segment.end {
+                        let coordinate = Coordinate::new(x + column - segment.start, y);
+                        if active && Coordinate::new(offset, start + i) == self.cursor {
+                            primary_cursor = Some(coordinate);
+                        } else {
+                            screen.draw_cursor(coordinate, &cursor_style);
+                        }
+                    }
+                }
             }
         }
 
         // If there are more rows in the window than lines in the cache, then fill out the
         // remaining rows with tildes.
-        let starting_line_no = self.line_cache.len();
 
         // FIXME: Don't display the trailing newline here.
         // To do this, we need to distinguish between the file having a trailing newline and a file
@@ -105,13 +237,314 @@ impl Window {
         // cursor doesn't actually get its correct position until the `scroll_to` notification gets
         // sent later.
 
-        for line_no in starting_line_no..bounds.size.height {
-            screen.write_str(Coordinate::new(0, line_no), "~");
+        for row in row..bounds.size.height {
+            screen.write_str(Coordinate::new(bounds.origin.x, origin.y + row), "~");
+        }
+
+        if active {
+            screen.set_cursor(primary_cursor);
         }
 
         Ok(())
     }
 
+    /// Renders the status line of the window.
+    ///
+    /// The mode should only be provided for the active window, which is also highlighted
+    /// differently from inactive windows.
+    pub fn render_status<W: Write>(
+        &self,
+        status_line: &StatusLine,
+        mode: Option<&str>,
+        styles: &Styles,
+        bounds: &Rect<usize>,
+        screen: &mut Screen<W>,
+    ) {
+        let file_name = self.display_name();
+        let percentage = (self.cursor.y + 1) * 100 / self.buffer_len().max(1);
+        let context = Context {
+            mode,
+            file_name: &file_name,
+            modified: !self.pristine,
+            line: self.cursor.y + 1,
+            column: self.cursor.x + 1,
+            percentage: percentage.min(100),
+            language: self.language.as_ref().map(String::as_str),
+        };
+
+        let cells = status_line.layout(&context, bounds.size.width);
+
+        screen.erase_rect(bounds);
+
+        let default_group = if mode.is_some() {
+            "StatusLine"
+        } else {
+            "StatusLineNC"
+        };
+
+        // Each cell is written separately, so that its style covers every column that it occupies.
+        let mut x = bounds.origin.x;
+        for (text, group) in &cells {
+            let origin = Coordinate::new(x, bounds.origin.y);
+            let width = screen.write_str(origin, text);
+            let style = group
+                .and_then(|group| styles.group(group))
+                .or_else(|| styles.group(default_group))
+                .unwrap_or_default();
+            screen.apply_style(origin, width, &style);
+            x += width;
+        }
+    }
+
+    /// The width of the line number gutter, including a trailing space.
+    ///
+    /// The width grows with the number of lines in the buffer, with a minimum of three digits.
+    /// Returns zero if line numbers are disabled.
+    pub fn gutter_width(&self) -> usize {
+        if !self.options.number && !self.options.relative_number {
+            return 0;
+        }
+
+        let digits = self.buffer_len().to_string().len();
+        digits.max(3) + 1
+    }
+
+    /// The number displayed in the gutter for a 0-based line index.
+    fn line_number(&self, line: usize) -> usize {
+        if self.options.relative_number && line > self.cursor.y {
+            line - self.cursor.y
+        } else if self.options.relative_number && line < self.cursor.y {
+            self.cursor.y - line
+        } else if self.options.number {
+            line + 1
+        } else {
+            0
+        }
+    }
+
+    /// Returns the range of columns of a line that are displayed on each row of the window.
+    ///
+    /// If wrapping is disabled, there is only one row, which is offset by the horizontal scroll
+    /// position of the window.
+    fn segments(&self, text: &DisplayLine<'_>, text_width: usize) -> Vec<Range<usize>> {
+        if !self.options.wrap {
+            let visible = self.offsets.left..self.offsets.left + text_width;
+            return vec![visible];
+        }
+
+        let continuation_width = text_width - str_width(self.break_prefix(1, text_width));
+        wrap::wrap_line(text, text_width, continuation_width, self.options.linebreak)
+    }
+
+    /// The segments of a line in the cache, or a single segment if the line is invalid.
+    fn line_segments(&self, line: usize, text_width: usize) -> Vec<Range<usize>> {
+        let text = self.line_text(line);
+        self.segments(&self.display_line(text), text_width)
+    }
+
+    /// Lays out a line of text using the configured tab size.
+    fn display_line<'a>(&self, text: &'a str) -> DisplayLine<'a> {
+        DisplayLine::new(text, self.config.tab_size)
+    }
+
+    /// The text that is inserted when tab is pressed in insert mode.
+    ///
+    /// If tabs are translated to spaces, this is enough spaces to reach the next tabstop.
+    pub fn tab_text(&self) -> String {
+        if !self.config.translate_tabs_to_spaces {
+            return String::from("\t");
+        }
+
+        let column = self
+            .display_line(self.line_text(self.cursor.y))
+            .column_of(self.cursor.x);
+        let tab_size = self.config.tab_size.max(1);
+        (0..tab_size - column % tab_size).map(|_| ' ').collect()
+    }
+
+    /// The text of a line in the cache without its trailing newline, or an empty string if the
+    /// line is invalid.
+    fn line_text(&self, line: usize) -> &str {
+        self.line_cache
+            .get(line)
+            .map(|line| line.text.trim_end_matches('\n'))
+            .unwrap_or_default()
+    }
+
+    /// The text displayed before a row of a line. Only continuation rows of wrapped lines have a
+    /// prefix, and only if `showbreak` fits within the window.
+    fn break_prefix(&self, segment_idx: usize, text_width: usize) -> &str {
+        if self.options.wrap && segment_idx > 0 && str_width(&self.options.showbreak) < text_width {
+            &self.options.showbreak
+        } else {
+            ""
+        }
+    }
+
+    /// The width of the window available for text, excluding the gutter.
+    fn text_width(&self, bounds: &Rect<usize>) -> usize {
+        bounds.size.width.saturating_sub(self.gutter_width()).max(1)
+    }
+
+    /// Returns the buffer coordinate that is one visual row above or below the cursor, keeping the
+    /// screen column of the cursor if possible.
+    ///
+    /// Returns `None` if wrapping is disabled or the cursor is at the start or end of the buffer.
+    pub fn visual_move(&self, bounds: &Rect<usize>, down: bool) -> Option<Coordinate> {
+        if !self.options.wrap {
+            return None;
+        }
+
+        let text_width = self.text_width(bounds);
+        let text = self.display_line(self.line_text(self.cursor.y));
+        let segments = self.segments(&text, text_width);
+        let cursor_column = text.column_of(self.cursor.x);
+        let row = segment_of(&segments, cursor_column);
+        let column =
+            cursor_column - segments[row].start + str_width(self.break_prefix(row, text_width));
+
+        let (line, row) = if down {
+            if row + 1 < segments.len() {
+                (self.cursor.y, row + 1)
+            } else if self.cursor.y + 1 < self.buffer_len() {
+                (self.cursor.y + 1, 0)
+            } else {
+                return None;
+            }
+        } else if row > 0 {
+            (self.cursor.y, row - 1)
+        } else if self.cursor.y > 0 {
+            let row = self.line_segments(self.cursor.y - 1, text_width).len() - 1;
+            (self.cursor.y - 1, row)
+        } else {
+            return None;
+        };
+
+        let text = self.display_line(self.line_cache.get(line)?.text.trim_end_matches('\n'));
+        let segments = self.segments(&text, text_width);
+        let segment = &segments[row];
+        let prefix_width = str_width(self.break_prefix(row, text_width));
+
+        // Like vim, the cursor cannot be placed past the last character of the row.
+        let last = if row + 1 < segments.len() {
+            segment.end - 1
+        } else {
+            text.width().saturating_sub(1).max(segment.start)
+        };
+        let column = (segment.start + column.saturating_sub(prefix_width)).min(last);
+
+        Some(Coordinate::new(text.offset_at(column), line))
+    }
+
+    /// Returns the buffer coordinate of the character displayed at a point on the screen, such as
+    /// the position of a mouse click.
+    ///
+    /// Like vim, points past the end of a row or below the last line select the nearest character,
+    /// and points in the gutter select the first character of the row.
+    pub fn position_at(&self, bounds: &Rect<usize>, point: Coordinate) -> Option<Coordinate> {
+        let text_width = self.text_width(bounds);
+
+        let mut position = (self.offsets.top, self.top_row);
+        for _ in 0..point.y.saturating_sub(bounds.origin.y) {
+            match self.next_row(position, text_width) {
+                Some(row) => position = row,
+                None => break,
+            }
+        }
+        let (line, row) = position;
+
+        let text = self.display_line(self.line_cache.get(line)?.text.trim_end_matches('\n'));
+        let segments = self.segments(&text, text_width);
+        let segment = segments.get(row)?;
+        let prefix_width = str_width(self.break_prefix(row, text_width));
+        let column = point
+            .x
+            .saturating_sub(bounds.origin.x + self.gutter_width() + prefix_width);
+
+        let last = if row + 1 < segments.len() {
+            segment.end - 1
+        } else {
+            text.width().saturating_sub(1).max(segment.start)
+        };
+        let column = (segment.start + column).min(last);
+
+        Some(Coordinate::new(text.offset_at(column), line))
+    }
+
+    /// Scrolls the window by a number of rows without moving the cursor, like the mouse wheel.
+    /// Positive values scroll towards the end of the buffer.
+    ///
+    /// Returns the coordinate that the cursor should move to if it's no longer visible, keeping
+    /// `scrolloff` rows around it.
+    pub fn scroll_by(&mut self, bounds: &Rect<usize>, rows: isize) -> Option<Coordinate> {
+        let text_width = self.text_width(bounds);
+
+        let mut top = (self.offsets.top, self.top_row);
+        for _ in 0..rows.abs() {
+            let row = if rows > 0 {
+                self.next_row(top, text_width)
+            } else {
+                self.previous_row(top, text_width)
+            };
+            match row {
+                Some(row) => top = row,
+                None => break,
+            }
+        }
+        self.offsets.top = top.0;
+        self.top_row = top.1;
+
+        let text = self.display_line(self.line_text(self.cursor.y));
+        let segments = self.segments(&text, text_width);
+        let cursor_column = text.column_of(self.cursor.x);
+        let cursor_row = segment_of(&segments, cursor_column);
+        let cursor = (self.cursor.y, cursor_row);
+
+        // Like `scroll_to`, keep `scrolloff` rows around the cursor unless the window is at the
+        // start of the buffer. The end of the buffer is ignored, which may move the cursor further
+        // than necessary.
+        let mut rows = vec![top];
+        while rows.len() < bounds.size.height {
+            match self.next_row(rows[rows.len() - 1], text_width) {
+                Some(row) => rows.push(row),
+                None => break,
+            }
+        }
+        let scrolloff = self.scrolloff(bounds);
+        let first = if top == (0, 0) {
+            0
+        } else {
+            scrolloff.min(rows.len() - 1)
+        };
+        let last = if rows.len() == bounds.size.height {
+            rows.len() - 1 - scrolloff
+        } else {
+            rows.len() - 1
+        };
+
+        let offset = if cursor < rows[first] {
+            first
+        } else if cursor > rows[last] {
+            last
+        } else {
+            return None;
+        };
+
+        // Keep the screen column of the cursor.
+        let column = cursor_column.saturating_sub(segments[cursor_row].start)
+            + str_width(self.break_prefix(cursor_row, text_width));
+        let point = Coordinate::new(
+            bounds.origin.x + self.gutter_width() + column,
+            bounds.origin.y + offset,
+        );
+        self.position_at(bounds, point)
+    }
+
+    /// The first line of the buffer that is displayed in the window.
+    pub fn top_line(&self) -> usize {
+        self.offsets.top
+    }
+
     /// The total number of lines in the window's buffer.
     pub fn buffer_len(&self) -> usize {
         self.line_cache.len()
@@ -124,31 +557,153 @@ impl Window {
     pub fn scroll_to(&mut self, bounds: &Rect<usize>, coordinate: Coordinate) {
         self.cursor = coordinate;
 
-        if self.cursor.x >= self.offsets.left + bounds.size.width {
-            self.offsets.left = self.cursor.x - (bounds.size.width - 1);
-        } else if self.cursor.x < self.offsets.left {
-            self.offsets.left = self.cursor.x;
+        let text_width = self.text_width(bounds);
+
+        if self.options.wrap {
+            self.scroll_to_wrapped(bounds, text_width);
+            debug!(
+                "scrolled cursor to {:?}, {:?}, row {}",
+                self.cursor, self.offsets, self.top_row
+            );
+            return;
         }
 
-        if self.cursor.y > self.offsets.top + bounds.size.height - 1 {
-            self.offsets.top = self.cursor.y - (bounds.size.height - 1);
-        } else if self.cursor.y < self.offsets.top {
-            self.offsets.top = self.cursor.y;
+        self.top_row = 0;
+
+        // The cursor may be on a double-width character, in which case both columns should be
+        // visible.
+        let text = self.display_line(self.line_text(self.cursor.y));
+        let start = text.column_of(self.cursor.x);
+        let end = text.end_column_of(self.cursor.x + 1);
+
+        if end > self.offsets.left + text_width {
+            self.offsets.left = end - text_width;
+        } else if start < self.offsets.left {
+            self.offsets.left = start;
+        }
+
+        let height = bounds.size.height;
+        let scrolloff = self.scrolloff(bounds);
+        let below = scrolloff.min(self.buffer_len().saturating_sub(self.cursor.y + 1));
+
+        if self.cursor.y + below > self.offsets.top + height - 1 {
+            self.offsets.top = self.cursor.y + below - (height - 1);
+        } else if self.cursor.y < self.offsets.top + scrolloff {
+            self.offsets.top = self.cursor.y.saturating_sub(scrolloff);
         }
 
         debug!("scrolled cursor to {:?}, {:?}", self.cursor, self.offsets);
     }
+
+    /// Updates the scroll position of a window with wrapping enabled, so that the row containing
+    /// the cursor is visible.
+    fn scroll_to_wrapped(&mut self, bounds: &Rect<usize>, text_width: usize) {
+        self.offsets.left = 0;
+
+        let text = self.display_line(self.line_text(self.cursor.y));
+        let cursor_row = segment_of(
+            &self.segments(&text, text_width),
+            text.column_of(self.cursor.x),
+        );
+        let cursor = (self.cursor.y, cursor_row);
+        let scrolloff = self.scrolloff(bounds);
+
+        let mut above = cursor;
+        for _ in 0..scrolloff {
+            match self.previous_row(above, text_width) {
+                Some(row) => above = row,
+                None => break,
+            }
+        }
+
+        if above < (self.offsets.top, self.top_row) {
+            self.offsets.top = above.0;
+            self.top_row = above.1;
+            return;
+        }
+
+        let mut below = cursor;
+        for _ in 0..scrolloff {
+            match self.next_row(below, text_width) {
+                Some(row) => below = row,
+                None => break,
+            }
+        }
+
+        // Walk backwards from the last row that should be visible until we reach the top of the
+        // window. If the window isn't tall enough to reach it, then the top of the window moves
+        // down.
+        let mut position = below;
+        for _ in 0..bounds.size.height.saturating_sub(1) {
+            match self.previous_row(position, text_width) {
+                Some(row) if position > (self.offsets.top, self.top_row) => position = row,
+                _ => return,
+            }
+        }
+
+        if position > (self.offsets.top, self.top_row) {
+            self.offsets.top = position.0;
+            self.top_row = position.1;
+        }
+    }
+
+    /// The number of rows of context to keep above and below the cursor. Like vim, this is limited
+    /// to half of the window.
+    fn scrolloff(&self, bounds: &Rect<usize>) -> usize {
+        self.options
+            .scrolloff
+            .min(bounds.size.height.saturating_sub(1) / 2)
+    }
+
+    /// The visual row before a (line, row) position when wrapping, if any.
+    fn previous_row(
+        &self,
+        (line, row): (usize, usize),
+        text_width: usize,
+    ) -> Option<(usize, usize)> {
+        if row > 0 {
+            Some((line, row - 1))
+        } else if line > 0 {
+            Some((line - 1, self.line_segments(line - 1, text_width).len() - 1))
+        } else {
+            None
+        }
+    }
+
+    /// The visual row after a (line, row) position when wrapping, if any.
+    fn next_row(&self, (line, row): (usize, usize), text_width: usize) -> Option<(usize, usize)> {
+        if row + 1 < self.line_segments(line, text_width).len() {
+            Some((line, row + 1))
+        } else if line + 1 < self.buffer_len() {
+            Some((line + 1, 0))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the index of the segment that contains a column.
+///
+/// Columns past the end of the last segment are considered part of the last segment.
+fn segment_of(segments: &[Range<usize>], column: usize) -> usize {
+    segments
+        .iter()
+        .position(|segment| column < segment.end)
+        .unwrap_or(segments.len() - 1)
 }
 
 #[cfg(test)]
 mod test {
     use std::io::Cursor;
+    use std::path::PathBuf;
 
-    use euclid::{Rect, Size2D};
+    use euclid::{Point2D, Rect, Size2D};
 
     use super::{LineCache, Screen, Window};
-    use crate::editor::styles::Styles;
-    use crate::screen::Coordinate;
+    use crate::editor::status_line::StatusLine;
+    use crate::editor::styles::{Style, Styles};
+    use crate::protocol::{Line, Op, OpKind, Update};
+    use crate::screen::{Attr, Coordinate};
 
     #[test]
     fn cache_smaller_than_window() {
@@ -160,7 +715,7 @@ mod test {
         let buf = Cursor::new(vec![]);
         let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
 
-        window.render(&styles, &bounds, &mut screen).unwrap();
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
     }
 
     #[test]
@@ -173,7 +728,7 @@ mod test {
         let buf = Cursor::new(vec![]);
         let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
 
-        window.render(&styles, &bounds, &mut screen).unwrap();
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
     }
 
     #[test]
@@ -199,4 +754,523 @@ mod test {
         window.scroll_to(&bounds, Coordinate::new(0, 0));
         assert_eq!(window.offsets.top, 0);
     }
+
+    #[test]
+    fn status_line() {
+        let styles = Styles::new();
+        let status_line = StatusLine::default();
+        let bounds = Rect::new(Point2D::new(0, 1), Size2D::new(45, 1));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0", "1", "2", "3"]);
+        window.path = Some(PathBuf::from("/tmp/foo.rs"));
+        window.language = Some(String::from("Rust"));
+        window.cursor = Coordinate::new(4, 1);
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(45, 2), buf).unwrap();
+
+        window.render_status(&status_line, Some("NORMAL"), &styles, &bounds, &mut screen);
+        assert_eq!(
+            screen.line(1),
+            " NORMAL  foo.rs             [Rust]  2:5  50%"
+        );
+
+        window.pristine = false;
+        window.render_status(&status_line, None, &styles, &bounds, &mut screen);
+        assert_eq!(
+            screen.line(1),
+            " foo.rs [+]                 [Rust]  2:5  50%"
+        );
+
+        let bounds = Rect::new(Point2D::new(0, 1), Size2D::new(20, 1));
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(20, 2), buf).unwrap();
+        window.render_status(&status_line, Some("NORMAL"), &styles, &bounds, &mut screen);
+        assert_eq!(screen.line(1), " NORMAL  < 2:5  50%");
+    }
+
+    #[test]
+    fn status_line_width() {
+        let styles = Styles::new();
+        let status_line = StatusLine::default();
+        let bounds = Rect::new(Point2D::new(0, 1), Size2D::new(32, 1));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0", "1"]);
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(32, 2), buf).unwrap();
+        let last = Coordinate::new(31, 1);
+
+        window.path = Some(PathBuf::from("/tmp/日本語😀.rs"));
+        window.render_status(&status_line, Some("NORMAL"), &styles, &bounds, &mut screen);
+        assert_eq!(screen.line(1), " NORMAL  日本語😀.rs   1:1  50%");
+        assert_eq!(screen.attr(last), screen.attr(Coordinate::new(0, 1)));
+
+        window.path = Some(PathBuf::from("/tmp/cafe\u{301}.rs"));
+        window.render_status(&status_line, Some("NORMAL"), &styles, &bounds, &mut screen);
+        assert_eq!(screen.line(1), " NORMAL  cafe\u{301}.rs       1:1  50%");
+        assert_eq!(screen.attr(last), screen.attr(Coordinate::new(0, 1)));
+
+        // The file name is truncated by columns, without splitting a double-width character.
+        let bounds = Rect::new(Point2D::new(0, 1), Size2D::new(25, 1));
+        let mut screen = Screen::new_from_write(Size2D::new(25, 2), Cursor::new(vec![])).unwrap();
+        window.path = Some(PathBuf::from("/tmp/日本語😀.rs"));
+        window.render_status(&status_line, Some("NORMAL"), &styles, &bounds, &mut screen);
+        assert_eq!(screen.line(1), " NORMAL  < .rs  1:1  50%");
+    }
+
+    #[test]
+    fn line_numbers() {
+        let styles = Styles::new();
+        let bounds = Rect::from_size(Size2D::new(10, 4));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["foo", "bar", "baz"]);
+        window.cursor = Coordinate::new(0, 1);
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
+
+        window.options.number = true;
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "  1 foo");
+        assert_eq!(screen.line(1), "  2 bar");
+        assert_eq!(screen.line(2), "  3 baz");
+        assert_eq!(screen.line(3), "~");
+
+        window.options.number = false;
+        window.options.relative_number = true;
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "  1 foo");
+        assert_eq!(screen.line(1), "  0 bar");
+        assert_eq!(screen.line(2), "  1 baz");
+
+        window.options.number = true;
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "  1 foo");
+        assert_eq!(screen.line(1), "  2 bar");
+        assert_eq!(screen.line(2), "  1 baz");
+
+        // The gutter is truncated in a window narrower than it.
+        let bounds = Rect::from_size(Size2D::new(3, 4));
+        let mut screen = Screen::new_from_write(bounds.size, Cursor::new(vec![])).unwrap();
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), " 1");
+        assert_eq!(screen.line(1), " 2");
+        assert_eq!(screen.line(2), " 1");
+    }
+
+    #[test]
+    fn gutter_width() {
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["foo"; 1000]);
+        assert_eq!(window.gutter_width(), 0);
+
+        window.options.number = true;
+        assert_eq!(window.gutter_width(), 5);
+    }
+
+    #[test]
+    fn scrolloff() {
+        let bounds = Rect::from_size(Size2D::new(10, 5));
+
+        let mut window = Window::new();
+        window.line_cache =
+            LineCache::new_from_lines(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        window.options.scrolloff = 1;
+
+        window.scroll_to(&bounds, Coordinate::new(0, 3));
+        assert_eq!(window.offsets.top, 0);
+
+        window.scroll_to(&bounds, Coordinate::new(0, 4));
+        assert_eq!(window.offsets.top, 1);
+
+        window.scroll_to(&bounds, Coordinate::new(0, 9));
+        assert_eq!(window.offsets.top, 5);
+
+        window.scroll_to(&bounds, Coordinate::new(0, 6));
+        assert_eq!(window.offsets.top, 5);
+
+        window.scroll_to(&bounds, Coordinate::new(0, 5));
+        assert_eq!(window.offsets.top, 4);
+
+        // The scrolloff is limited to half of the window.
+        window.options.scrolloff = 100;
+        window.scroll_to(&bounds, Coordinate::new(0, 2));
+        assert_eq!(window.offsets.top, 0);
+        window.scroll_to(&bounds, Coordinate::new(0, 5));
+        assert_eq!(window.offsets.top, 3);
+
+        window.options.wrap = true;
+        window.options.scrolloff = 1;
+        window.line_cache = LineCache::new_from_lines(&["0", "0123456789", "2", "3"]);
+        let bounds = Rect::from_size(Size2D::new(4, 3));
+        window.scroll_to(&bounds, Coordinate::new(0, 0));
+        assert_eq!((window.offsets.top, window.top_row), (0, 0));
+
+        window.scroll_to(&bounds, Coordinate::new(5, 1));
+        assert_eq!((window.offsets.top, window.top_row), (1, 0));
+
+        window.scroll_to(&bounds, Coordinate::new(0, 3));
+        assert_eq!((window.offsets.top, window.top_row), (1, 2));
+
+        window.scroll_to(&bounds, Coordinate::new(0, 1));
+        assert_eq!((window.offsets.top, window.top_row), (0, 0));
+    }
+
+    #[test]
+    fn scroll_to_with_gutter() {
+        let bounds = Rect::from_size(Size2D::new(10, 5));
+
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0123456789abcdef"]);
+        window.options.number = true;
+
+        window.scroll_to(&bounds, Coordinate::new(5, 0));
+        assert_eq!(window.offsets.left, 0);
+
+        window.scroll_to(&bounds, Coordinate::new(6, 0));
+        assert_eq!(window.offsets.left, 1);
+    }
+
+    #[test]
+    fn wrap() {
+        let styles = Styles::new();
+        let bounds = Rect::from_size(Size2D::new(6, 5));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["hello world", "foo"]);
+        window.options.wrap = true;
+        window.options.showbreak = String::from("> ");
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 5), buf).unwrap();
+
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "hello");
+        assert_eq!(screen.line(1), "> worl");
+        assert_eq!(screen.line(2), "> d");
+        assert_eq!(screen.line(3), "foo");
+        assert_eq!(screen.line(4), "~");
+
+        window.options.showbreak = String::new();
+        window.options.linebreak = true;
+        window.options.number = true;
+        let bounds = Rect::from_size(Size2D::new(10, 5));
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "  1 hello");
+        assert_eq!(screen.line(1), "    world");
+        assert_eq!(screen.line(2), "  2 foo");
+    }
+
+    #[test]
+    fn scroll_to_wrapped() {
+        let bounds = Rect::from_size(Size2D::new(4, 3));
+
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0", "0123456789", "2", "3"]);
+        window.options.wrap = true;
+
+        window.scroll_to(&bounds, Coordinate::new(0, 1));
+        assert_eq!((window.offsets.top, window.top_row), (0, 0));
+
+        window.scroll_to(&bounds, Coordinate::new(5, 1));
+        assert_eq!((window.offsets.top, window.top_row), (0, 0));
+
+        window.scroll_to(&bounds, Coordinate::new(8, 1));
+        assert_eq!((window.offsets.top, window.top_row), (1, 0));
+
+        window.scroll_to(&bounds, Coordinate::new(0, 3));
+        assert_eq!((window.offsets.top, window.top_row), (1, 2));
+
+        window.scroll_to(&bounds, Coordinate::new(4, 1));
+        assert_eq!((window.offsets.top, window.top_row), (1, 1));
+
+        window.scroll_to(&bounds, Coordinate::new(0, 0));
+        assert_eq!((window.offsets.top, window.top_row), (0, 0));
+    }
+
+    #[test]
+    fn wide_characters() {
+        let styles = Styles::new();
+        let bounds = Rect::from_size(Size2D::new(6, 4));
+        let mut window = Window::new();
+        window.line_cache =
+            LineCache::new_from_lines(&["日本語です", "cafe\u{301}s", "👨\u{200d}👩\u{200d}👧!"]);
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
+
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "日本語");
+        assert_eq!(screen.line(1), "cafe\u{301}s");
+        assert_eq!(screen.line(2), "👨\u{200d}👩\u{200d}👧!");
+
+        // The double-width character that doesn't fit in the first row is moved to the next row.
+        window.options.wrap = true;
+        let bounds = Rect::from_size(Size2D::new(5, 4));
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "日本");
+        assert_eq!(screen.line(1), "語で");
+        assert_eq!(screen.line(2), "す");
+    }
+
+    #[test]
+    fn scroll_to_wide_characters() {
+        let bounds = Rect::from_size(Size2D::new(4, 5));
+
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["日本語です"]);
+
+        // The third character starts at byte 6 and column 4.
+        window.scroll_to(&bounds, Coordinate::new(3, 0));
+        assert_eq!(window.offsets.left, 0);
+
+        window.scroll_to(&bounds, Coordinate::new(6, 0));
+        assert_eq!(window.offsets.left, 2);
+
+        window.scroll_to(&bounds, Coordinate::new(0, 0));
+        assert_eq!(window.offsets.left, 0);
+
+        window.options.wrap = true;
+        window.cursor = Coordinate::new(3, 0);
+        assert_eq!(
+            window.visual_move(&bounds, true),
+            Some(Coordinate::new(9, 0))
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let mut styles = Styles::new();
+        styles.define(
+            2,
+            Style {
+                bold: true,
+                ..Default::default()
+            },
+        );
+        let bounds = Rect::from_size(Size2D::new(12, 2));
+        let mut window = Window::new();
+        window.config.tab_size = 4;
+        window.line_cache.update(Update {
+            rev: None,
+            ops: vec![Op {
+                op: OpKind::Ins,
+                n: 1,
+                lines: Some(vec![Line {
+                    text: Some(String::from("\tfoo \n")),
+                    cursor: Some(vec![4]),
+                    styles: Some(vec![1, 3, 2]),
+                }]),
+            }],
+            pristine: true,
+        });
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
+
+        // The style span and cursor are shifted by the width of the tab.
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "    foo");
+        assert!(!screen.attr(Coordinate::new(3, 0)).contains(Attr::BOLD));
+        assert!(screen.attr(Coordinate::new(4, 0)).contains(Attr::BOLD));
+        assert!(screen.attr(Coordinate::new(6, 0)).contains(Attr::BOLD));
+        assert!(screen.attr(Coordinate::new(7, 0)).contains(Attr::REVERSE));
+
+        // The primary cursor of the active window is the terminal cursor.
+        window.cursor = Coordinate::new(4, 0);
+        screen.erase();
+        window.render(&styles, &bounds, true, &mut screen).unwrap();
+        assert!(!screen.attr(Coordinate::new(7, 0)).contains(Attr::REVERSE));
+        assert_eq!(screen.cursor(), Some(Coordinate::new(7, 0)));
+
+        window.options.list = true;
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), "    foo $");
+
+        window.options.listchars = "tab:>-,trail:~,eol:$".parse().unwrap();
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+        assert_eq!(screen.line(0), ">---foo~$");
+    }
+
+    #[test]
+    fn tab_text() {
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["a", "\tab"]);
+        assert_eq!(window.tab_text(), "    ");
+
+        window.cursor = Coordinate::new(1, 0);
+        assert_eq!(window.tab_text(), "   ");
+
+        window.cursor = Coordinate::new(2, 1);
+        assert_eq!(window.tab_text(), "   ");
+
+        window.config.translate_tabs_to_spaces = false;
+        assert_eq!(window.tab_text(), "\t");
+    }
+
+    #[test]
+    fn visual_move() {
+        let bounds = Rect::from_size(Size2D::new(4, 10));
+
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0", "0123456789", "2"]);
+        assert_eq!(window.visual_move(&bounds, true), None);
+
+        window.options.wrap = true;
+        window.cursor = Coordinate::new(0, 0);
+        assert_eq!(window.visual_move(&bounds, false), None);
+        assert_eq!(
+            window.visual_move(&bounds, true),
+            Some(Coordinate::new(0, 1))
+        );
+
+        window.cursor = Coordinate::new(2, 1);
+        assert_eq!(
+            window.visual_move(&bounds, true),
+            Some(Coordinate::new(6, 1))
+        );
+        assert_eq!(
+            window.visual_move(&bounds, false),
+            Some(Coordinate::new(0, 0))
+        );
+
+        // The last row of the line is shorter than the cursor column.
+        window.cursor = Coordinate::new(7, 1);
+        assert_eq!(
+            window.visual_move(&bounds, true),
+            Some(Coordinate::new(9, 1))
+        );
+        window.cursor = Coordinate::new(9, 1);
+        assert_eq!(
+            window.visual_move(&bounds, true),
+            Some(Coordinate::new(0, 2))
+        );
+    }
+
+    #[test]
+    fn cursor_line() {
+        let styles = Styles::new();
+        let bounds = Rect::from_size(Size2D::new(6, 3));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["foo", "bar", "baz"]);
+        window.cursor = Coordinate::new(1, 1);
+        window.options.cursor_line = true;
+
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(bounds.size, buf).unwrap();
+        window.render(&styles, &bounds, false, &mut screen).unwrap();
+
+        // Without a theme, the line is underlined across the whole window.
+        for x in 0..6 {
+            assert!(!screen.attr(Coordinate::new(x, 0)).contains(Attr::UNDERLINE));
+            assert!(screen.attr(Coordinate::new(x, 1)).contains(Attr::UNDERLINE));
+        }
+    }
+
+    #[test]
+    fn position_at() {
+        let bounds = Rect::new(Point2D::new(0, 1), Size2D::new(10, 4));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["hello\n", "日本語\n", "\n"]);
+        let at = |window: &Window, x, y| window.position_at(&bounds, Coordinate::new(x, y));
+
+        assert_eq!(at(&window, 1, 1), Some(Coordinate::new(1, 0)));
+
+        // Either column of a double-width character selects it.
+        assert_eq!(at(&window, 2, 2), Some(Coordinate::new(3, 1)));
+        assert_eq!(at(&window, 3, 2), Some(Coordinate::new(3, 1)));
+
+        // Points past the end of a line select its last character, and points below the last line
+        // select the last line.
+        assert_eq!(at(&window, 8, 1), Some(Coordinate::new(4, 0)));
+        assert_eq!(at(&window, 9, 2), Some(Coordinate::new(6, 1)));
+        assert_eq!(at(&window, 5, 3), Some(Coordinate::new(0, 2)));
+        assert_eq!(at(&window, 2, 4), Some(Coordinate::new(0, 2)));
+
+        // Points in the gutter select the first character of the row.
+        window.options.number = true;
+        assert_eq!(window.gutter_width(), 4);
+        assert_eq!(at(&window, 0, 1), Some(Coordinate::new(0, 0)));
+        assert_eq!(at(&window, 3, 2), Some(Coordinate::new(0, 1)));
+        assert_eq!(at(&window, 6, 1), Some(Coordinate::new(2, 0)));
+
+        window.line_cache = LineCache::new();
+        assert_eq!(at(&window, 0, 1), None);
+    }
+
+    #[test]
+    fn position_at_wrapped() {
+        let bounds = Rect::from_size(Size2D::new(4, 4));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0123456789\n", "ab"]);
+        window.options.wrap = true;
+        let at = |window: &Window, x, y| window.position_at(&bounds, Coordinate::new(x, y));
+
+        assert_eq!(at(&window, 3, 0), Some(Coordinate::new(3, 0)));
+        assert_eq!(at(&window, 1, 1), Some(Coordinate::new(5, 0)));
+        assert_eq!(at(&window, 3, 2), Some(Coordinate::new(9, 0)));
+        assert_eq!(at(&window, 3, 3), Some(Coordinate::new(1, 1)));
+
+        // With `linebreak`, rows can end before the edge of the window. Points past the end of a
+        // row select its last character rather than the first character of the next row.
+        window.options.linebreak = true;
+        window.line_cache = LineCache::new_from_lines(&["ab cdef"]);
+        assert_eq!(at(&window, 3, 0), Some(Coordinate::new(2, 0)));
+        assert_eq!(at(&window, 3, 1), Some(Coordinate::new(6, 0)));
+
+        // The prefix of a wrapped row is skipped like the gutter.
+        window.options.linebreak = false;
+        window.options.showbreak = String::from("> ");
+        window.line_cache = LineCache::new_from_lines(&["0123456789"]);
+        assert_eq!(at(&window, 0, 1), Some(Coordinate::new(4, 0)));
+        assert_eq!(at(&window, 3, 1), Some(Coordinate::new(5, 0)));
+    }
+
+    #[test]
+    fn scroll_by() {
+        let bounds = Rect::from_size(Size2D::new(10, 5));
+        let mut window = Window::new();
+        window.line_cache =
+            LineCache::new_from_lines(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        window.options.scrolloff = 1;
+
+        // The cursor is moved down when it would be scrolled into the top `scrolloff` rows.
+        assert_eq!(window.scroll_by(&bounds, 2), Some(Coordinate::new(0, 3)));
+        assert_eq!(window.top_line(), 2);
+
+        // It stays put while it's outside of them.
+        window.cursor = Coordinate::new(0, 4);
+        assert_eq!(window.scroll_by(&bounds, -1), None);
+        assert_eq!(window.top_line(), 1);
+
+        // And it's moved up when it would be scrolled into the bottom `scrolloff` rows.
+        assert_eq!(window.scroll_by(&bounds, -1), Some(Coordinate::new(0, 3)));
+        assert_eq!(window.top_line(), 0);
+
+        // Scrolling stops at the start and end of the buffer.
+        window.cursor = Coordinate::new(0, 1);
+        assert_eq!(window.scroll_by(&bounds, -3), None);
+        assert_eq!(window.top_line(), 0);
+        assert_eq!(window.scroll_by(&bounds, 100), Some(Coordinate::new(0, 9)));
+        assert_eq!(window.top_line(), 9);
+    }
+
+    #[test]
+    fn scroll_by_wrapped() {
+        let bounds = Rect::from_size(Size2D::new(4, 3));
+        let mut window = Window::new();
+        window.line_cache = LineCache::new_from_lines(&["0", "0123456789ab", "2", "3"]);
+        window.options.wrap = true;
+        window.cursor = Coordinate::new(6, 1);
+
+        // The screen column of the cursor is kept when it's moved to another row.
+        assert_eq!(window.scroll_by(&bounds, 1), None);
+        assert_eq!(window.scroll_by(&bounds, 2), Some(Coordinate::new(10, 1)));
+        assert_eq!((window.offsets.top, window.top_row), (1, 2));
+
+        // Unless the row is too short.
+        window.line_cache = LineCache::new_from_lines(&["0", "0123456789", "2", "3"]);
+        window.scroll_by(&bounds, -3);
+        assert_eq!(window.scroll_by(&bounds, 3), Some(Coordinate::new(9, 1)));
+    }
 }
@@ -0,0 +1,86 @@
+//! Soft wrapping of lines that are too long to fit in a window.
+
+use std::ops::Range;
+
+use super::display_line::DisplayLine;
+
+/// Splits a line into the range of columns displayed on each screen row.
+///
+/// The first row of the line may be `first_width` columns wide, and subsequent rows may be `width`
+/// columns wide. If `linebreak` is true, lines are broken after whitespace where possible instead
+/// of in the middle of a word. Double-width characters are never split across rows.
+///
+/// The range of the last row always spans the full width of the row, even if the line is shorter,
+/// so that a cursor placed after the last character is still considered part of the row.
+pub fn wrap_line(
+    line: &DisplayLine<'_>,
+    first_width: usize,
+    width: usize,
+    linebreak: bool,
+) -> Vec<Range<usize>> {
+    let mut rows = vec![];
+    let mut start = 0;
+    let mut row_width = first_width.max(1);
+    let mut break_after = None;
+
+    for cluster in line.clusters() {
+        while cluster.column + cluster.width > start + row_width && cluster.column > start {
+            let end = match break_after {
+                Some(column) if linebreak => column,
+                _ => cluster.column,
+            };
+
+            rows.push(start..end);
+            start = end;
+            row_width = width.max(1);
+            break_after = None;
+        }
+
+        if cluster.is_whitespace() {
+            break_after = Some(cluster.column + cluster.width);
+        }
+    }
+
+    rows.push(start..start + row_width);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wrap_line;
+    use crate::editor::display_line::DisplayLine;
+
+    #[test]
+    fn short_line() {
+        let wrap = |text, width| wrap_line(&DisplayLine::new(text, 8), width, width, false);
+        assert_eq!(wrap("foo", 10), vec![0..10]);
+        assert_eq!(wrap("", 10), vec![0..10]);
+        assert_eq!(wrap("0123456789", 10), vec![0..10]);
+    }
+
+    #[test]
+    fn wrap() {
+        assert_eq!(
+            wrap_line(&DisplayLine::new("0123456789abcdefghij", 8), 8, 6, false),
+            vec![0..8, 8..14, 14..20]
+        );
+    }
+
+    #[test]
+    fn linebreak() {
+        let line = DisplayLine::new("the quick brown fox", 8);
+        assert_eq!(wrap_line(&line, 12, 12, false), vec![0..12, 12..24]);
+        assert_eq!(wrap_line(&line, 12, 12, true), vec![0..10, 10..22]);
+
+        // Words that are longer than the row are broken anyway.
+        let line = DisplayLine::new("supercalifragilistic", 8);
+        assert_eq!(wrap_line(&line, 12, 12, true), vec![0..12, 12..24]);
+    }
+
+    #[test]
+    fn wide_characters() {
+        // The third character doesn't fit on the first row, so it is moved to the next row.
+        let line = DisplayLine::new("日本語です", 8);
+        assert_eq!(wrap_line(&line, 5, 5, false), vec![0..4, 4..8, 8..13]);
+    }
+}
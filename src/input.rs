@@ -0,0 +1,333 @@
+//! Reading input events from the terminal.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Condvar, Mutex};
+
+use termion::event::{self, Key, MouseEvent};
+
+/// Marks the beginning and end of pasted text while bracketed paste is enabled.
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// An input event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(Key),
+    Mouse(MouseEvent),
+
+    /// Text that was pasted into the terminal.
+    Paste(String),
+
+    /// A sequence that isn't understood.
+    Unsupported(Vec<u8>),
+}
+
+impl From<event::Event> for Event {
+    fn from(event: event::Event) -> Self {
+        match event {
+            event::Event::Key(key) => Event::Key(key),
+            event::Event::Mouse(mouse) => Event::Mouse(mouse),
+            event::Event::Unsupported(bytes) => Event::Unsupported(bytes),
+        }
+    }
+}
+
+/// An iterator over the input events read from the terminal.
+///
+/// Events are parsed by termion, except for pasted text, which termion doesn't recognize.
+pub struct Events<R> {
+    source: R,
+
+    /// Bytes that have been read but not parsed.
+    buf: Vec<u8>,
+}
+
+impl<R: Read> Events<R> {
+    pub fn new(source: R) -> Self {
+        Events {
+            source,
+            buf: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<usize> {
+        fill(&mut self.source, &mut self.buf)
+    }
+
+    fn paste(&mut self) -> io::Result<Event> {
+        self.buf.drain(..PASTE_START.len());
+
+        let mut text = Vec::new();
+        loop {
+            if let Some(end) = self
+                .buf
+                .windows(PASTE_END.len())
+                .position(|window| window == PASTE_END)
+            {
+                text.extend(self.buf.drain(..end));
+                self.buf.drain(..PASTE_END.len());
+                break;
+            }
+
+            // The end marker may have been split between reads.
+            let complete = self.buf.len().saturating_sub(PASTE_END.len() - 1);
+            text.extend(self.buf.drain(..complete));
+            if self.fill()? == 0 {
+                text.append(&mut self.buf);
+                break;
+            }
+        }
+
+        // Terminals send newlines as carriage returns, like the enter key.
+        let text = String::from_utf8_lossy(&text)
+            .replace("\r\n", "\n")
+            .replace('\r', "\n");
+        Ok(Event::Paste(text))
+    }
+
+    fn event(&mut self) -> io::Result<Event> {
+        let first = self.buf.remove(0);
+        let mut raw = vec![first];
+
+        let result = {
+            let Events { source, buf } = self;
+            let mut bytes = Bytes {
+                source,
+                buf,
+                raw: &mut raw,
+            };
+            event::parse_event(first, &mut bytes)
+        };
+
+        match result {
+            Ok(event) => Ok(event.into()),
+            Err(ref e) if e.kind() == io::ErrorKind::Other => Ok(Event::Unsupported(raw)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The bytes that termion parses an event from, taken from the buffer and then read from the
+/// source. The bytes are recorded in `raw`, so that unsupported sequences can be reported.
+struct Bytes<'a, R> {
+    source: &'a mut R,
+    buf: &'a mut Vec<u8>,
+    raw: &'a mut Vec<u8>,
+}
+
+impl<'a, R: Read> Iterator for Bytes<'a, R> {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<io::Result<u8>> {
+        if self.buf.is_empty() {
+            match fill(self.source, self.buf) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        let byte = self.buf.remove(0);
+        self.raw.push(byte);
+        Some(Ok(byte))
+    }
+}
+
+impl<R: Read> Iterator for Events<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<io::Result<Event>> {
+        if self.buf.is_empty() {
+            match self.fill() {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        // Escape sequences arrive all at once, so an escape byte at the end of the input is the
+        // escape key.
+        if self.buf == b"\x1b" {
+            self.buf.clear();
+            return Some(Ok(Event::Key(Key::Esc)));
+        }
+
+        // Wait for the rest of a paste marker that was split between reads.
+        while self.buf.len() < PASTE_START.len() && PASTE_START.starts_with(&self.buf) {
+            match self.fill() {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        if self.buf.starts_with(PASTE_START) {
+            Some(self.paste())
+        } else {
+            Some(self.event())
+        }
+    }
+}
+
+/// The terminal that input is read from.
+///
+/// Reading can be paused while another process uses the terminal, such as a shell started by the
+/// editor, so that its input isn't stolen.
+pub struct Tty {
+    file: File,
+    pause: PauseHandle,
+}
+
+impl Tty {
+    pub fn open() -> io::Result<Self> {
+        Ok(Tty {
+            file: termion::get_tty()?,
+            pause: PauseHandle::default(),
+        })
+    }
+
+    /// Returns a handle that pauses reading from the terminal.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.pause.clone()
+    }
+}
+
+impl Read for Tty {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // Wait for input without reading it, so that it's left for another process if reading
+            // is paused in the meantime.
+            let mut fd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut fd, 1, -1) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            let (paused, resumed) = &*self.pause.0;
+            let mut paused = paused.lock().unwrap();
+            if !*paused {
+                // The input is available, so this doesn't block while holding the lock.
+                return self.file.read(buf);
+            }
+
+            while *paused {
+                paused = resumed.wait(paused).unwrap();
+            }
+        }
+    }
+}
+
+/// A handle that pauses reading from a `Tty`.
+#[derive(Debug, Clone, Default)]
+pub struct PauseHandle(Arc<(Mutex<bool>, Condvar)>);
+
+impl PauseHandle {
+    /// Stops reading from the terminal until the returned guard is dropped. Input that was already
+    /// read is still delivered.
+    pub fn pause(&self) -> PauseGuard {
+        *(self.0).0.lock().unwrap() = true;
+        PauseGuard(self.clone())
+    }
+}
+
+/// Resumes reading from the terminal when dropped.
+#[must_use]
+pub struct PauseGuard(PauseHandle);
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        let (paused, resumed) = &*(self.0).0;
+        *paused.lock().unwrap() = false;
+        resumed.notify_all();
+    }
+}
+
+/// Reads the bytes that are available into a buffer, returning the number of bytes read.
+fn fill(source: &mut impl Read, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let mut chunk = [0; 1024];
+    let n = source.read(&mut chunk)?;
+    buf.extend_from_slice(&chunk[..n]);
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use termion::event::{Key, MouseButton, MouseEvent};
+
+    use super::{Event, Events};
+
+    /// A reader that returns its input in chunks, like a terminal.
+    struct Chunks(Vec<&'static [u8]>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    fn events(chunks: Vec<&'static [u8]>) -> Vec<Event> {
+        Events::new(Chunks(chunks))
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            events(vec![b"a\x1b", b"\x1bb", b"\x1b[A", b"\x1b[<0;3;4M"]),
+            vec![
+                Event::Key(Key::Char('a')),
+                Event::Key(Key::Esc),
+                Event::Key(Key::Alt('b')),
+                Event::Key(Key::Up),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 3, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(
+            events(vec![b"\x1b[99~x"]),
+            vec![
+                Event::Unsupported(b"\x1b[99~".to_vec()),
+                Event::Key(Key::Char('x')),
+            ]
+        );
+    }
+
+    #[test]
+    fn paste() {
+        assert_eq!(
+            events(vec![b"i\x1b[200~fn main() {\r\t\x1b[A}\x1b[201~\x1b"]),
+            vec![
+                Event::Key(Key::Char('i')),
+                Event::Paste(String::from("fn main() {\n\t\x1b[A}")),
+                Event::Key(Key::Esc),
+            ]
+        );
+
+        // The markers and the text may be split between reads.
+        assert_eq!(
+            events(vec![b"\x1b[20", b"0~h\xc3", b"\xa9llo\x1b[2", b"01~"]),
+            vec![Event::Paste(String::from("héllo"))]
+        );
+    }
+}
@@ -2,9 +2,14 @@
 
 mod core;
 mod editor;
+mod input;
 mod protocol;
 mod screen;
 
-pub use crate::core::Core;
+pub use crate::core::{
+    in_process, pipe, Core, InProcess, PipeReader, PipeWriter, Process, Tcp, Transport, UnixSocket,
+};
 pub use crate::editor::Editor;
+pub use crate::input::{Event, Events, PauseHandle, Tty};
 pub use crate::protocol::Notification;
+pub use crate::screen::ColorDepth;
@@ -47,11 +47,31 @@ pub enum Request {
         #[serde(skip_serializing_if = "Option::is_none")]
         file_path: Option<PathBuf>,
     },
+
+    Edit {
+        #[serde(flatten)]
+        method: EditRequest,
+        view_id: ViewId,
+    },
+}
+
+/// Edits that return a value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum EditRequest {
+    /// Returns the selected text, or `null` if the selection is empty.
+    Copy,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Response {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// The result, which may be `null`. It is only `None` if it's missing.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_present",
+        skip_serializing_if = "Option::is_none"
+    )]
     result: Option<Value>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -68,14 +88,19 @@ impl Response {
                 Ok(serde_json::from_value(result).map_err(CoreError::Protocol)?)
             }
             (None, Some(error)) => Err(CoreError::BadResponse(error)),
-            _ => {
-                // FIXME: This should be an error in deserialization
-                panic!("expected exactly one of `result` or `error`");
-            }
+            _ => Err(CoreError::InvalidResponse),
         }
     }
 }
 
+/// Deserializes a value that is present, including `null`, as `Some`.
+fn deserialize_present<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Value::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
@@ -96,11 +121,22 @@ pub enum Notification {
         view_id: ViewId,
     },
 
+    // Frontend -> Backend
+    CloseView {
+        view_id: ViewId,
+    },
+
     // Frontend -> Backend
     SetTheme {
         theme_name: String,
     },
 
+    // Frontend -> Backend
+    ModifyUserConfig {
+        domain: ConfigDomain,
+        changes: ConfigChanges,
+    },
+
     // Backend -> Frontend
     AvailableThemes {
         themes: Vec<String>,
@@ -134,6 +170,12 @@ pub enum Notification {
         queries: Vec<Value>,
     },
 
+    // Backend -> Frontend
+    LanguageChanged {
+        view_id: ViewId,
+        language_id: String,
+    },
+
     // Backend -> Frontend
     PluginStarted {
         view_id: ViewId,
@@ -167,27 +209,86 @@ pub enum Notification {
         name: String,
         theme: ThemeSettings,
     },
+
+    /// Sent by `Core`, rather than the core process, when the core exits.
+    #[serde(skip)]
+    CoreExited,
+
+    /// Sent by `Core`, rather than the core process, when the core sends a message that can't be
+    /// handled.
+    #[serde(skip)]
+    InvalidMessage(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
 pub enum EditMethod {
-    Insert { chars: String },
+    Insert {
+        chars: String,
+    },
     Scroll(u16, u16),
     DeleteBackward,
+    DeleteForward,
     MoveUp,
     MoveDown,
     MoveLeft,
     MoveRight,
     MoveWordLeft,
     MoveWordRight,
+    SelectAll,
+    Gesture {
+        line: usize,
+        col: usize,
+        ty: GestureType,
+    },
+
+    /// Extends the selection to a position while the mouse is dragged: the line, the column and
+    /// the modifier keys that are held.
+    Drag(usize, usize, u64),
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureType {
+    PointSelect,
+}
+
+/// The colors of a theme that aren't associated with a scope.
+///
+/// These correspond to the global settings of a TextMate theme. Every setting is optional, so
+/// frontends must fall back to their own defaults.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ThemeSettings {
     pub foreground: Option<Color>,
     pub background: Option<Color>,
+    pub caret: Option<Color>,
+    pub line_highlight: Option<Color>,
+    pub misspelling: Option<Color>,
+    pub minimap_border: Option<Color>,
+    pub accent: Option<Color>,
+    pub popup_css: Option<String>,
+    pub phantom_css: Option<String>,
+    pub bracket_contents_foreground: Option<Color>,
+    pub brackets_foreground: Option<Color>,
+    pub brackets_background: Option<Color>,
+    pub tags_foreground: Option<Color>,
+    pub highlight: Option<Color>,
+    pub find_highlight: Option<Color>,
+    pub find_highlight_foreground: Option<Color>,
+    pub gutter: Option<Color>,
+    pub gutter_foreground: Option<Color>,
+    pub selection: Option<Color>,
+    pub selection_foreground: Option<Color>,
+    pub selection_background: Option<Color>,
+    pub selection_border: Option<Color>,
+    pub inactive_selection: Option<Color>,
+    pub inactive_selection_foreground: Option<Color>,
+    pub guide: Option<Color>,
+    pub active_guide: Option<Color>,
+    pub stack_guide: Option<Color>,
+    pub highlight_foreground: Option<Color>,
+    pub shadow: Option<Color>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -255,6 +356,59 @@ mod tests {
         assert_eq!(json, actual);
     }
 
+    #[test]
+    fn copy() {
+        let req = Message::Request {
+            id: 2,
+            req: Request::Edit {
+                method: EditRequest::Copy,
+                view_id: ViewId(String::from("view-id-1")),
+            },
+        };
+        let json = json!({
+            "id": 2,
+            "method": "edit",
+            "params": {
+                "method": "copy",
+                "view_id": "view-id-1",
+            },
+        });
+
+        let actual: Message = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(req, actual);
+
+        let actual = serde_json::to_value(req).unwrap();
+        assert_eq!(json, actual);
+    }
+
+    #[test]
+    fn response_results() {
+        let res = |json| match serde_json::from_value(json).unwrap() {
+            Message::Response { id: 0, res } => res,
+            message => panic!("expected a response, got {:?}", message),
+        };
+
+        let copied: Option<String> = res(json!({ "id": 0, "result": null }))
+            .into_result()
+            .unwrap();
+        assert_eq!(copied, None);
+
+        match res(json!({ "id": 0, "error": "no view" })).into_result::<Value>() {
+            Err(CoreError::BadResponse(error)) => assert_eq!(error, json!("no view")),
+            result => panic!("expected a bad response, got {:?}", result),
+        }
+
+        for json in &[
+            json!({ "id": 0 }),
+            json!({ "id": 0, "result": 1, "error": "no view" }),
+        ] {
+            match res(json.clone()).into_result::<Value>() {
+                Err(CoreError::InvalidResponse) => (),
+                result => panic!("expected an invalid response, got {:?}", result),
+            }
+        }
+    }
+
     #[test]
     fn scroll() {
         let not = Notification::Edit {
@@ -274,17 +428,99 @@ mod tests {
         assert_eq!(serde_json::to_value(not).unwrap(), json);
     }
 
+    #[test]
+    fn modify_user_config() {
+        let not = Notification::ModifyUserConfig {
+            domain: ConfigDomain::UserOverride(ViewId(String::from("view-id-1"))),
+            changes: ConfigChanges {
+                tab_size: Some(2),
+                ..Default::default()
+            },
+        };
+
+        let json = json!({
+            "method": "modify_user_config",
+            "params": {
+                "domain": { "user_override": "view-id-1" },
+                "changes": { "tab_size": 2 },
+            },
+        });
+
+        assert_eq!(serde_json::to_value(not).unwrap(), json);
+
+        let not = Notification::ModifyUserConfig {
+            domain: ConfigDomain::General,
+            changes: ConfigChanges {
+                translate_tabs_to_spaces: Some(false),
+                ..Default::default()
+            },
+        };
+
+        let json = json!({
+            "method": "modify_user_config",
+            "params": {
+                "domain": "general",
+                "changes": { "translate_tabs_to_spaces": false },
+            },
+        });
+
+        assert_eq!(serde_json::to_value(not).unwrap(), json);
+    }
+
+    #[test]
+    fn gesture() {
+        let not = Notification::Edit {
+            method: EditMethod::Gesture {
+                line: 1,
+                col: 5,
+                ty: GestureType::PointSelect,
+            },
+            view_id: ViewId(String::from("view-id-1")),
+        };
+
+        let json = json!({
+            "method": "edit",
+            "params": {
+                "method": "gesture",
+                "params": { "line": 1, "col": 5, "ty": "point_select" },
+                "view_id": "view-id-1",
+            },
+        });
+
+        assert_eq!(serde_json::to_value(not).unwrap(), json);
+    }
+
+    #[test]
+    fn drag() {
+        let not = Notification::Edit {
+            method: EditMethod::Drag(3, 7, 0),
+            view_id: ViewId(String::from("view-id-1")),
+        };
+
+        let json = json!({
+            "method": "edit",
+            "params": {
+                "method": "drag",
+                "params": [3, 7, 0],
+                "view_id": "view-id-1",
+            },
+        });
+
+        assert_eq!(serde_json::to_value(not).unwrap(), json);
+    }
+
     #[test]
     fn config_changed() {
         let not = Notification::ConfigChanged {
             view_id: ViewId(String::from("view-id-2")),
             changes: ConfigChanges {
+                tab_size: Some(4),
                 other: {
                     let mut map = BTreeMap::new();
-                    map.insert("tab_size".to_string(), json!(4));
                     map.insert("theme".to_string(), json!("Solarized (dark)"));
                     map
                 },
+                ..Default::default()
             },
         };
 
@@ -299,6 +535,61 @@ mod tests {
             },
         });
 
-        assert_eq!(serde_json::to_value(not).unwrap(), json);
+        assert_eq!(serde_json::to_value(&not).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Notification>(json).unwrap(), not);
+    }
+
+    #[test]
+    fn theme_changed() {
+        let json = json!({
+            "method": "theme_changed",
+            "params": {
+                "name": "InspiredGitHub",
+                "theme": {
+                    "foreground": { "r": 50, "g": 50, "b": 50, "a": 255 },
+                    "background": { "r": 255, "g": 255, "b": 255, "a": 255 },
+                    "caret": { "r": 50, "g": 50, "b": 50, "a": 255 },
+                    "line_highlight": { "r": 245, "g": 245, "b": 245, "a": 255 },
+                    "selection": { "r": 200, "g": 222, "b": 255, "a": 255 },
+                    "brackets_options": "Underline",
+                    "popup_css": null,
+                },
+            },
+        });
+
+        assert_eq!(
+            serde_json::from_value::<Notification>(json).unwrap(),
+            Notification::ThemeChanged {
+                name: String::from("InspiredGitHub"),
+                theme: ThemeSettings {
+                    foreground: Some(Color {
+                        r: 50,
+                        g: 50,
+                        b: 50
+                    }),
+                    background: Some(Color {
+                        r: 255,
+                        g: 255,
+                        b: 255,
+                    }),
+                    caret: Some(Color {
+                        r: 50,
+                        g: 50,
+                        b: 50
+                    }),
+                    line_highlight: Some(Color {
+                        r: 245,
+                        g: 245,
+                        b: 245,
+                    }),
+                    selection: Some(Color {
+                        r: 200,
+                        g: 222,
+                        b: 255,
+                    }),
+                    ..Default::default()
+                },
+            }
+        );
     }
 }
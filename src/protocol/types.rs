@@ -19,8 +19,44 @@ pub struct Plugin {
     pub running: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The set of views affected by a change to the configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigDomain {
+    /// Every view.
+    General,
+
+    /// Views of buffers with the given language.
+    Syntax(String),
+
+    /// A single view. Changes in this domain take precedence over the other domains.
+    UserOverride(ViewId),
+}
+
+/// A set of changes to the configuration of a view. Only the settings that changed are present.
+///
+/// Settings that the frontend doesn't interpret (such as the font settings, which don't apply to a
+/// terminal) are collected in `other`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ConfigChanges {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_size: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translate_tabs_to_spaces: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap_width: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_indent: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_ending: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scroll_past_end: Option<bool>,
+
     #[serde(flatten)]
     pub other: BTreeMap<String, Value>,
 }
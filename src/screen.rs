@@ -1,23 +1,26 @@
-use std::io::{self, Stdout, Write};
+use std::io::{self, Write};
 
 use bitflags::bitflags;
-use euclid::{Point2D, Size2D};
+use euclid::{Point2D, Rect, Size2D};
 use log::*;
-use ndarray::prelude::*;
-use termion::{
-    clear,
-    color::{Bg, Fg},
-    cursor,
-    raw::{IntoRawMode, RawTerminal},
-    screen::AlternateScreen,
-    style,
-};
+use ndarray::{prelude::*, s};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::editor::styles::Style;
 
+mod capabilities;
 mod color;
+mod diff;
+mod terminal;
+mod terminfo;
+mod width;
 
-pub use self::color::Color;
+use self::capabilities::Capabilities;
+use self::diff::{Painter, Scroll};
+
+pub use self::color::{Color, ColorDepth};
+pub use self::terminal::{Output, Terminal};
+pub use self::width::{grapheme_width, str_width};
 
 type Buffer = Array2<Cell>;
 
@@ -27,22 +30,78 @@ pub type Coordinate = Point2D<usize>;
 bitflags! {
     /// Terminal video attributes.
     #[derive(Default)]
-    pub struct Attr: u8 {
+    pub struct Attr: u16 {
         const BOLD = 1;
         const ITALIC = 1 << 1;
         const UNDERLINE = 1 << 2;
         const REVERSE = 1 << 3;
+        const DIM = 1 << 4;
+        const BLINK = 1 << 5;
+        const STRIKETHROUGH = 1 << 6;
+        const DOUBLE_UNDERLINE = 1 << 7;
+        const CURLY_UNDERLINE = 1 << 8;
+        const DOTTED_UNDERLINE = 1 << 9;
+
+        /// Every style of underline. A cell has at most one of them.
+        const UNDERLINES = Self::UNDERLINE.bits
+            | Self::DOUBLE_UNDERLINE.bits
+            | Self::CURLY_UNDERLINE.bits
+            | Self::DOTTED_UNDERLINE.bits;
+    }
+}
+
+/// The shape of the terminal cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
+/// The text displayed in a cell.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Content {
+    Empty,
+
+    /// A grapheme cluster consisting of a single character.
+    Char(char),
+
+    /// A grapheme cluster consisting of multiple characters, such as a base character followed by
+    /// combining marks.
+    Cluster(Box<str>),
+
+    /// The second column of a double-width grapheme cluster in the previous cell.
+    Continuation,
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Empty
+    }
+}
+
+impl Content {
+    fn from_grapheme(grapheme: &str) -> Self {
+        let mut chars = grapheme.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => Content::Empty,
+            (Some(c), None) => Content::Char(c),
+            _ => Content::Cluster(grapheme.into()),
+        }
     }
 }
 
 /// A single position in the terminal display.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 struct Cell {
-    /// The character that should be displayed by this cell.
-    c: Option<char>,
+    /// The text that should be displayed by this cell.
+    content: Content,
     fg: Option<Color>,
     bg: Option<Color>,
     attr: Attr,
+
+    /// The color of underlines, if it differs from the foreground color.
+    underline_color: Option<Color>,
 }
 
 /// An ncurses-like abstraction over the terminal screen.
@@ -51,7 +110,7 @@ struct Cell {
 /// writing the appropriate escape sequences to the screen by calling `refresh()`.
 ///
 /// Note that unlike raw escape sequences, all indices expected by this struct are 0-based.
-pub struct Screen<W = AlternateScreen<RawTerminal<Stdout>>>
+pub struct Screen<W = Terminal>
 where
     W: Write,
 {
@@ -61,16 +120,39 @@ where
     /// A buffer containing what is currently displayed on the screen.
     cur_buf: Buffer,
 
+    /// The number of colors that the terminal supports. Colors are approximated if necessary.
+    color_depth: ColorDepth,
+
+    /// The escape sequences understood by the terminal.
+    capabilities: Capabilities,
+
+    /// The position and shape of the terminal cursor at the next refresh. The cursor is hidden if
+    /// it has no position.
+    cursor: Option<Coordinate>,
+    cursor_shape: CursorShape,
+
+    /// The position and shape of the terminal cursor that is currently displayed.
+    cur_cursor: Option<Coordinate>,
+    cur_cursor_shape: Option<CursorShape>,
+
+    /// Whether mouse reporting is turned on.
+    mouse: bool,
+
     out: W,
 }
 
 impl Screen {
-    pub fn new(size: Size2D<usize>) -> io::Result<Self> {
-        let mut screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
-        write!(screen, "{}{}", cursor::Hide, clear::All)?;
-        screen.flush()?;
+    pub fn new(size: Size2D<usize>, color_depth: ColorDepth) -> io::Result<Self> {
+        let capabilities = Capabilities::from_env();
+
+        let mut terminal = Terminal::new(capabilities.restore())?;
+        terminal.write_all(&capabilities.setup())?;
+        terminal.flush()?;
 
-        Self::new_from_write(size, screen)
+        let mut screen = Self::new_from_write(size, terminal)?;
+        screen.color_depth = color_depth;
+        screen.capabilities = capabilities;
+        Ok(screen)
     }
 
     pub fn new_from_write<W>(size: Size2D<usize>, write: W) -> io::Result<Screen<W>>
@@ -81,43 +163,155 @@ impl Screen {
         Ok(Screen {
             cur_buf: buf.clone(),
             buf,
+            color_depth: ColorDepth::TrueColor,
+            capabilities: Capabilities::xterm(),
+            cursor: None,
+            cursor_shape: CursorShape::Block,
+            cur_cursor: None,
+            cur_cursor_shape: None,
+            mouse: false,
             out: write,
         })
     }
 }
 
+impl<W: Output> Screen<W> {
+    /// Restores the terminal to the state it was in before the screen was created, so that
+    /// another program can use it.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        self.out.suspend()
+    }
+
+    /// Prepares the terminal for drawing after `suspend`. The whole screen is drawn at the next
+    /// refresh.
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.out.resume()?;
+        self.out.write_all(&self.capabilities.setup())?;
+        if self.mouse {
+            self.out.write_all(self.capabilities.mouse(true).unwrap())?;
+        }
+        self.out.flush()?;
+
+        self.cur_buf.fill(Cell::default());
+        self.cur_cursor = None;
+        self.cur_cursor_shape = None;
+        Ok(())
+    }
+}
+
 impl<W: Write> Screen<W> {
+    /// The size of the screen, in cells.
+    pub fn size(&self) -> Size2D<usize> {
+        let (rows, cols) = self.buf.dim();
+        Size2D::new(cols, rows)
+    }
+
+    /// Applies a style to `n` cells, starting at a coordinate.
+    ///
+    /// Styles are layered: colors that the style doesn't specify are left unchanged, so a syntax
+    /// highlighting span that only sets the foreground keeps the background of the line.
     pub fn apply_style(&mut self, Coordinate { y, x, .. }: Coordinate, n: usize, style: &Style) {
         let mut row = self.buf.row_mut(y);
 
         for i in x..(x + n) {
-            row[i].fg = style.fg;
-            row[i].bg = style.bg;
+            if style.fg.is_some() {
+                row[i].fg = style.fg;
+            }
 
-            if style.bold {
-                row[i].attr |= Attr::BOLD;
+            if style.bg.is_some() {
+                row[i].bg = style.bg;
             }
 
-            if style.italic {
-                row[i].attr |= Attr::ITALIC;
+            if style.underline_color.is_some() {
+                row[i].underline_color = style.underline_color;
             }
 
-            if style.underline {
-                row[i].attr |= Attr::UNDERLINE;
+            // The underline of the style replaces the existing one.
+            let attr = style.attr();
+            if attr.intersects(Attr::UNDERLINES) {
+                row[i].attr -= Attr::UNDERLINES;
             }
+            row[i].attr |= attr;
         }
     }
 
-    pub fn write_str(&mut self, Coordinate { x, y, .. }: Coordinate, s: &str) {
+    /// Writes a string to the screen.
+    ///
+    /// Each grapheme cluster in the string occupies a single cell, or two cells if the cluster is
+    /// double-width. Text that does not fit within the row is discarded. Returns the number of
+    /// columns written.
+    pub fn write_str(&mut self, Coordinate { x, y, .. }: Coordinate, s: &str) -> usize {
         let mut row = self.buf.row_mut(y);
+        let mut col = x;
+
+        for grapheme in s.graphemes(true) {
+            let width = grapheme_width(grapheme);
+            if col + width > row.len() {
+                break;
+            }
+
+            // Overwriting either half of a double-width character erases the other half.
+            if col > 0 && row[col].content == Content::Continuation {
+                row[col - 1].content = Content::Empty;
+            }
+            if col + width < row.len() && row[col + width].content == Content::Continuation {
+                row[col + width].content = Content::Empty;
+            }
 
-        for (i, c) in s.chars().enumerate() {
-            row[x + i].c = Some(c);
+            row[col].content = Content::from_grapheme(grapheme);
+            if width == 2 {
+                row[col + 1].content = Content::Continuation;
+            }
+
+            col += width;
         }
+
+        col - x
     }
 
-    pub fn draw_cursor(&mut self, Coordinate { x, y, .. }: Coordinate) {
-        self.buf.row_mut(y)[x].attr |= Attr::REVERSE;
+    /// Places the terminal cursor at a coordinate after the next refresh, or hides it.
+    ///
+    /// The terminal cursor should be used for the primary cursor, so that screen readers and input
+    /// methods can find it.
+    pub fn set_cursor(&mut self, cursor: Option<Coordinate>) {
+        self.cursor = cursor;
+    }
+
+    /// Turns reporting of mouse events on or off.
+    pub fn set_mouse(&mut self, enabled: bool) -> io::Result<()> {
+        match self.capabilities.mouse(enabled) {
+            Some(sequence) => {
+                self.mouse = enabled;
+                self.out.write_all(sequence)?;
+                self.out.flush()
+            }
+            None => {
+                warn!("the terminal does not report mouse events");
+                Ok(())
+            }
+        }
+    }
+
+    /// Changes the shape of the terminal cursor at the next refresh.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+    }
+
+    /// Draws a block cursor with the given style, for cursors other than the terminal cursor.
+    pub fn draw_cursor(&mut self, Coordinate { mut x, y, .. }: Coordinate, style: &Style) {
+        let row = self.buf.row(y);
+
+        // The cursor is drawn over the entire double-width character.
+        if x > 0 && row[x].content == Content::Continuation {
+            x -= 1;
+        }
+        let width = if x + 1 < row.len() && row[x + 1].content == Content::Continuation {
+            2
+        } else {
+            1
+        };
+
+        self.apply_style(Coordinate::new(x, y), width, style);
     }
 
     /// Erase all characters from the screen.
@@ -125,128 +319,160 @@ impl<W: Write> Screen<W> {
         self.buf.fill(Cell::default());
     }
 
+    /// Erase all characters within a rectangle of the screen.
+    pub fn erase_rect(&mut self, rect: &Rect<usize>) {
+        self.buf
+            .slice_mut(s![rect.min_y()..rect.max_y(), rect.min_x()..rect.max_x()])
+            .fill(Cell::default());
+    }
+
     pub fn erase_line(&mut self, line: usize) {
         self.buf.row_mut(line).fill(Cell::default());
     }
 
+    /// Returns the position of the terminal cursor at the next refresh.
+    #[cfg(test)]
+    pub fn cursor(&self) -> Option<Coordinate> {
+        self.cursor
+    }
+
+    /// Returns the attributes of a cell in the internal buffer.
+    #[cfg(test)]
+    pub fn attr(&self, Coordinate { x, y, .. }: Coordinate) -> Attr {
+        self.buf[(y, x)].attr
+    }
+
+    /// Returns the text of a line in the internal buffer, without trailing whitespace.
+    #[cfg(test)]
+    pub fn line(&self, y: usize) -> String {
+        let line = self
+            .buf
+            .row(y)
+            .iter()
+            .map(|cell| match &cell.content {
+                Content::Empty => String::from(" "),
+                Content::Char(c) => c.to_string(),
+                Content::Cluster(cluster) => cluster.to_string(),
+                Content::Continuation => String::new(),
+            })
+            .collect::<String>();
+        String::from(line.trim_end())
+    }
+
     /// Push the contents of the internal buffer to the screen.
+    ///
+    /// Only the cells that changed since the last refresh are written. If whole lines moved, such
+    /// as when the view is scrolled, the terminal is asked to scroll them instead.
+    ///
+    /// The changes are written all at once, and if the terminal supports synchronized output, it
+    /// displays them as a single frame.
     pub fn refresh(&mut self) -> io::Result<()> {
         debug!("refreshing screen contents");
 
-        let mut fg = None;
-        let mut bg = None;
-        let mut is_bold = false;
-        let mut is_italic = false;
-        let mut is_underline = false;
-        let mut is_reverse = false;
-        let mut needs_goto = false;
-
-        for (row_idx, row) in self.buf.genrows().into_iter().enumerate() {
-            if row == self.cur_buf.subview(Axis(0), row_idx) {
-                continue;
+        let Screen {
+            buf,
+            cur_buf,
+            color_depth,
+            capabilities,
+            cursor,
+            cursor_shape,
+            cur_cursor,
+            cur_cursor_shape,
+            out,
+            ..
+        } = self;
+        let mut frame = vec![];
+        let mut painter = Painter::new(&mut frame, capabilities, *color_depth);
+
+        if let Some(scroll) = Scroll::find(cur_buf, buf) {
+            if painter.scroll(&scroll, buf.len_of(Axis(0)))? {
+                scroll.apply(cur_buf);
             }
+        }
 
-            write!(
-                self.out,
-                "{}{}",
-                cursor::Goto(1, row_idx as u16 + 1),
-                clear::CurrentLine
-            )?;
-
-            for (cell_idx, cell) in row.into_iter().enumerate() {
-                if cell.fg != fg {
-                    fg = cell.fg;
-                    if let Some(fg) = cell.fg {
-                        write!(self.out, "{}", Fg(fg.as_escapes()))?;
-                    }
-                }
-
-                if cell.bg != bg {
-                    bg = cell.bg;
-                    if let Some(bg) = cell.bg {
-                        write!(self.out, "{}", Bg(bg.as_escapes()))?;
-                    }
-                }
-
-                if cell.attr.contains(Attr::BOLD) && !is_bold {
-                    write!(self.out, "{}", style::Bold)?
-                } else if is_bold && !cell.attr.contains(Attr::BOLD) {
-                    // While disabling bold is technically the right escape sequence to use here
-                    // (SGR 21), it's not supported by iTerm2 or xterm. So, we emit SGR 22, which
-                    // disables bold and faint. We don't use faint anywhere else, so it's OK.
-                    //
-                    // See https://gitlab.com/gnachman/iterm2/issues/3208
-                    write!(self.out, "{}", style::NoFaint)?;
-                }
-
-                if cell.attr.contains(Attr::ITALIC) && !is_italic {
-                    write!(self.out, "{}", style::Italic)?;
-                } else if is_italic && !cell.attr.contains(Attr::ITALIC) {
-                    write!(self.out, "{}", style::NoItalic)?;
-                }
-
-                if cell.attr.contains(Attr::UNDERLINE) && !is_underline {
-                    write!(self.out, "{}", style::Underline)?;
-                } else if is_underline && !cell.attr.contains(Attr::UNDERLINE) {
-                    write!(self.out, "{}", style::NoUnderline)?;
-                }
-
-                if cell.attr.contains(Attr::REVERSE) && !is_reverse {
-                    write!(self.out, "{}", style::Invert)?;
-                } else if !cell.attr.contains(Attr::REVERSE) && is_reverse {
-                    write!(self.out, "{}", style::NoInvert)?;
-                }
-
-                let c = match cell.c {
-                    Some(c) => Some(c),
-                    // Special case: reverse video needs a non-empty cell.
-                    None if cell.attr.contains(Attr::REVERSE) => Some(' '),
-                    None => None,
-                };
-
-                if let Some(c) = c {
-                    if needs_goto {
-                        write!(
-                            self.out,
-                            "{}",
-                            cursor::Goto(cell_idx as u16 + 1, row_idx as u16 + 1)
-                        )?;
-                        needs_goto = false;
-                    }
-                    write!(self.out, "{}", c)?;
-                } else {
-                    needs_goto = true;
-                }
-
-                is_bold = cell.attr.contains(Attr::BOLD);
-                is_italic = cell.attr.contains(Attr::ITALIC);
-                is_underline = cell.attr.contains(Attr::UNDERLINE);
-                is_reverse = cell.attr.contains(Attr::REVERSE);
+        for (y, row) in buf.genrows().into_iter().enumerate() {
+            let old = cur_buf.row(y);
+            if row != old {
+                painter.row(y, old, row)?;
             }
         }
 
-        self.out.flush()?;
-        self.cur_buf = self.buf.clone();
+        // Leave the terminal in a known state for the next refresh.
+        painter.reset()?;
+
+        // Drawing moves the terminal cursor, so it is hidden until the frame is complete, unless
+        // the terminal doesn't display the frame until then anyway.
+        let moved = !frame.is_empty();
+        if moved && cur_cursor.is_some() && capabilities.synchronized_output().is_none() {
+            frame.splice(..0, capabilities.hide_cursor().iter().cloned());
+            *cur_cursor = None;
+        }
+
+        if let Some(position) = *cursor {
+            if *cur_cursor_shape != Some(*cursor_shape) {
+                frame.extend(capabilities.cursor_shape(*cursor_shape).unwrap_or_default());
+                *cur_cursor_shape = Some(*cursor_shape);
+            }
+
+            if moved || *cur_cursor != Some(position) {
+                frame.extend(capabilities.goto(position.x, position.y));
+            }
+
+            if cur_cursor.is_none() {
+                frame.extend(capabilities.show_cursor());
+            }
+        } else if cur_cursor.is_some() {
+            frame.extend(capabilities.hide_cursor());
+        }
+        *cur_cursor = *cursor;
+
+        if !frame.is_empty() {
+            match capabilities.synchronized_output() {
+                Some((begin, end)) => out.write_all(&[begin, frame, end].concat())?,
+                None => out.write_all(&frame)?,
+            }
+            out.flush()?;
+        }
+        *cur_buf = buf.clone();
 
         Ok(())
     }
 }
 
+#[cfg(test)]
+impl Screen<Vec<u8>> {
+    /// Returns the bytes that have been written to the terminal since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::replace(&mut self.out, Vec::new())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
+    use std::mem;
 
-    use euclid::Size2D;
+    use euclid::{Point2D, Rect, Size2D};
     use termion::{
         clear,
-        color::{Fg, Rgb},
-        cursor::Goto,
-        style::{Bold, Invert, Italic, NoFaint, NoInvert, NoItalic, NoUnderline, Underline},
+        color::{AnsiValue, Bg, Fg, Reset, Rgb},
+        cursor::{self, Goto},
+        style::{self, Bold, Invert, Italic, Underline},
     };
 
-    use super::{Color, Coordinate, Screen};
+    use super::capabilities::Capabilities;
+    use super::terminfo::{compile, TermInfo};
+    use super::{Attr, Color, ColorDepth, Content, Coordinate, CursorShape, Screen};
     use crate::editor::styles::Style;
 
+    /// The style of a cursor when there is no theme.
+    fn reverse() -> Style {
+        Style {
+            reverse: true,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn write_str() {
         let buf = Cursor::new(vec![]);
@@ -254,17 +480,67 @@ mod tests {
 
         screen.write_str(Coordinate::new(0, 0), "Hello, world!");
 
+        assert_eq!(screen.line(0), "Hello, world!");
+    }
+
+    #[test]
+    fn write_str_clipped() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(5, 1), buf).unwrap();
+
+        assert_eq!(screen.write_str(Coordinate::new(0, 0), "Hello, world!"), 5);
+        assert_eq!(screen.line(0), "Hello");
+
+        // A double-width character that doesn't fit is not displayed.
+        screen.erase();
+        assert_eq!(screen.write_str(Coordinate::new(0, 0), "日本語"), 4);
+        assert_eq!(screen.line(0), "日本");
+    }
+
+    #[test]
+    fn write_wide() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 1), buf).unwrap();
+
+        assert_eq!(screen.write_str(Coordinate::new(0, 0), "a中b😀"), 6);
+        assert_eq!(screen.buf[(0, 1)].content, Content::Char('中'));
+        assert_eq!(screen.buf[(0, 2)].content, Content::Continuation);
+        assert_eq!(screen.buf[(0, 5)].content, Content::Continuation);
+        assert_eq!(screen.line(0), "a中b😀");
+
+        screen.refresh().unwrap();
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(sequences, format!("{}a中b😀", Goto(1, 1)));
+    }
+
+    #[test]
+    fn overwrite_wide() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 1), buf).unwrap();
+
+        screen.write_str(Coordinate::new(0, 0), "中文字");
+        screen.write_str(Coordinate::new(1, 0), "a");
+        screen.write_str(Coordinate::new(4, 0), "b");
+        assert_eq!(screen.line(0), " a文b");
+    }
+
+    #[test]
+    fn write_clusters() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 1), buf).unwrap();
+
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(screen.write_str(Coordinate::new(0, 0), "e\u{301}"), 1);
+        assert_eq!(screen.write_str(Coordinate::new(1, 0), family), 2);
         assert_eq!(
-            screen
-                .buf
-                .row(0)
-                .into_slice()
-                .unwrap()
-                .iter()
-                .flat_map(|cell| cell.c)
-                .collect::<String>(),
-            "Hello, world!"
+            screen.buf[(0, 0)].content,
+            Content::Cluster("e\u{301}".into())
         );
+        assert_eq!(screen.buf[(0, 1)].content, Content::Cluster(family.into()));
+        assert_eq!(screen.buf[(0, 2)].content, Content::Continuation);
+
+        screen.draw_cursor(Coordinate::new(2, 0), &reverse());
+        assert!(screen.buf[(0, 1)].attr.contains(Attr::REVERSE));
     }
 
     #[test]
@@ -279,12 +555,7 @@ mod tests {
         let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
         assert_eq!(
             sequences,
-            format!(
-                "{}{}hello{}goodbye",
-                Goto(1, 1),
-                clear::CurrentLine,
-                Goto(11, 1),
-            )
+            format!("{}hello{}goodbye", Goto(1, 1), Goto(11, 1),)
         );
     }
 
@@ -293,20 +564,13 @@ mod tests {
         let buf = Cursor::new(vec![]);
         let mut screen = Screen::new_from_write(Size2D::new(20, 1), buf).unwrap();
 
-        screen.draw_cursor(Coordinate::new(10, 0));
+        screen.draw_cursor(Coordinate::new(10, 0), &reverse());
         screen.refresh().unwrap();
 
         let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
         assert_eq!(
             sequences,
-            format!(
-                "{}{}{}{} {}",
-                Goto(1, 1),
-                clear::CurrentLine,
-                Invert,
-                Goto(11, 1),
-                NoInvert
-            )
+            format!("{}{} {}", Goto(11, 1), Invert, style::Reset,)
         )
     }
 
@@ -329,13 +593,7 @@ mod tests {
         let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
         assert_eq!(
             sequences,
-            format!(
-                "{}{}{}Hello{}, world!",
-                Goto(1, 1),
-                clear::CurrentLine,
-                Bold,
-                NoFaint,
-            )
+            format!("{}{}Hello{}, world!", Goto(1, 1), Bold, style::Reset,)
         );
     }
 
@@ -367,13 +625,12 @@ mod tests {
         assert_eq!(
             sequences,
             format!(
-                "{}{}{}bold{}{}italic{}",
+                "{}{}bold{}{}italic{}",
                 Goto(1, 1),
-                clear::CurrentLine,
                 Bold,
-                NoFaint,
+                style::Reset,
                 Italic,
-                NoItalic,
+                style::Reset,
             )
         );
     }
@@ -398,13 +655,12 @@ mod tests {
         assert_eq!(
             sequences,
             format!(
-                "{}{}{}int{} {}main{}() {{}}",
+                "{}{}int{} {}main{}() {{}}",
                 Goto(1, 1),
-                clear::CurrentLine,
                 Bold,
-                NoFaint,
+                style::Reset,
                 Bold,
-                NoFaint,
+                style::Reset,
             ),
         );
     }
@@ -471,12 +727,12 @@ mod tests {
         assert_eq!(
             sequences,
             format!(
-                "{}{}{}red{}green{}blue",
+                "{}{}red{}green{}blue{}",
                 Goto(1, 1),
-                clear::CurrentLine,
                 Fg(Rgb(255, 0, 0)),
                 Fg(Rgb(0, 255, 0)),
                 Fg(Rgb(0, 0, 255)),
+                style::Reset,
             ),
         );
     }
@@ -495,7 +751,7 @@ mod tests {
 
         screen.write_str(Coordinate::new(0, 0), "foo");
         screen.apply_style(Coordinate::new(0, 0), 3, &style);
-        screen.draw_cursor(Coordinate::new(1, 0));
+        screen.draw_cursor(Coordinate::new(1, 0), &reverse());
         screen.refresh().unwrap();
 
         println!("{:#?}", screen.buf);
@@ -504,17 +760,19 @@ mod tests {
         assert_eq!(
             sequences,
             format!(
-                "{}{}{}{}{}f{}o{}o{}{}{}",
+                "{}{}{}{}f{}o{}{}{}{}o{}",
                 Goto(1, 1),
-                clear::CurrentLine,
                 Bold,
                 Italic,
                 Underline,
                 Invert,
-                NoInvert,
-                NoFaint,
-                NoItalic,
-                NoUnderline,
+                // Reverse video can't be turned off on its own, so the other attributes are
+                // turned back on.
+                style::Reset,
+                Bold,
+                Italic,
+                Underline,
+                style::Reset,
             )
         );
     }
@@ -530,10 +788,334 @@ mod tests {
         screen.write_str(Coordinate::new(0, 0), "foo");
         screen.refresh().unwrap();
 
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(sequences, format!("{}foo", Goto(1, 1)),);
+    }
+
+    #[test]
+    fn background() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(4, 2), buf).unwrap();
+
+        let highlight = Style {
+            bg: Some(Color { r: 0, g: 0, b: 255 }),
+            ..Default::default()
+        };
+        let red = Style {
+            fg: Some(Color { r: 255, g: 0, b: 0 }),
+            ..Default::default()
+        };
+
+        // The background of empty cells is drawn, and isn't replaced by a style without one.
+        screen.write_str(Coordinate::new(0, 0), "a");
+        screen.apply_style(Coordinate::new(0, 0), 3, &highlight);
+        screen.apply_style(Coordinate::new(0, 0), 1, &red);
+        screen.write_str(Coordinate::new(0, 1), "b");
+        screen.refresh().unwrap();
+
         let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
         assert_eq!(
             sequences,
-            format!("{}{}foo", Goto(1, 1), clear::CurrentLine),
+            format!(
+                "{}{}{}a{}  {}{}b",
+                Goto(1, 1),
+                Fg(Rgb(255, 0, 0)),
+                Bg(Rgb(0, 0, 255)),
+                Fg(Reset),
+                Goto(1, 2),
+                Bg(Reset),
+            )
         );
     }
+
+    #[test]
+    fn color_depth() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(3, 1), buf).unwrap();
+        screen.color_depth = ColorDepth::Ansi256;
+
+        screen.write_str(Coordinate::new(0, 0), "red");
+        screen.apply_style(
+            Coordinate::new(0, 0),
+            3,
+            &Style {
+                fg: Some(Color { r: 255, g: 0, b: 0 }),
+                ..Default::default()
+            },
+        );
+        screen.refresh().unwrap();
+
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!("{}{}red{}", Goto(1, 1), Fg(AnsiValue(196)), style::Reset,)
+        );
+    }
+
+    #[test]
+    fn changed_cells() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(15, 2), buf).unwrap();
+
+        screen.write_str(Coordinate::new(0, 0), "hello world");
+        screen.write_str(Coordinate::new(0, 1), "hello world");
+        screen.refresh().unwrap();
+        screen.out = Cursor::new(vec![]);
+
+        // Only the changed cells are written, and the rest of a shortened line is cleared.
+        screen.write_str(Coordinate::new(6, 0), "W");
+        screen.erase_rect(&Rect::new(Point2D::new(5, 1), Size2D::new(10, 1)));
+        screen.refresh().unwrap();
+
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!("{}W{}{}", Goto(7, 1), Goto(6, 2), clear::UntilNewline)
+        );
+    }
+
+    #[test]
+    fn scroll() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(5, 4), buf).unwrap();
+
+        for (y, line) in ["a", "b", "c", "d"].iter().enumerate() {
+            screen.write_str(Coordinate::new(0, y), line);
+        }
+        screen.refresh().unwrap();
+        screen.out = Cursor::new(vec![]);
+
+        screen.erase();
+        for (y, line) in ["b", "c", "d", "e"].iter().enumerate() {
+            screen.write_str(Coordinate::new(0, y), line);
+        }
+        screen.refresh().unwrap();
+
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!("\x1b[1;4r{}\n\x1b[1;4r{}e", Goto(1, 4), Goto(1, 4))
+        );
+    }
+
+    #[test]
+    fn synchronized_output() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(4, 1), buf).unwrap();
+        let entry = compile(
+            "xterm-sync",
+            &[],
+            &[],
+            &[("Sync", "\x1b[?2026%?%p1%{1}%-%tl%eh%;")],
+        );
+        screen.capabilities = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+
+        screen.write_str(Coordinate::new(0, 0), "foo");
+        screen.refresh().unwrap();
+
+        // Nothing is written if nothing changed.
+        screen.refresh().unwrap();
+
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!("\x1b[?2026h{}foo\x1b[?2026l", Goto(1, 1))
+        );
+    }
+
+    #[test]
+    fn extended_attributes() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 1), buf).unwrap();
+
+        screen.write_str(Coordinate::new(0, 0), "abcdefg");
+        screen.apply_style(
+            Coordinate::new(0, 0),
+            3,
+            &Style {
+                italic: true,
+                strikethrough: true,
+                ..Default::default()
+            },
+        );
+        screen.apply_style(
+            Coordinate::new(1, 0),
+            3,
+            &Style {
+                undercurl: true,
+                underline_color: Some(Color::new(255, 0, 0)),
+                ..Default::default()
+            },
+        );
+        screen.apply_style(
+            Coordinate::new(5, 0),
+            1,
+            &Style {
+                dim: true,
+                blink: true,
+                ..Default::default()
+            },
+        );
+        screen.refresh().unwrap();
+
+        // Italics and strikethrough can be turned off on their own, but the underline color can't.
+        // Without support for styled underlines, the terminal uses a single underline.
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!(
+                "{}{}\x1b[9ma{}bc\x1b[23m\x1b[29md{}e\x1b[2m\x1b[5mf{}g",
+                Goto(1, 1),
+                Italic,
+                Underline,
+                style::Reset,
+                style::Reset,
+            )
+        );
+    }
+
+    #[test]
+    fn styled_underlines() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(10, 1), buf).unwrap();
+        let entry = compile(
+            "xterm-kitty",
+            &[],
+            &[],
+            &[
+                ("smul", "\x1b[4m"),
+                ("rmul", "\x1b[24m"),
+                ("Smulx", "\x1b[4:%p1%dm"),
+                (
+                    "Setulc",
+                    "\x1b[58:2:%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%dm",
+                ),
+            ],
+        );
+        screen.capabilities = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+
+        screen.write_str(Coordinate::new(0, 0), "abc");
+        screen.apply_style(
+            Coordinate::new(0, 0),
+            2,
+            &Style {
+                undercurl: true,
+                underline_color: Some(Color::new(255, 0, 0)),
+                ..Default::default()
+            },
+        );
+        screen.apply_style(
+            Coordinate::new(1, 0),
+            2,
+            &Style {
+                underdouble: true,
+                ..Default::default()
+            },
+        );
+        screen.refresh().unwrap();
+
+        // The double underline replaces the curly underline, without turning off the underline.
+        let sequences = String::from_utf8(screen.out.into_inner()).unwrap();
+        assert_eq!(
+            sequences,
+            format!(
+                "{}\x1b[4:3m\x1b[58:2:255:0:0ma\x1b[4:2mb{}\x1b[4:2mc{}",
+                Goto(1, 1),
+                style::Reset,
+                style::Reset,
+            )
+        );
+    }
+
+    #[test]
+    fn terminal_cursor() {
+        let buf = Cursor::new(vec![]);
+        let mut screen = Screen::new_from_write(Size2D::new(4, 2), buf).unwrap();
+        let mut sequences = Vec::new();
+
+        screen.write_str(Coordinate::new(0, 0), "foo");
+        screen.set_cursor(Some(Coordinate::new(1, 0)));
+        screen.refresh().unwrap();
+        sequences.push(mem::replace(&mut screen.out, Cursor::new(vec![])));
+
+        // Nothing is written if neither the cells nor the cursor changed.
+        screen.refresh().unwrap();
+        sequences.push(mem::replace(&mut screen.out, Cursor::new(vec![])));
+
+        screen.set_cursor(Some(Coordinate::new(2, 0)));
+        screen.set_cursor_shape(CursorShape::Bar);
+        screen.refresh().unwrap();
+        sequences.push(mem::replace(&mut screen.out, Cursor::new(vec![])));
+
+        // The cursor is hidden while drawing.
+        screen.write_str(Coordinate::new(0, 1), "x");
+        screen.refresh().unwrap();
+        sequences.push(mem::replace(&mut screen.out, Cursor::new(vec![])));
+
+        screen.set_cursor(None);
+        screen.refresh().unwrap();
+        sequences.push(mem::replace(&mut screen.out, Cursor::new(vec![])));
+
+        let sequences = sequences
+            .into_iter()
+            .map(|out| String::from_utf8(out.into_inner()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sequences,
+            vec![
+                format!("{}foo\x1b[1 q{}{}", Goto(1, 1), Goto(2, 1), cursor::Show),
+                String::new(),
+                format!("\x1b[5 q{}", Goto(3, 1)),
+                format!(
+                    "{}{}x{}{}",
+                    cursor::Hide,
+                    Goto(1, 2),
+                    Goto(3, 1),
+                    cursor::Show
+                ),
+                cursor::Hide.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_bytes() {
+        let size = Size2D::new(80, 24);
+        let lines = (0..25)
+            .map(|i| {
+                format!(
+                    "{:>4} the quick brown fox jumps over the lazy dog {}",
+                    i,
+                    i * 31
+                )
+            })
+            .collect::<Vec<_>>();
+        let keyword = Style {
+            fg: Some(Color { r: 255, g: 0, b: 0 }),
+            bold: true,
+            ..Default::default()
+        };
+
+        let mut screen = Screen::new_from_write(size, Cursor::new(vec![])).unwrap();
+        let draw = |screen: &mut Screen<_>, first: usize| {
+            screen.erase();
+            for y in 0..size.height {
+                screen.write_str(Coordinate::new(0, y), &lines[first + y]);
+                screen.apply_style(Coordinate::new(9, y), 5, &keyword);
+            }
+            screen.refresh().unwrap();
+            mem::replace(&mut screen.out, Cursor::new(vec![]))
+                .into_inner()
+                .len()
+        };
+
+        let full = draw(&mut screen, 0);
+        let scrolled = draw(&mut screen, 1);
+        let unchanged = draw(&mut screen, 1);
+
+        // Scrolling by a line only redraws the line that was scrolled into view.
+        assert!(full > 1500, "{}", full);
+        assert!(scrolled < 150, "{}", scrolled);
+        assert_eq!(unchanged, 0);
+    }
 }
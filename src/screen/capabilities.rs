@@ -0,0 +1,486 @@
+//! The escape sequences used to control the terminal.
+
+use log::*;
+
+use super::terminfo::{self, TermInfo};
+use super::{Attr, Color, CursorShape};
+
+/// Begins (with parameter 1) or ends (with parameter 2) a synchronized update with mode 2026.
+///
+/// Terminals that don't support synchronized output ignore the mode, so it's used for terminals
+/// that are compatible with xterm but have no `Sync` capability, even though xterm itself doesn't
+/// support it.
+const SYNCHRONIZED_OUTPUT_MODE: &[u8] = b"\x1b[?2026%?%p1%{1}%-%tl%eh%;";
+
+/// The escape sequences used by the screen, read from the terminfo entry of the terminal.
+///
+/// If the terminal has no terminfo entry, the sequences understood by xterm (and nearly every
+/// other terminal emulator) are used instead. If the entry is missing a capability that the screen
+/// can do without, such as italics or the alternate screen, the feature is not used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Moves the cursor to a row and column (`cup`).
+    cursor_address: Vec<u8>,
+
+    /// Clears the screen (`clear`).
+    clear_screen: Vec<u8>,
+
+    /// Clears from the cursor to the end of the line (`el`).
+    clear_eol: Vec<u8>,
+
+    /// Switches to and from the alternate screen (`smcup` and `rmcup`).
+    enter_alternate_screen: Vec<u8>,
+    exit_alternate_screen: Vec<u8>,
+
+    /// Hides and shows the cursor (`civis` and `cnorm`).
+    hide_cursor: Vec<u8>,
+    show_cursor: Vec<u8>,
+
+    /// Turns off all attributes, including colors (`sgr0`).
+    exit_attributes: Vec<u8>,
+
+    bold: Option<Vec<u8>>,
+    dim: Option<Vec<u8>>,
+    italic: Option<Vec<u8>>,
+    underline: Option<Vec<u8>>,
+    blink: Option<Vec<u8>>,
+    reverse: Option<Vec<u8>>,
+    strikethrough: Option<Vec<u8>>,
+
+    /// Turns off a single attribute (`ritm`, `rmul` and `rmxx`). Other attributes can only be
+    /// turned off with `sgr0`.
+    exit_italic: Option<Vec<u8>>,
+    exit_underline: Option<Vec<u8>>,
+    exit_strikethrough: Option<Vec<u8>>,
+
+    /// Sets the style of underlines and the color of underlines (`Smulx` and `Setulc`). These are
+    /// extended capabilities.
+    styled_underline: Option<Vec<u8>>,
+    underline_color: Option<Vec<u8>>,
+
+    /// Sets and restores the cursor shape chosen by the user (`Ss` and `Se`). These are extended
+    /// capabilities that are usually DECSCUSR.
+    set_cursor_shape: Option<Vec<u8>>,
+    reset_cursor_shape: Option<Vec<u8>>,
+
+    /// Limits scrolling to a range of rows (`csr`).
+    change_scroll_region: Option<Vec<u8>>,
+
+    /// Scrolls the scroll region up or down by one row (`ind` and `ri`).
+    scroll_forward: Option<Vec<u8>>,
+    scroll_reverse: Option<Vec<u8>>,
+
+    /// Whether the terminal reports mouse events (`kmous`). Terminfo has no sequences for turning
+    /// on SGR mouse reporting, so xterm's are used.
+    mouse: bool,
+
+    /// Turns bracketed paste on and off (`BE` and `BD`). These are extended capabilities.
+    enable_bracketed_paste: Option<Vec<u8>>,
+    disable_bracketed_paste: Option<Vec<u8>>,
+
+    /// Begins or ends a synchronized update, during which the terminal doesn't display any output
+    /// (`Sync`). This is an extended capability that is usually mode 2026.
+    synchronized_output: Option<Vec<u8>>,
+}
+
+impl Capabilities {
+    /// The capabilities of xterm.
+    pub fn xterm() -> Self {
+        Capabilities {
+            cursor_address: b"\x1b[%i%p1%d;%p2%dH".to_vec(),
+            clear_screen: b"\x1b[H\x1b[2J".to_vec(),
+            clear_eol: b"\x1b[K".to_vec(),
+            enter_alternate_screen: b"\x1b[?1049h".to_vec(),
+            exit_alternate_screen: b"\x1b[?1049l".to_vec(),
+            hide_cursor: b"\x1b[?25l".to_vec(),
+            show_cursor: b"\x1b[?25h".to_vec(),
+            exit_attributes: b"\x1b[m".to_vec(),
+            bold: Some(b"\x1b[1m".to_vec()),
+            dim: Some(b"\x1b[2m".to_vec()),
+            italic: Some(b"\x1b[3m".to_vec()),
+            underline: Some(b"\x1b[4m".to_vec()),
+            blink: Some(b"\x1b[5m".to_vec()),
+            reverse: Some(b"\x1b[7m".to_vec()),
+            strikethrough: Some(b"\x1b[9m".to_vec()),
+            exit_italic: Some(b"\x1b[23m".to_vec()),
+            exit_underline: Some(b"\x1b[24m".to_vec()),
+            exit_strikethrough: Some(b"\x1b[29m".to_vec()),
+            styled_underline: None,
+            underline_color: None,
+            set_cursor_shape: Some(b"\x1b[%p1%d q".to_vec()),
+            reset_cursor_shape: Some(b"\x1b[0 q".to_vec()),
+            change_scroll_region: Some(b"\x1b[%i%p1%d;%p2%dr".to_vec()),
+            scroll_forward: Some(b"\n".to_vec()),
+            scroll_reverse: Some(b"\x1bM".to_vec()),
+            mouse: true,
+            enable_bracketed_paste: Some(b"\x1b[?2004h".to_vec()),
+            disable_bracketed_paste: Some(b"\x1b[?2004l".to_vec()),
+            synchronized_output: None,
+        }
+    }
+
+    /// Reads the capabilities of the terminal named by `TERM`.
+    pub fn from_env() -> Self {
+        match TermInfo::from_env() {
+            Ok(info) => {
+                info!("using terminfo entry {}", info.names().join("|"));
+                Self::from_terminfo(&info)
+            }
+            Err(e) => {
+                warn!("{}, assuming xterm", e);
+                Capabilities {
+                    synchronized_output: Some(SYNCHRONIZED_OUTPUT_MODE.to_vec()),
+                    ..Self::xterm()
+                }
+            }
+        }
+    }
+
+    pub fn from_terminfo(info: &TermInfo) -> Self {
+        let xterm = Self::xterm();
+        let required = |name, default: Vec<u8>| {
+            info.string(name).map(<[u8]>::to_vec).unwrap_or_else(|| {
+                warn!("terminal is missing {}, assuming xterm", name);
+                default
+            })
+        };
+        let optional = |name| info.string(name).map(<[u8]>::to_vec);
+
+        // Few terminfo entries describe bracketed paste or synchronized output, but terminals that
+        // report the mouse like xterm generally support its bracketed paste mode too, and ignore
+        // mode 2026 if they don't support synchronized output.
+        let mouse = info.string("kmous").is_some();
+        let (enable_bracketed_paste, disable_bracketed_paste) =
+            match (optional("BE"), optional("BD")) {
+                (Some(enable), Some(disable)) => (Some(enable), Some(disable)),
+                _ if mouse => (xterm.enable_bracketed_paste, xterm.disable_bracketed_paste),
+                _ => (None, None),
+            };
+        let synchronized_output = match optional("Sync") {
+            Some(sync) => Some(sync),
+            None if mouse => Some(SYNCHRONIZED_OUTPUT_MODE.to_vec()),
+            None => None,
+        };
+
+        Capabilities {
+            cursor_address: required("cup", xterm.cursor_address),
+            clear_screen: required("clear", xterm.clear_screen),
+            clear_eol: required("el", xterm.clear_eol),
+            exit_attributes: required("sgr0", xterm.exit_attributes),
+            enter_alternate_screen: optional("smcup").unwrap_or_default(),
+            exit_alternate_screen: optional("rmcup").unwrap_or_default(),
+            hide_cursor: optional("civis").unwrap_or_default(),
+            show_cursor: optional("cnorm").unwrap_or_default(),
+            bold: optional("bold"),
+            dim: optional("dim"),
+            italic: optional("sitm"),
+            underline: optional("smul"),
+            blink: optional("blink"),
+            reverse: optional("rev"),
+            strikethrough: optional("smxx"),
+            exit_italic: optional("ritm"),
+            exit_underline: optional("rmul"),
+            exit_strikethrough: optional("rmxx"),
+            styled_underline: optional("Smulx"),
+            underline_color: optional("Setulc"),
+            set_cursor_shape: optional("Ss"),
+            reset_cursor_shape: optional("Se"),
+            change_scroll_region: optional("csr"),
+            scroll_forward: optional("ind"),
+            scroll_reverse: optional("ri"),
+            mouse,
+            enable_bracketed_paste,
+            disable_bracketed_paste,
+            synchronized_output,
+        }
+    }
+
+    /// Moves the cursor to a 0-based coordinate.
+    pub fn goto(&self, x: usize, y: usize) -> Vec<u8> {
+        terminfo::expand(&self.cursor_address, &[y as i32, x as i32])
+    }
+
+    pub fn clear_eol(&self) -> &[u8] {
+        &self.clear_eol
+    }
+
+    pub fn exit_attributes(&self) -> &[u8] {
+        &self.exit_attributes
+    }
+
+    pub fn hide_cursor(&self) -> &[u8] {
+        &self.hide_cursor
+    }
+
+    pub fn show_cursor(&self) -> &[u8] {
+        &self.show_cursor
+    }
+
+    /// Returns the sequence that changes the shape of the cursor, if the terminal supports it.
+    pub fn cursor_shape(&self, shape: CursorShape) -> Option<Vec<u8>> {
+        // The blinking DECSCUSR shapes.
+        let style = match shape {
+            CursorShape::Block => 1,
+            CursorShape::Underline => 3,
+            CursorShape::Bar => 5,
+        };
+
+        self.set_cursor_shape
+            .as_ref()
+            .map(|sequence| terminfo::expand(sequence, &[style]))
+    }
+
+    /// Returns the sequence that scrolls the rows from `top` to `bottom` (inclusive) by `shift`
+    /// rows, or `None` if the terminal doesn't support scroll regions.
+    ///
+    /// A positive shift moves the contents up, like scrolling down in a buffer. The rows that are
+    /// scrolled into view are cleared. The position of the cursor is unknown afterwards.
+    pub fn scroll(
+        &self,
+        top: usize,
+        bottom: usize,
+        shift: isize,
+        height: usize,
+    ) -> Option<Vec<u8>> {
+        let region = self.change_scroll_region.as_ref()?;
+        let (row, step) = if shift > 0 {
+            (bottom, self.scroll_forward.as_ref()?)
+        } else {
+            (top, self.scroll_reverse.as_ref()?)
+        };
+
+        let mut sequence = terminfo::expand(region, &[top as i32, bottom as i32]);
+        sequence.extend(self.goto(0, row));
+        for _ in 0..shift.abs() {
+            sequence.extend(step);
+        }
+        sequence.extend(terminfo::expand(region, &[0, height as i32 - 1]));
+        Some(sequence)
+    }
+
+    /// Returns the sequence that turns mouse reporting on or off, if the terminal supports it.
+    ///
+    /// Presses, releases and movement while a button is held are reported in the SGR encoding,
+    /// which isn't limited to 223 rows and columns.
+    pub fn mouse(&self, enabled: bool) -> Option<&'static [u8]> {
+        match (self.mouse, enabled) {
+            (false, _) => None,
+            (true, true) => Some(b"\x1b[?1002h\x1b[?1006h"),
+            (true, false) => Some(b"\x1b[?1006l\x1b[?1002l"),
+        }
+    }
+
+    /// Returns the sequences that begin and end a synchronized update, if the terminal supports
+    /// them.
+    pub fn synchronized_output(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.synchronized_output
+            .as_ref()
+            .map(|sync| (terminfo::expand(sync, &[1]), terminfo::expand(sync, &[2])))
+    }
+
+    /// Returns the sequence that turns on a single attribute, if the terminal supports it.
+    ///
+    /// Terminals that don't support styled underlines use a single underline instead.
+    pub fn attribute(&self, attr: Attr) -> Option<Vec<u8>> {
+        let underline_style = match attr {
+            Attr::DOUBLE_UNDERLINE => 2,
+            Attr::CURLY_UNDERLINE => 3,
+            Attr::DOTTED_UNDERLINE => 4,
+            _ => 0,
+        };
+        if underline_style != 0 {
+            return match &self.styled_underline {
+                Some(sequence) => Some(terminfo::expand(sequence, &[underline_style])),
+                None => self.attribute(Attr::UNDERLINE),
+            };
+        }
+
+        let sequence = match attr {
+            Attr::BOLD => &self.bold,
+            Attr::DIM => &self.dim,
+            Attr::ITALIC => &self.italic,
+            Attr::UNDERLINE => &self.underline,
+            Attr::BLINK => &self.blink,
+            Attr::REVERSE => &self.reverse,
+            Attr::STRIKETHROUGH => &self.strikethrough,
+            _ => return None,
+        };
+
+        sequence.clone()
+    }
+
+    /// Returns the sequence that turns off a single attribute without affecting the others, if
+    /// there is one.
+    pub fn exit_attribute(&self, attr: Attr) -> Option<&[u8]> {
+        let sequence = match attr {
+            Attr::ITALIC => &self.exit_italic,
+            Attr::UNDERLINE
+            | Attr::DOUBLE_UNDERLINE
+            | Attr::CURLY_UNDERLINE
+            | Attr::DOTTED_UNDERLINE => &self.exit_underline,
+            Attr::STRIKETHROUGH => &self.exit_strikethrough,
+            _ => return None,
+        };
+
+        sequence.as_ref().map(Vec::as_slice)
+    }
+
+    /// Returns the sequence that sets the color of underlines, if the terminal supports it.
+    pub fn underline_color(&self, color: Color) -> Option<Vec<u8>> {
+        let Color { r, g, b } = color;
+        let rgb = i32::from(r) << 16 | i32::from(g) << 8 | i32::from(b);
+        self.underline_color
+            .as_ref()
+            .map(|sequence| terminfo::expand(sequence, &[rgb]))
+    }
+
+    /// The sequence that prepares the terminal for drawing the screen.
+    pub fn setup(&self) -> Vec<u8> {
+        [
+            &self.enter_alternate_screen[..],
+            &self.hide_cursor,
+            &self.clear_screen,
+            self.enable_bracketed_paste
+                .as_ref()
+                .map_or(&[], Vec::as_slice),
+        ]
+        .concat()
+    }
+
+    /// The sequence that restores the terminal to the state it was in before `setup`.
+    pub fn restore(&self) -> Vec<u8> {
+        [
+            self.mouse(false).unwrap_or_default(),
+            self.disable_bracketed_paste
+                .as_ref()
+                .map_or(&[], Vec::as_slice),
+            &self.exit_attributes[..],
+            self.reset_cursor_shape.as_ref().map_or(&[], Vec::as_slice),
+            &self.show_cursor,
+            &self.exit_alternate_screen,
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::terminfo::{compile, TermInfo};
+    use super::super::{Attr, Color, CursorShape};
+    use super::Capabilities;
+
+    #[test]
+    fn xterm() {
+        let caps = Capabilities::xterm();
+        assert_eq!(caps.goto(0, 0), b"\x1b[1;1H");
+        assert_eq!(caps.goto(9, 4), b"\x1b[5;10H");
+        assert_eq!(caps.attribute(Attr::BOLD).unwrap(), b"\x1b[1m");
+        assert_eq!(caps.attribute(Attr::CURLY_UNDERLINE).unwrap(), b"\x1b[4m");
+        assert_eq!(caps.exit_attribute(Attr::BOLD), None);
+        assert_eq!(
+            caps.exit_attribute(Attr::STRIKETHROUGH).unwrap(),
+            b"\x1b[29m"
+        );
+        assert_eq!(caps.underline_color(Color::new(255, 0, 0)), None);
+        assert_eq!(caps.synchronized_output(), None);
+        assert_eq!(caps.cursor_shape(CursorShape::Bar).unwrap(), b"\x1b[5 q");
+        assert!(caps.setup().ends_with(b"\x1b[?2004h"));
+        assert!(caps
+            .restore()
+            .starts_with(b"\x1b[?1006l\x1b[?1002l\x1b[?2004l"));
+        assert_eq!(
+            caps.scroll(2, 5, 2, 10).unwrap(),
+            b"\x1b[3;6r\x1b[6;1H\n\n\x1b[1;10r"
+        );
+        assert_eq!(
+            caps.scroll(0, 9, -1, 10).unwrap(),
+            b"\x1b[1;10r\x1b[1;1H\x1bM\x1b[1;10r"
+        );
+    }
+
+    #[test]
+    fn synchronized_output_fallback() {
+        // Terminals that report the mouse like xterm are assumed to ignore mode 2026 if they
+        // don't support it.
+        let entry = compile(
+            "rxvt|rxvt terminal emulator",
+            &[],
+            &[],
+            &[("kmous", "\x1b[M")],
+        );
+        let caps = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+        assert_eq!(
+            caps.synchronized_output(),
+            Some((b"\x1b[?2026h".to_vec(), b"\x1b[?2026l".to_vec()))
+        );
+
+        let entry = compile("vt100|dec vt100", &[], &[], &[]);
+        let caps = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+        assert_eq!(caps.synchronized_output(), None);
+    }
+
+    #[test]
+    fn styled_underlines() {
+        // The capabilities of kitty.
+        let entry = compile(
+            "xterm-kitty|KovIdTTY",
+            &[],
+            &[],
+            &[
+                ("smul", "\x1b[4m"),
+                ("Smulx", "\x1b[4:%p1%dm"),
+                (
+                    "Setulc",
+                    "\x1b[58:2:%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%dm",
+                ),
+            ],
+        );
+        let caps = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+
+        assert_eq!(caps.attribute(Attr::UNDERLINE).unwrap(), b"\x1b[4m");
+        assert_eq!(caps.attribute(Attr::CURLY_UNDERLINE).unwrap(), b"\x1b[4:3m");
+        assert_eq!(
+            caps.attribute(Attr::DOTTED_UNDERLINE).unwrap(),
+            b"\x1b[4:4m"
+        );
+        assert_eq!(
+            caps.underline_color(Color::new(255, 128, 0)).unwrap(),
+            b"\x1b[58:2:255:128:0m"
+        );
+    }
+
+    #[test]
+    fn from_terminfo() {
+        // The Linux console has no italics, alternate screen or cursor shapes.
+        let entry = compile(
+            "linux|linux console",
+            &[],
+            &[("colors", 8)],
+            &[
+                ("cup", "\x1b[%i%p1%d;%p2%dH"),
+                ("el", "\x1b[K"),
+                ("sgr0", "\x1b[m\x0f"),
+                ("bold", "\x1b[1m"),
+                ("smul", "\x1b[4m"),
+                ("rev", "\x1b[7m"),
+                ("civis", "\x1b[?25l\x1b[?1c"),
+                ("cnorm", "\x1b[?25h\x1b[?0c"),
+                ("Sync", "\x1b[?2026%?%p1%{1}%-%tl%eh%;"),
+            ],
+        );
+        let caps = Capabilities::from_terminfo(&TermInfo::parse(&entry).unwrap());
+
+        assert_eq!(caps.attribute(Attr::ITALIC), None);
+        assert_eq!(caps.cursor_shape(CursorShape::Block), None);
+        assert_eq!(caps.scroll(0, 9, 1, 10), None);
+        assert_eq!(caps.mouse(true), None);
+        assert_eq!(
+            caps.synchronized_output(),
+            Some((b"\x1b[?2026h".to_vec(), b"\x1b[?2026l".to_vec()))
+        );
+        assert_eq!(caps.attribute(Attr::UNDERLINE).unwrap(), b"\x1b[4m");
+        assert_eq!(caps.exit_attributes(), b"\x1b[m\x0f");
+        assert_eq!(caps.setup(), b"\x1b[?25l\x1b[?1c\x1b[H\x1b[2J");
+        assert_eq!(caps.restore(), b"\x1b[m\x0f\x1b[?25h\x1b[?0c");
+    }
+}
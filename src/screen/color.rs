@@ -1,10 +1,148 @@
+use std::env;
 use std::fmt::{self, Display};
+use std::str::FromStr;
 
-use termion::color::Rgb;
+use failure::Fail;
+use termion::color::{self, AnsiValue, Rgb};
 
 use crate::protocol;
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+use super::terminfo::TermInfo;
+
+/// The number of colors that a terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// The first 8 ANSI colors, for terminals that can't display the bright colors.
+    Ansi8,
+
+    /// The 16 ANSI colors. The actual colors depend on the terminal's palette.
+    Ansi16,
+
+    /// The xterm 256-color palette.
+    Ansi256,
+
+    /// 24-bit color.
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Detects the color depth of the terminal from the environment.
+    ///
+    /// There is no reliable way to detect support for 24-bit color, so we trust `COLORTERM`,
+    /// which is set by most terminals that support it, and the nonstandard `RGB` and `Tc`
+    /// terminfo capabilities. Otherwise, the depth is determined by the `colors` capability of the
+    /// terminfo entry for `TERM`.
+    pub fn detect() -> Self {
+        let colors = TermInfo::from_env().ok().and_then(|info| {
+            if info.flag("RGB") || info.flag("Tc") {
+                Some(1 << 24)
+            } else {
+                info.number("colors")
+            }
+        });
+
+        Self::from_env(
+            env::var("COLORTERM").ok().as_ref().map(String::as_str),
+            env::var("TERM").ok().as_ref().map(String::as_str),
+            colors,
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>, colors: Option<u32>) -> Self {
+        if let Some("truecolor") | Some("24bit") = colorterm {
+            return ColorDepth::TrueColor;
+        }
+
+        match colors {
+            // Terminfo entries for "direct color" terminals, such as `xterm-direct`.
+            Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+            Some(colors) if colors >= 256 => ColorDepth::Ansi256,
+            Some(colors) if colors >= 16 => ColorDepth::Ansi16,
+            Some(_) => ColorDepth::Ansi8,
+            None if term.map_or(false, |term| term.contains("256color")) => ColorDepth::Ansi256,
+            None => ColorDepth::Ansi16,
+        }
+    }
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+#[fail(
+    display = "invalid color depth: {} (expected 8, 16, 256 or truecolor)",
+    _0
+)]
+pub struct ParseColorDepthError(String);
+
+impl FromStr for ColorDepth {
+    type Err = ParseColorDepthError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(ColorDepth::Ansi8),
+            "16" => Ok(ColorDepth::Ansi16),
+            "256" => Ok(ColorDepth::Ansi256),
+            "truecolor" | "24bit" => Ok(ColorDepth::TrueColor),
+            _ => Err(ParseColorDepthError(s.to_string())),
+        }
+    }
+}
+
+/// The colors of the xterm 256-color palette that make up the 6x6x6 color cube.
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+/// The default xterm colors for the 16 ANSI colors.
+///
+/// Terminals are free to use a different palette, so this is only an approximation.
+const ANSI_COLORS: [Color; 16] = [
+    Color::new(0x00, 0x00, 0x00),
+    Color::new(0xcd, 0x00, 0x00),
+    Color::new(0x00, 0xcd, 0x00),
+    Color::new(0xcd, 0xcd, 0x00),
+    Color::new(0x00, 0x00, 0xee),
+    Color::new(0xcd, 0x00, 0xcd),
+    Color::new(0x00, 0xcd, 0xcd),
+    Color::new(0xe5, 0xe5, 0xe5),
+    Color::new(0x7f, 0x7f, 0x7f),
+    Color::new(0xff, 0x00, 0x00),
+    Color::new(0x00, 0xff, 0x00),
+    Color::new(0xff, 0xff, 0x00),
+    Color::new(0x5c, 0x5c, 0xff),
+    Color::new(0xff, 0x00, 0xff),
+    Color::new(0x00, 0xff, 0xff),
+    Color::new(0xff, 0xff, 0xff),
+];
+
+/// The escape sequences for a color at a particular color depth.
+#[derive(Debug, Clone, Copy)]
+pub enum Escape {
+    Rgb(Rgb),
+    Ansi256(AnsiValue),
+
+    /// One of the 16 ANSI colors. Unlike `AnsiValue`, this uses the SGR sequences that are
+    /// understood by terminals without 256-color support.
+    Ansi16(u8),
+}
+
+impl color::Color for Escape {
+    fn write_fg(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Escape::Rgb(rgb) => rgb.write_fg(f),
+            Escape::Ansi256(value) => value.write_fg(f),
+            Escape::Ansi16(n) if n < 8 => write!(f, "\x1b[{}m", 30 + n),
+            Escape::Ansi16(n) => write!(f, "\x1b[{}m", 90 + n - 8),
+        }
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Escape::Rgb(rgb) => rgb.write_bg(f),
+            Escape::Ansi256(value) => value.write_bg(f),
+            Escape::Ansi16(n) if n < 8 => write!(f, "\x1b[{}m", 40 + n),
+            Escape::Ansi16(n) => write!(f, "\x1b[{}m", 100 + n - 8),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -12,6 +150,10 @@ pub struct Color {
 }
 
 impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
     pub fn from_argb(argb: u32) -> Self {
         Self {
             r: ((argb & 0x00ff_0000) >> 16) as u8,
@@ -20,8 +162,87 @@ impl Color {
         }
     }
 
-    pub fn as_escapes(&self) -> Rgb {
-        Rgb(self.r, self.g, self.b)
+    /// Returns the escape sequences for the color, approximated by the nearest color that the
+    /// terminal can display.
+    pub fn as_escapes(&self, depth: ColorDepth) -> Escape {
+        match depth {
+            ColorDepth::TrueColor => Escape::Rgb(Rgb(self.r, self.g, self.b)),
+            ColorDepth::Ansi256 => Escape::Ansi256(AnsiValue(self.to_ansi256())),
+            ColorDepth::Ansi16 => Escape::Ansi16(self.to_ansi16()),
+            ColorDepth::Ansi8 => Escape::Ansi16(self.to_ansi8()),
+        }
+    }
+
+    /// Returns the index of the nearest color in the xterm 256-color palette.
+    ///
+    /// The first 16 colors are skipped, since they're usually changed by the terminal's theme.
+    pub fn to_ansi256(self) -> u8 {
+        let nearest_level = |c: u8| {
+            CUBE_LEVELS
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &level)| (i16::from(level) - i16::from(c)).abs())
+                .map(|(i, _)| i as u8)
+                .unwrap()
+        };
+        let (r, g, b) = (
+            nearest_level(self.r),
+            nearest_level(self.g),
+            nearest_level(self.b),
+        );
+        let cube = Color::new(
+            CUBE_LEVELS[usize::from(r)],
+            CUBE_LEVELS[usize::from(g)],
+            CUBE_LEVELS[usize::from(b)],
+        );
+
+        // The grayscale ramp runs from 8 to 238 in steps of 10.
+        let average = (u16::from(self.r) + u16::from(self.g) + u16::from(self.b)) / 3;
+        let gray_idx = (average.saturating_sub(3) / 10).min(23) as u8;
+        let gray_level = 8 + 10 * gray_idx;
+        let gray = Color::new(gray_level, gray_level, gray_level);
+
+        if self.distance(&gray) < self.distance(&cube) {
+            232 + gray_idx
+        } else {
+            16 + 36 * r + 6 * g + b
+        }
+    }
+
+    /// Returns the index of the nearest of the 16 ANSI colors.
+    pub fn to_ansi16(self) -> u8 {
+        self.nearest(&ANSI_COLORS)
+    }
+
+    /// Returns the index of the nearest of the first 8 ANSI colors.
+    pub fn to_ansi8(self) -> u8 {
+        self.nearest(&ANSI_COLORS[..8])
+    }
+
+    /// Returns the index of the nearest color in a palette.
+    fn nearest(self, palette: &[Color]) -> u8 {
+        palette
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, color)| self.distance(color))
+            .map(|(i, _)| i as u8)
+            .unwrap()
+    }
+
+    /// An approximation of the perceived difference between two colors.
+    ///
+    /// This is the "redmean" weighted Euclidean distance, which weights each component by how
+    /// sensitive the eye is to it. It's much cheaper than converting to a perceptual color space,
+    /// and good enough to choose between palette colors.
+    fn distance(&self, other: &Color) -> u32 {
+        let mean = (u32::from(self.r) + u32::from(other.r)) / 2;
+        let dr = i32::from(self.r) - i32::from(other.r);
+        let dg = i32::from(self.g) - i32::from(other.g);
+        let db = i32::from(self.b) - i32::from(other.b);
+
+        (((512 + mean) * (dr * dr) as u32) >> 8)
+            + 4 * (dg * dg) as u32
+            + (((767 - mean) * (db * db) as u32) >> 8)
     }
 }
 
@@ -43,7 +264,7 @@ impl From<protocol::Color> for Color {
 
 #[cfg(test)]
 mod tests {
-    use super::Color;
+    use super::{Color, ColorDepth, ParseColorDepthError, ANSI_COLORS, CUBE_LEVELS};
 
     #[test]
     fn argb_conversion() {
@@ -61,4 +282,130 @@ mod tests {
     fn display() {
         assert_eq!(Color::from_argb(4292032130).to_string(), "#d33682");
     }
+
+    #[test]
+    fn ansi256_cube() {
+        // Colors in the palette map to themselves.
+        for (r, &red) in CUBE_LEVELS.iter().enumerate() {
+            for (g, &green) in CUBE_LEVELS.iter().enumerate() {
+                for (b, &blue) in CUBE_LEVELS.iter().enumerate() {
+                    let expected = 16 + 36 * r + 6 * g + b;
+                    assert_eq!(
+                        usize::from(Color::new(red, green, blue).to_ansi256()),
+                        expected
+                    );
+                }
+            }
+        }
+
+        assert_eq!(Color::new(0xd3, 0x36, 0x82).to_ansi256(), 168);
+        assert_eq!(Color::new(0x26, 0x8b, 0xd2).to_ansi256(), 32);
+        assert_eq!(Color::new(0xfd, 0xf6, 0xe3).to_ansi256(), 230);
+    }
+
+    #[test]
+    fn ansi256_grayscale() {
+        for i in 0..24 {
+            let level = 8 + 10 * i;
+            assert_eq!(Color::new(level, level, level).to_ansi256(), 232 + i);
+        }
+
+        // Grays that are closer to the ends of the cube use the cube.
+        assert_eq!(Color::new(0, 0, 0).to_ansi256(), 16);
+        assert_eq!(Color::new(0xff, 0xff, 0xff).to_ansi256(), 231);
+        assert_eq!(Color::new(0x00, 0x2b, 0x36).to_ansi256(), 234);
+    }
+
+    #[test]
+    fn ansi16() {
+        for (i, color) in ANSI_COLORS.iter().enumerate() {
+            assert_eq!(usize::from(color.to_ansi16()), i);
+        }
+
+        assert_eq!(Color::new(0xdc, 0x32, 0x2f).to_ansi16(), 1);
+        assert_eq!(Color::new(0x2a, 0xa1, 0x98).to_ansi16(), 6);
+        assert_eq!(Color::new(0x26, 0x8b, 0xd2).to_ansi16(), 6);
+        assert_eq!(Color::new(0x93, 0xa1, 0xa1).to_ansi16(), 8);
+    }
+
+    #[test]
+    fn ansi8() {
+        for (i, color) in ANSI_COLORS[..8].iter().enumerate() {
+            assert_eq!(usize::from(color.to_ansi8()), i);
+        }
+
+        // Bright colors are approximated by their normal counterparts.
+        assert_eq!(Color::new(0xff, 0x00, 0x00).to_ansi8(), 1);
+        assert_eq!(Color::new(0xff, 0xff, 0xff).to_ansi8(), 7);
+        assert_eq!(Color::new(0x93, 0xa1, 0xa1).to_ansi8(), 7);
+    }
+
+    #[test]
+    fn escapes() {
+        let color = Color::new(0xd3, 0x36, 0x82);
+        let fg = |depth| termion::color::Fg(color.as_escapes(depth)).to_string();
+        let bg = |depth| termion::color::Bg(color.as_escapes(depth)).to_string();
+
+        assert_eq!(fg(ColorDepth::TrueColor), "\x1b[38;2;211;54;130m");
+        assert_eq!(fg(ColorDepth::Ansi256), "\x1b[38;5;168m");
+        assert_eq!(bg(ColorDepth::Ansi256), "\x1b[48;5;168m");
+        assert_eq!(fg(ColorDepth::Ansi16), "\x1b[35m");
+        assert_eq!(bg(ColorDepth::Ansi16), "\x1b[45m");
+        assert_eq!(fg(ColorDepth::Ansi8), "\x1b[35m");
+
+        // Terminals with 8 colors don't get the codes for bright colors.
+        let white = Color::new(0xff, 0xff, 0xff);
+        assert_eq!(
+            termion::color::Fg(white.as_escapes(ColorDepth::Ansi16)).to_string(),
+            "\x1b[97m"
+        );
+        assert_eq!(
+            termion::color::Bg(white.as_escapes(ColorDepth::Ansi8)).to_string(),
+            "\x1b[47m"
+        );
+        assert_eq!(
+            termion::color::Fg(Color::new(0xcd, 0, 0).as_escapes(ColorDepth::Ansi16)).to_string(),
+            "\x1b[31m"
+        );
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            ColorDepth::from_env(Some("truecolor"), Some("xterm-256color"), Some(256)),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-direct"), Some(1 << 24)),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-256color"), Some(256)),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("screen-256color"), None),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-16color"), Some(16)),
+            ColorDepth::Ansi16
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm"), Some(8)),
+            ColorDepth::Ansi8
+        );
+        assert_eq!(ColorDepth::from_env(None, None, None), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("8".parse(), Ok(ColorDepth::Ansi8));
+        assert_eq!("256".parse(), Ok(ColorDepth::Ansi256));
+        assert_eq!("truecolor".parse(), Ok(ColorDepth::TrueColor));
+        assert_eq!(
+            "88".parse::<ColorDepth>(),
+            Err(ParseColorDepthError(String::from("88")))
+        );
+    }
 }
@@ -0,0 +1,446 @@
+//! Writing the difference between two frames to the terminal.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+use ndarray::prelude::*;
+use termion::color::{Bg, Fg, Reset};
+
+use super::capabilities::Capabilities;
+use super::{Attr, Buffer, Cell, Color, ColorDepth, Content};
+
+/// The number of unchanged cells that may be rewritten to avoid moving the cursor.
+///
+/// Moving the cursor takes at least six bytes, so short gaps are cheaper to rewrite.
+const MAX_GAP: usize = 4;
+
+/// The number of rows that a scroll must save from being redrawn to be worth it.
+const MIN_SCROLL_BENEFIT: usize = 2;
+
+/// Every attribute, in the order that they are turned on.
+const ATTRIBUTES: [Attr; 10] = [
+    Attr::BOLD,
+    Attr::DIM,
+    Attr::ITALIC,
+    Attr::UNDERLINE,
+    Attr::DOUBLE_UNDERLINE,
+    Attr::CURLY_UNDERLINE,
+    Attr::DOTTED_UNDERLINE,
+    Attr::BLINK,
+    Attr::REVERSE,
+    Attr::STRIKETHROUGH,
+];
+
+/// A range of rows whose contents moved by a whole number of lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scroll {
+    /// The first row of the region.
+    pub top: usize,
+
+    /// The last row of the region (inclusive).
+    pub bottom: usize,
+
+    /// The number of rows that the contents moved. Positive values move the contents up.
+    pub shift: isize,
+}
+
+impl Scroll {
+    /// Finds the scroll that saves the most rows from being redrawn when updating the screen from
+    /// `old` to `new`, if there is one that's worth it.
+    ///
+    /// Rows are compared by their hashes, so that each candidate scroll doesn't compare whole
+    /// rows. A collision only makes the scroll less useful, since the rows that differ after
+    /// scrolling are still redrawn.
+    pub fn find(old: &Buffer, new: &Buffer) -> Option<Scroll> {
+        let height = new.len_of(Axis(0));
+        let changed = (0..height)
+            .map(|y| new.row(y) != old.row(y))
+            .collect::<Vec<_>>();
+
+        // A scroll can't save more rows than have changed.
+        if changed.iter().filter(|&&c| c).count() < MIN_SCROLL_BENEFIT {
+            return None;
+        }
+
+        let old_hashes = row_hashes(old);
+        let new_hashes = row_hashes(new);
+        let blank = (0..height)
+            .map(|y| new.row(y).iter().all(|cell| *cell == Cell::default()))
+            .collect::<Vec<_>>();
+
+        let mut best: Option<(usize, Scroll)> = None;
+
+        for distance in 1..height {
+            for &shift in &[distance as isize, -(distance as isize)] {
+                let source = |y: usize| (y as isize + shift) as usize;
+                let (first, last) = if shift > 0 {
+                    (0, height - distance)
+                } else {
+                    (distance, height)
+                };
+
+                let mut y = first;
+                while y < last {
+                    if new_hashes[y] != old_hashes[source(y)] {
+                        y += 1;
+                        continue;
+                    }
+
+                    let start = y;
+                    while y < last && new_hashes[y] == old_hashes[source(y)] {
+                        y += 1;
+                    }
+
+                    // The rows that are scrolled into view are cleared, so they may need to be
+                    // redrawn even if they didn't change.
+                    let (scroll, exposed) = if shift > 0 {
+                        let scroll = Scroll {
+                            top: start,
+                            bottom: y + distance - 1,
+                            shift,
+                        };
+                        (scroll, y..y + distance)
+                    } else {
+                        let scroll = Scroll {
+                            top: start - distance,
+                            bottom: y - 1,
+                            shift,
+                        };
+                        (scroll, start - distance..start)
+                    };
+
+                    let saved = changed[start..y].iter().filter(|&&c| c).count();
+                    let lost = exposed.filter(|&y| !changed[y] && !blank[y]).count();
+                    let benefit = saved.saturating_sub(lost);
+
+                    if benefit >= MIN_SCROLL_BENEFIT && benefit > best.map_or(0, |(best, _)| best) {
+                        best = Some((benefit, scroll));
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, scroll)| scroll)
+    }
+
+    /// Scrolls a buffer the same way that the terminal is scrolled.
+    pub fn apply(&self, buf: &mut Buffer) {
+        let old = buf.clone();
+
+        for y in self.top..=self.bottom {
+            let source = y as isize + self.shift;
+            if source >= self.top as isize && source <= self.bottom as isize {
+                buf.row_mut(y).assign(&old.row(source as usize));
+            } else {
+                buf.row_mut(y).fill(Cell::default());
+            }
+        }
+    }
+}
+
+/// Returns the hash of each row of a buffer.
+fn row_hashes(buf: &Buffer) -> Vec<u64> {
+    buf.genrows()
+        .into_iter()
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            for cell in row {
+                cell.hash(&mut hasher);
+            }
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Writes cells to the terminal, keeping track of the cursor position and the current attributes
+/// to avoid writing redundant escape sequences.
+pub struct Painter<'a, W: Write> {
+    out: &'a mut W,
+    caps: &'a Capabilities,
+    color_depth: ColorDepth,
+
+    fg: Option<Color>,
+    bg: Option<Color>,
+    attr: Attr,
+    underline_color: Option<Color>,
+
+    /// The position of the terminal cursor, if it is known.
+    cursor: Option<(usize, usize)>,
+}
+
+impl<'a, W: Write> Painter<'a, W> {
+    /// Creates a new painter. The terminal's attributes must be reset.
+    pub fn new(out: &'a mut W, caps: &'a Capabilities, color_depth: ColorDepth) -> Self {
+        Painter {
+            out,
+            caps,
+            color_depth,
+            fg: None,
+            bg: None,
+            attr: Attr::empty(),
+            underline_color: None,
+            cursor: None,
+        }
+    }
+
+    /// Updates a row that changed from `old` to `new`.
+    pub fn row(
+        &mut self,
+        y: usize,
+        old: ArrayView1<Cell>,
+        new: ArrayView1<Cell>,
+    ) -> io::Result<()> {
+        let width = new.len();
+        let end_of = |row: ArrayView1<Cell>| {
+            row.iter()
+                .rposition(|cell| *cell != Cell::default())
+                .map_or(0, |x| x + 1)
+        };
+        let end = end_of(new);
+
+        let mut x = 0;
+        while x < end {
+            if new[x] == old[x] {
+                x += 1;
+                continue;
+            }
+
+            // Find the end of the run of changes, including short gaps of unchanged cells.
+            let mut start = x;
+            let mut run_end = x + 1;
+            while run_end < end {
+                match (run_end..end.min(run_end + MAX_GAP + 1)).find(|&x| new[x] != old[x]) {
+                    Some(next) => run_end = next + 1,
+                    None => break,
+                }
+            }
+
+            // Double-width characters must be written in their entirety.
+            if new[start].content == Content::Continuation {
+                start -= 1;
+            }
+            if run_end < width && new[run_end].content == Content::Continuation {
+                run_end += 1;
+            }
+
+            for x in start..run_end {
+                self.cell(x, y, new)?;
+            }
+            x = run_end;
+        }
+
+        if end_of(old) > end {
+            self.clear_eol(end, y)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scrolls a region of the terminal.
+    ///
+    /// Returns `false` if the terminal does not support scroll regions.
+    pub fn scroll(&mut self, scroll: &Scroll, height: usize) -> io::Result<bool> {
+        let sequence = match self
+            .caps
+            .scroll(scroll.top, scroll.bottom, scroll.shift, height)
+        {
+            Some(sequence) => sequence,
+            None => return Ok(false),
+        };
+
+        // The new rows are filled with the current background color.
+        self.reset()?;
+        self.out.write_all(&sequence)?;
+        self.cursor = None;
+        Ok(true)
+    }
+
+    /// Turns off all attributes, if any are set.
+    pub fn reset(&mut self) -> io::Result<()> {
+        if !self.attr.is_empty()
+            || self.fg.is_some()
+            || self.bg.is_some()
+            || self.underline_color.is_some()
+        {
+            self.out.write_all(self.caps.exit_attributes())?;
+            self.attr = Attr::empty();
+            self.fg = None;
+            self.bg = None;
+            self.underline_color = None;
+        }
+
+        Ok(())
+    }
+
+    fn move_to(&mut self, x: usize, y: usize) -> io::Result<()> {
+        if self.cursor != Some((x, y)) {
+            self.out.write_all(&self.caps.goto(x, y))?;
+            self.cursor = Some((x, y));
+        }
+
+        Ok(())
+    }
+
+    fn clear_eol(&mut self, x: usize, y: usize) -> io::Result<()> {
+        // Clearing the line fills it with the current background color.
+        self.reset()?;
+        self.move_to(x, y)?;
+        self.out.write_all(self.caps.clear_eol())
+    }
+
+    fn cell(&mut self, x: usize, y: usize, row: ArrayView1<Cell>) -> io::Result<()> {
+        let cell = &row[x];
+        let width = row.len();
+        let advance = match cell.content {
+            // The double-width character in the previous cell already moved the terminal cursor
+            // past this cell.
+            Content::Continuation => return Ok(()),
+            _ if x + 1 < width && row[x + 1].content == Content::Continuation => 2,
+            _ => 1,
+        };
+
+        self.move_to(x, y)?;
+        self.style(cell)?;
+
+        match &cell.content {
+            Content::Char(c) => write!(self.out, "{}", c)?,
+            Content::Cluster(cluster) => write!(self.out, "{}", cluster)?,
+            _ => write!(self.out, " ")?,
+        }
+
+        // After writing to the last column, terminals differ in where they leave the cursor.
+        self.cursor = if x + advance < width {
+            Some((x + advance, y))
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+
+    fn style(&mut self, cell: &Cell) -> io::Result<()> {
+        let caps = self.caps;
+        let mut removed = self.attr - cell.attr;
+
+        // Setting a style of underline replaces the current one, so it isn't turned off.
+        if cell.attr.intersects(Attr::UNDERLINES) {
+            self.attr -= removed & Attr::UNDERLINES;
+            removed -= Attr::UNDERLINES;
+        }
+
+        let exits = ATTRIBUTES
+            .iter()
+            .filter(|&&flag| removed.contains(flag))
+            .map(|&flag| caps.exit_attribute(flag))
+            .collect::<Option<Vec<_>>>();
+
+        match exits {
+            Some(mut exits) if cell.underline_color.is_some() || self.underline_color.is_none() => {
+                // All underline styles are turned off by the same sequence.
+                exits.dedup();
+                for sequence in exits {
+                    self.out.write_all(sequence)?;
+                }
+            }
+            // Terminfo has no sequences for turning off most attributes or the underline color, so
+            // everything is turned off and the remaining attributes are turned back on. This also
+            // turns off the colors, so attributes must be set first.
+            _ => self.reset()?,
+        }
+
+        for &flag in &ATTRIBUTES {
+            if cell.attr.contains(flag) && !self.attr.contains(flag) {
+                if let Some(sequence) = caps.attribute(flag) {
+                    self.out.write_all(&sequence)?;
+                }
+            }
+        }
+        self.attr = cell.attr;
+
+        if cell.underline_color != self.underline_color {
+            self.underline_color = cell.underline_color;
+            if let Some(sequence) = cell.underline_color.and_then(|c| caps.underline_color(c)) {
+                self.out.write_all(&sequence)?;
+            }
+        }
+
+        if cell.fg != self.fg {
+            self.fg = cell.fg;
+            match cell.fg {
+                Some(fg) => write!(self.out, "{}", Fg(fg.as_escapes(self.color_depth)))?,
+                None => write!(self.out, "{}", Fg(Reset))?,
+            }
+        }
+
+        if cell.bg != self.bg {
+            self.bg = cell.bg;
+            match cell.bg {
+                Some(bg) => write!(self.out, "{}", Bg(bg.as_escapes(self.color_depth)))?,
+                None => write!(self.out, "{}", Bg(Reset))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::s;
+
+    use super::super::{Buffer, Cell, Content};
+    use super::Scroll;
+
+    fn buffer(rows: &[&str]) -> Buffer {
+        let mut buf = Buffer::from_elem((rows.len(), 1), Cell::default());
+        for (y, row) in rows.iter().enumerate() {
+            if let Some(c) = row.chars().next() {
+                buf[(y, 0)].content = Content::Char(c);
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn find_scroll() {
+        let old = buffer(&["a", "b", "c", "d", "e", "f"]);
+
+        let new = buffer(&["a", "c", "d", "e", "f", ""]);
+        let scroll = Scroll::find(&old, &new).unwrap();
+        assert_eq!(
+            scroll,
+            Scroll {
+                top: 1,
+                bottom: 5,
+                shift: 1
+            }
+        );
+        let mut scrolled = old.clone();
+        scroll.apply(&mut scrolled);
+        assert_eq!(scrolled, new);
+
+        let new = buffer(&["x", "y", "a", "b", "c", "d"]);
+        let scroll = Scroll::find(&old, &new).unwrap();
+        assert_eq!(
+            scroll,
+            Scroll {
+                top: 0,
+                bottom: 5,
+                shift: -2
+            }
+        );
+        let mut scrolled = old.clone();
+        scroll.apply(&mut scrolled);
+        assert_eq!(scrolled.slice(s![2.., ..]), new.slice(s![2.., ..]));
+
+        // Scrolling a single changed row isn't worth it.
+        let new = buffer(&["a", "b", "c", "d", "f", "f"]);
+        assert_eq!(Scroll::find(&old, &new), None);
+
+        // Neither is swapping two rows, since only one of them can be scrolled into place.
+        let new = buffer(&["b", "a", "c", "d", "e", "f"]);
+        assert_eq!(Scroll::find(&old, &new), None);
+        assert_eq!(Scroll::find(&old, &old), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! The terminal that the screen is drawn on.
+
+use std::io::{self, Stdout, Write};
+use std::mem;
+use std::panic;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::thread;
+
+use log::*;
+
+/// The state needed to restore the terminal.
+struct Restore {
+    /// The sequence that undoes the setup of the screen.
+    sequence: Vec<u8>,
+
+    /// The mode of the terminal before raw mode was enabled.
+    cooked: libc::termios,
+}
+
+/// The state of the current terminal, which is shared with the panic hook and the signal handlers.
+static RESTORE: AtomicPtr<Restore> = AtomicPtr::new(ptr::null_mut());
+
+/// Whether the terminal is set up for the screen, and must be restored.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the panic hook and the signal handlers have been installed.
+static HOOKS_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// The output that the screen is drawn to.
+pub trait Output: Write {
+    /// Restores the terminal, so that another program can use it.
+    fn suspend(&mut self) -> io::Result<()>;
+
+    /// Enables raw mode again after `suspend`. The screen must be set up again.
+    fn resume(&mut self) -> io::Result<()>;
+}
+
+/// The terminal, in raw mode and displaying the screen.
+///
+/// The terminal is restored to its previous state when this is dropped, when the thread that
+/// created it panics, and when the process is terminated by `SIGTERM` or `SIGHUP`, so that the
+/// user's shell is usable afterwards. The terminal is restored before the panic hook that was
+/// installed when the terminal was created runs, so panic messages are printed to the normal
+/// screen.
+///
+/// Panics in other threads leave the terminal alone, since the thread that spawned them may
+/// recover, such as by joining them, or panic itself when it notices that they stopped.
+pub struct Terminal {
+    out: Stdout,
+
+    /// The mode of the terminal while the screen is displayed.
+    raw: libc::termios,
+}
+
+impl Terminal {
+    /// Enables raw mode. `sequence` undoes the setup of the screen.
+    pub fn new(sequence: Vec<u8>) -> io::Result<Self> {
+        let cooked = terminal_mode()?;
+        let mut raw = cooked;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        set_terminal_mode(&raw)?;
+
+        // The previous state is leaked rather than freed, because a signal handler could be using
+        // it. Terminals are rarely created more than once.
+        let state = Box::new(Restore { sequence, cooked });
+        RESTORE.store(Box::into_raw(state), Ordering::SeqCst);
+        ACTIVE.store(true, Ordering::SeqCst);
+
+        if !HOOKS_INSTALLED.swap(true, Ordering::SeqCst) {
+            let hook = panic::take_hook();
+            let owner = thread::current().id();
+            panic::set_hook(Box::new(move |info| {
+                if thread::current().id() == owner {
+                    restore();
+                }
+                hook(info);
+            }));
+
+            unsafe {
+                libc::signal(
+                    libc::SIGTERM,
+                    handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                );
+                libc::signal(
+                    libc::SIGHUP,
+                    handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                );
+            }
+        }
+
+        Ok(Terminal {
+            out: io::stdout(),
+            raw,
+        })
+    }
+}
+
+impl Output for Terminal {
+    fn suspend(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        restore();
+        Ok(())
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        set_terminal_mode(&self.raw)?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Output that is kept in memory, for testing.
+#[cfg(test)]
+impl Output for Vec<u8> {
+    fn suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if let Err(e) = self.out.flush() {
+            error!("could not flush terminal: {}", e);
+        }
+        restore();
+    }
+}
+
+/// Restores the terminal, if it is set up for the screen.
+///
+/// This is called by signal handlers, so it may only use async-signal-safe functions.
+fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    let restore = match unsafe { RESTORE.load(Ordering::SeqCst).as_ref() } {
+        Some(restore) => restore,
+        None => return,
+    };
+
+    let mut sequence = &restore.sequence[..];
+    while !sequence.is_empty() {
+        let written = unsafe {
+            libc::write(
+                libc::STDOUT_FILENO,
+                sequence.as_ptr() as *const libc::c_void,
+                sequence.len(),
+            )
+        };
+        if written < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            break;
+        }
+        sequence = &sequence[written as usize..];
+    }
+
+    unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &restore.cooked) };
+}
+
+extern "C" fn handle_signal(signal: libc::c_int) {
+    restore();
+
+    // Terminate the process with the default action, so that the parent can tell why it ended.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+fn terminal_mode() -> io::Result<libc::termios> {
+    unsafe {
+        let mut termios = mem::zeroed();
+        if libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(termios)
+    }
+}
+
+fn set_terminal_mode(termios: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
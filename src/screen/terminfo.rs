@@ -0,0 +1,806 @@
+//! A parser for compiled terminfo entries.
+//!
+//! Terminfo describes the escape sequences that a terminal understands. Entries are read from the
+//! database directories used by ncurses, in the format described by term(5), including the
+//! extended capabilities (such as `Ss` or `RGB`) that are not part of the original standard.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use failure::Fail;
+use log::*;
+
+/// The names of the standard boolean capabilities, in the order that they are stored.
+const BOOL_NAMES: &[&str] = &[
+    "bw", "am", "xsb", "xhp", "xenl", "eo", "gn", "hc", "km", "hs", "in", "db", "da", "mir",
+    "msgr", "os", "eslok", "xt", "hz", "ul", "xon", "nxon", "mc5i", "chts", "nrrmc", "npc",
+    "ndscr", "ccc", "bce", "hls", "xhpa", "crxm", "daisy", "xvpa", "sam", "cpix", "lpix", "OTbs",
+    "OTns", "OTnc", "OTMT", "OTNL", "OTpt", "OTxr",
+];
+
+/// The names of the standard numeric capabilities, in the order that they are stored.
+const NUMBER_NAMES: &[&str] = &[
+    "cols", "it", "lines", "lm", "xmc", "pb", "vt", "wsl", "nlab", "lh", "lw", "ma", "wnum",
+    "colors", "pairs", "ncv", "bufsz", "spinv", "spinh", "maddr", "mjump", "mcs", "mls", "npins",
+    "orc", "orl", "orhi", "orvi", "cps", "widcs", "btns", "bitwin", "bitype", "UTug", "OTdC",
+    "OTdN", "OTdB", "OTdT", "OTkn",
+];
+
+/// The names of the standard string capabilities, in the order that they are stored.
+const STRING_NAMES: &[&str] = &[
+    "cbt", "bel", "cr", "csr", "tbc", "clear", "el", "ed", "hpa", "cmdch", "cup", "cud1", "home",
+    "civis", "cub1", "mrcup", "cnorm", "cuf1", "ll", "cuu1", "cvvis", "dch1", "dl1", "dsl", "hd",
+    "smacs", "blink", "bold", "smcup", "smdc", "dim", "smir", "invis", "prot", "rev", "smso",
+    "smul", "ech", "rmacs", "sgr0", "rmcup", "rmdc", "rmir", "rmso", "rmul", "flash", "ff", "fsl",
+    "is1", "is2", "is3", "if", "ich1", "il1", "ip", "kbs", "ktbc", "kclr", "kctab", "kdch1",
+    "kdl1", "kcud1", "krmir", "kel", "ked", "kf0", "kf1", "kf10", "kf2", "kf3", "kf4", "kf5",
+    "kf6", "kf7", "kf8", "kf9", "khome", "kich1", "kil1", "kcub1", "kll", "knp", "kpp", "kcuf1",
+    "kind", "kri", "khts", "kcuu1", "rmkx", "smkx", "lf0", "lf1", "lf10", "lf2", "lf3", "lf4",
+    "lf5", "lf6", "lf7", "lf8", "lf9", "rmm", "smm", "nel", "pad", "dch", "dl", "cud", "ich",
+    "indn", "il", "cub", "cuf", "rin", "cuu", "pfkey", "pfloc", "pfx", "mc0", "mc4", "mc5", "rep",
+    "rs1", "rs2", "rs3", "rf", "rc", "vpa", "sc", "ind", "ri", "sgr", "hts", "wind", "ht", "tsl",
+    "uc", "hu", "iprog", "ka1", "ka3", "kb2", "kc1", "kc3", "mc5p", "rmp", "acsc", "pln", "kcbt",
+    "smxon", "rmxon", "smam", "rmam", "xonc", "xoffc", "enacs", "smln", "rmln", "kbeg", "kcan",
+    "kclo", "kcmd", "kcpy", "kcrt", "kend", "kent", "kext", "kfnd", "khlp", "kmrk", "kmsg", "kmov",
+    "knxt", "kopn", "kopt", "kprv", "kprt", "krdo", "kref", "krfr", "krpl", "krst", "kres", "ksav",
+    "kspd", "kund", "kBEG", "kCAN", "kCMD", "kCPY", "kCRT", "kDC", "kDL", "kslt", "kEND", "kEOL",
+    "kEXT", "kFND", "kHLP", "kHOM", "kIC", "kLFT", "kMSG", "kMOV", "kNXT", "kOPT", "kPRV", "kPRT",
+    "kRDO", "kRPL", "kRIT", "kRES", "kSAV", "kSPD", "kUND", "rfi", "kf11", "kf12", "kf13", "kf14",
+    "kf15", "kf16", "kf17", "kf18", "kf19", "kf20", "kf21", "kf22", "kf23", "kf24", "kf25", "kf26",
+    "kf27", "kf28", "kf29", "kf30", "kf31", "kf32", "kf33", "kf34", "kf35", "kf36", "kf37", "kf38",
+    "kf39", "kf40", "kf41", "kf42", "kf43", "kf44", "kf45", "kf46", "kf47", "kf48", "kf49", "kf50",
+    "kf51", "kf52", "kf53", "kf54", "kf55", "kf56", "kf57", "kf58", "kf59", "kf60", "kf61", "kf62",
+    "kf63", "el1", "mgc", "smgl", "smgr", "fln", "sclk", "dclk", "rmclk", "cwin", "wingo", "hup",
+    "dial", "qdial", "tone", "pulse", "hook", "pause", "wait", "u0", "u1", "u2", "u3", "u4", "u5",
+    "u6", "u7", "u8", "u9", "op", "oc", "initc", "initp", "scp", "setf", "setb", "cpi", "lpi",
+    "chr", "cvr", "defc", "swidm", "sdrfq", "sitm", "slm", "smicm", "snlq", "snrmq", "sshm",
+    "ssubm", "ssupm", "sum", "rwidm", "ritm", "rlm", "rmicm", "rshm", "rsubm", "rsupm", "rum",
+    "mhpa", "mcud1", "mcub1", "mcuf1", "mvpa", "mcuu1", "porder", "mcud", "mcub", "mcuf", "mcuu",
+    "scs", "smgb", "smgbp", "smglp", "smgrp", "smgt", "smgtp", "sbim", "scsd", "rbim", "rcsd",
+    "subcs", "supcs", "docr", "zerom", "csnm", "kmous", "minfo", "reqmp", "getm", "setaf", "setab",
+    "pfxl", "devt", "csin", "s0ds", "s1ds", "s2ds", "s3ds", "smglr", "smgtb", "birep", "binel",
+    "bicr", "colornm", "defbi", "endbi", "setcolor", "slines", "dispc", "smpch", "rmpch", "smsc",
+    "rmsc", "pctrm", "scesc", "scesa", "ehhlm", "elhlm", "elohlm", "erhlm", "ethlm", "evhlm",
+    "sgr1", "slength", "OTi2", "OTrs", "OTnl", "OTbs", "OTko", "OTma", "OTG2", "OTG3", "OTG1",
+    "OTG4", "OTGR", "OTGL", "OTGU", "OTGD", "OTGH", "OTGV", "OTGC", "meml", "memu", "box1",
+];
+
+/// The magic number of entries that store numbers as 16-bit integers.
+const MAGIC: u16 = 0o432;
+
+/// The magic number of entries that store numbers as 32-bit integers.
+const MAGIC_32BIT: u16 = 0o1036;
+
+#[derive(Debug, Fail)]
+pub enum TermInfoError {
+    #[fail(display = "TERM is not set")]
+    TermUnset,
+
+    #[fail(display = "no terminfo entry for {}", _0)]
+    NotFound(String),
+
+    #[fail(display = "could not read terminfo entry: {}", _0)]
+    Io(#[cause] io::Error),
+
+    #[fail(display = "invalid terminfo entry: {}", _0)]
+    Invalid(&'static str),
+}
+
+/// The capabilities of a terminal, as described by its terminfo entry.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TermInfo {
+    names: Vec<String>,
+    bools: HashMap<String, bool>,
+    numbers: HashMap<String, u32>,
+    strings: HashMap<String, Vec<u8>>,
+}
+
+impl TermInfo {
+    /// Reads the terminfo entry for the terminal named by `TERM`.
+    pub fn from_env() -> Result<Self, TermInfoError> {
+        match env::var("TERM") {
+            Ok(ref term) if !term.is_empty() => Self::from_name(term),
+            _ => Err(TermInfoError::TermUnset),
+        }
+    }
+
+    /// Reads the terminfo entry for a terminal from the first database directory that has one.
+    pub fn from_name(name: &str) -> Result<Self, TermInfoError> {
+        let first = match name.chars().next() {
+            Some(first) if !name.contains('/') => first,
+            _ => return Err(TermInfoError::NotFound(name.to_string())),
+        };
+
+        for dir in search_dirs() {
+            // Some systems (such as macOS) name the subdirectories by the hexadecimal value of the
+            // first character instead of the character itself.
+            for subdir in &[first.to_string(), format!("{:x}", first as u32)] {
+                let path = dir.join(subdir).join(name);
+                match fs::read(&path) {
+                    Ok(bytes) => return Self::parse(&bytes),
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(TermInfoError::Io(e)),
+                }
+            }
+        }
+
+        Err(TermInfoError::NotFound(name.to_string()))
+    }
+
+    /// Parses a compiled terminfo entry.
+    pub fn parse(bytes: &[u8]) -> Result<Self, TermInfoError> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        let number_size = match reader.u16()? {
+            MAGIC => 2,
+            MAGIC_32BIT => 4,
+            _ => return Err(TermInfoError::Invalid("bad magic number")),
+        };
+
+        let names_size = reader.count()?;
+        let bools_count = reader.count()?;
+        let numbers_count = reader.count()?;
+        let strings_count = reader.count()?;
+        let table_size = reader.count()?;
+
+        if bools_count > BOOL_NAMES.len()
+            || numbers_count > NUMBER_NAMES.len()
+            || strings_count > STRING_NAMES.len()
+        {
+            return Err(TermInfoError::Invalid("too many capabilities"));
+        }
+
+        let names = String::from_utf8_lossy(reader.take(names_size)?)
+            .trim_end_matches('\0')
+            .split('|')
+            .map(String::from)
+            .collect();
+
+        let mut info = TermInfo {
+            names,
+            ..Default::default()
+        };
+
+        for (name, &value) in BOOL_NAMES.iter().zip(reader.take(bools_count)?) {
+            if value == 1 {
+                info.bools.insert(name.to_string(), true);
+            }
+        }
+        reader.align();
+
+        for name in &NUMBER_NAMES[..numbers_count] {
+            if let Some(value) = reader.number(number_size)? {
+                info.numbers.insert(name.to_string(), value);
+            }
+        }
+
+        let offsets = (0..strings_count)
+            .map(|_| reader.i16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let table = reader.take(table_size)?;
+        for (name, &offset) in STRING_NAMES.iter().zip(&offsets) {
+            if let Some(value) = string_at(table, offset)? {
+                info.strings.insert(name.to_string(), value.to_vec());
+            }
+        }
+
+        if reader.is_empty() {
+            return Ok(info);
+        }
+
+        reader.align();
+        info.parse_extended(&mut reader, number_size)?;
+
+        Ok(info)
+    }
+
+    /// Parses the extended capabilities that follow the standard capabilities.
+    ///
+    /// Unlike the standard capabilities, their names are stored in the entry, after their values.
+    fn parse_extended(
+        &mut self,
+        reader: &mut Reader<'_>,
+        number_size: usize,
+    ) -> Result<(), TermInfoError> {
+        let bools_count = reader.count()?;
+        let numbers_count = reader.count()?;
+        let strings_count = reader.count()?;
+        let _table_entries = reader.count()?;
+        let table_size = reader.count()?;
+
+        let bools = reader.take(bools_count)?.to_vec();
+        reader.align();
+
+        let numbers = (0..numbers_count)
+            .map(|_| reader.number(number_size))
+            .collect::<Result<Vec<_>, _>>()?;
+        let string_offsets = (0..strings_count)
+            .map(|_| reader.i16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let name_offsets = (0..bools_count + numbers_count + strings_count)
+            .map(|_| reader.i16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let table = reader.take(table_size)?;
+
+        // The names are stored after the values of the strings.
+        let mut values = vec![];
+        let mut names_start = 0;
+        for &offset in &string_offsets {
+            let value = string_at(table, offset)?;
+            if let Some(value) = value {
+                names_start = names_start.max(offset as usize + value.len() + 1);
+            }
+            values.push(value);
+        }
+
+        let names = name_offsets
+            .iter()
+            .map(|&offset| {
+                let names = table
+                    .get(names_start..)
+                    .ok_or(TermInfoError::Invalid("string table is too short"))?;
+                string_at(names, offset)?
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .ok_or(TermInfoError::Invalid("missing extended capability name"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (bool_names, names) = names.split_at(bools_count);
+        let (number_names, string_names) = names.split_at(numbers_count);
+
+        for (name, &value) in bool_names.iter().zip(&bools) {
+            if value == 1 {
+                self.bools.insert(name.clone(), true);
+            }
+        }
+
+        for (name, value) in number_names.iter().zip(numbers) {
+            if let Some(value) = value {
+                self.numbers.insert(name.clone(), value);
+            }
+        }
+
+        for (name, value) in string_names.iter().zip(values) {
+            if let Some(value) = value {
+                self.strings.insert(name.clone(), value.to_vec());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The names of the terminal. The last name is usually a description.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns whether a boolean capability is present.
+    pub fn flag(&self, name: &str) -> bool {
+        self.bools.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the value of a numeric capability.
+    pub fn number(&self, name: &str) -> Option<u32> {
+        self.numbers.get(name).cloned()
+    }
+
+    /// Returns the value of a string capability, which may contain parameters to be expanded with
+    /// `expand`.
+    pub fn string(&self, name: &str) -> Option<&[u8]> {
+        self.strings.get(name).map(Vec::as_slice)
+    }
+}
+
+/// The directories that are searched for terminfo entries, in the same order as ncurses.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+
+    if let Ok(terminfo_dirs) = env::var("TERMINFO_DIRS") {
+        // An empty entry stands for the default location.
+        dirs.extend(terminfo_dirs.split(':').map(|dir| match dir {
+            "" => PathBuf::from("/usr/share/terminfo"),
+            dir => PathBuf::from(dir),
+        }));
+    }
+
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .iter()
+            .map(PathBuf::from),
+    );
+
+    dirs
+}
+
+/// Returns the NUL-terminated string at an offset in a string table.
+///
+/// Negative offsets denote capabilities that are absent or cancelled.
+fn string_at(table: &[u8], offset: i16) -> Result<Option<&[u8]>, TermInfoError> {
+    if offset < 0 {
+        return Ok(None);
+    }
+
+    let rest = table
+        .get(offset as usize..)
+        .ok_or(TermInfoError::Invalid("string offset is out of bounds"))?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(TermInfoError::Invalid("string is missing a NUL terminator"))?;
+
+    Ok(Some(&rest[..len]))
+}
+
+/// Reads little-endian integers from a compiled entry.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], TermInfoError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or(TermInfoError::Invalid("unexpected end of entry"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, TermInfoError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from(bytes[0]) | u16::from(bytes[1]) << 8)
+    }
+
+    fn i16(&mut self) -> Result<i16, TermInfoError> {
+        self.u16().map(|n| n as i16)
+    }
+
+    /// Reads the size of a section. A size of -1 denotes an empty section.
+    fn count(&mut self) -> Result<usize, TermInfoError> {
+        match self.i16()? {
+            -1 => Ok(0),
+            n if n >= 0 => Ok(n as usize),
+            _ => Err(TermInfoError::Invalid("negative section size")),
+        }
+    }
+
+    /// Reads a numeric capability. Negative values denote capabilities that are absent.
+    fn number(&mut self, size: usize) -> Result<Option<u32>, TermInfoError> {
+        let n = if size == 4 {
+            let bytes = self.take(4)?;
+            (u32::from(bytes[0])
+                | u32::from(bytes[1]) << 8
+                | u32::from(bytes[2]) << 16
+                | u32::from(bytes[3]) << 24) as i32
+        } else {
+            i32::from(self.i16()?)
+        };
+
+        Ok(if n >= 0 { Some(n as u32) } else { None })
+    }
+
+    /// Skips the padding byte that aligns the next section to an even offset.
+    fn align(&mut self) {
+        if self.pos % 2 == 1 {
+            self.pos += 1;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+/// Expands the parameters of a string capability, such as the cursor position in `cup`.
+///
+/// This is a subset of `tparm` that only supports numeric parameters, which is enough for the
+/// capabilities used by the editor. Padding (such as `$<5>`) is removed, since modern terminals
+/// don't need it.
+pub fn expand(cap: &[u8], params: &[i32]) -> Vec<u8> {
+    let mut params = {
+        let mut array = [0; 9];
+        for (param, &value) in array.iter_mut().zip(params) {
+            *param = value;
+        }
+        array
+    };
+    let mut stack: Vec<i32> = vec![];
+    let mut dynamic_vars = [0; 26];
+    let mut static_vars = [0; 26];
+    let mut out = vec![];
+
+    let mut i = 0;
+    while i < cap.len() {
+        let c = cap[i];
+        i += 1;
+
+        if c == b'$' && cap.get(i) == Some(&b'<') {
+            match cap[i..].iter().position(|&b| b == b'>') {
+                Some(end) => i += end + 1,
+                None => out.push(c),
+            }
+            continue;
+        }
+
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+
+        let c = match cap.get(i) {
+            Some(&c) => c,
+            None => break,
+        };
+        i += 1;
+
+        match c {
+            b'%' => out.push(b'%'),
+            b'c' => out.push(stack.pop().unwrap_or_default() as u8),
+            b'p' => {
+                let idx = cap.get(i).map(|&b| b.wrapping_sub(b'1')).unwrap_or(9);
+                stack.push(params.get(usize::from(idx)).cloned().unwrap_or_default());
+                i += 1;
+            }
+            b'P' | b'g' => {
+                let var = match cap.get(i) {
+                    Some(&b @ b'a'..=b'z') => &mut dynamic_vars[usize::from(b - b'a')],
+                    Some(&b @ b'A'..=b'Z') => &mut static_vars[usize::from(b - b'A')],
+                    _ => break,
+                };
+                if c == b'P' {
+                    *var = stack.pop().unwrap_or_default();
+                } else {
+                    stack.push(*var);
+                }
+                i += 1;
+            }
+            b'\'' => {
+                stack.push(cap.get(i).cloned().map(i32::from).unwrap_or_default());
+                i += 2;
+            }
+            b'{' => {
+                let end = cap[i..]
+                    .iter()
+                    .position(|&b| b == b'}')
+                    .map_or(cap.len(), |end| i + end);
+                let n = String::from_utf8_lossy(&cap[i..end]).parse().unwrap_or(0);
+                stack.push(n);
+                i = end + 1;
+            }
+            b'l' => {
+                // String parameters aren't supported, so their length is always zero.
+                stack.pop();
+                stack.push(0);
+            }
+            b'+' | b'-' | b'*' | b'/' | b'm' | b'&' | b'|' | b'^' | b'=' | b'<' | b'>' | b'A'
+            | b'O' => {
+                let b = stack.pop().unwrap_or_default();
+                let a = stack.pop().unwrap_or_default();
+                stack.push(match c {
+                    b'+' => a.wrapping_add(b),
+                    b'-' => a.wrapping_sub(b),
+                    b'*' => a.wrapping_mul(b),
+                    b'/' => a.checked_div(b).unwrap_or(0),
+                    b'm' => a.checked_rem(b).unwrap_or(0),
+                    b'&' => a & b,
+                    b'|' => a | b,
+                    b'^' => a ^ b,
+                    b'=' => i32::from(a == b),
+                    b'<' => i32::from(a < b),
+                    b'>' => i32::from(a > b),
+                    b'A' => i32::from(a != 0 && b != 0),
+                    _ => i32::from(a != 0 || b != 0),
+                });
+            }
+            b'!' => {
+                let a = stack.pop().unwrap_or_default();
+                stack.push(i32::from(a == 0));
+            }
+            b'~' => {
+                let a = stack.pop().unwrap_or_default();
+                stack.push(!a);
+            }
+            b'i' => {
+                params[0] += 1;
+                params[1] += 1;
+            }
+            b'?' | b';' => (),
+            b't' => {
+                if stack.pop().unwrap_or_default() == 0 {
+                    i = skip_conditional(cap, i, true);
+                }
+            }
+            b'e' => i = skip_conditional(cap, i, false),
+            b':' | b'#' | b' ' | b'.' | b'0'..=b'9' | b'd' | b'o' | b'x' | b'X' | b's' => {
+                // A printf-style conversion, such as `%d` or `%:-3x`.
+                let start = i - 1;
+                let end = match cap[start..].iter().position(|b| b"doxXs".contains(b)) {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let n = stack.pop().unwrap_or_default();
+                out.extend(format_number(&cap[start..end], cap[end], n));
+                i = end + 1;
+            }
+            _ => warn!("unsupported terminfo operator: %{}", c as char),
+        }
+    }
+
+    out
+}
+
+/// Returns the index after the end of the current branch of a conditional.
+///
+/// If `else_branch` is true, skipping stops after the `%e` of the current conditional, if there
+/// is one. Otherwise, skipping stops after the `%;` that ends the conditional.
+fn skip_conditional(cap: &[u8], mut i: usize, else_branch: bool) -> usize {
+    let mut depth = 0;
+
+    while i + 1 < cap.len() {
+        if cap[i] != b'%' {
+            i += 1;
+            continue;
+        }
+
+        i += 2;
+        match cap[i - 1] {
+            b'?' => depth += 1,
+            b';' if depth == 0 => return i,
+            b';' => depth -= 1,
+            b'e' if depth == 0 && else_branch => return i,
+            _ => (),
+        }
+    }
+
+    cap.len()
+}
+
+/// Formats a number for a printf-style conversion with the given flags, width and precision.
+fn format_number(spec: &[u8], conversion: u8, n: i32) -> Vec<u8> {
+    let spec = String::from_utf8_lossy(spec);
+    let spec = spec.trim_start_matches(':');
+
+    let flags = spec
+        .chars()
+        .take_while(|c| "-+# 0".contains(*c))
+        .collect::<String>();
+    let mut parts = spec[flags.len()..].splitn(2, '.');
+    let width: usize = parts.next().and_then(|w| w.parse().ok()).unwrap_or(0);
+    let precision: usize = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+
+    let mut digits = match conversion {
+        b'o' => format!("{:o}", n),
+        b'x' => format!("{:x}", n),
+        b'X' => format!("{:X}", n),
+        _ => n.abs().to_string(),
+    };
+    if digits.len() < precision {
+        digits = format!("{}{}", "0".repeat(precision - digits.len()), digits);
+    }
+
+    let prefix = match conversion {
+        b'd' | b's' if n < 0 => "-",
+        b'd' | b's' if flags.contains('+') => "+",
+        b'd' | b's' if flags.contains(' ') => " ",
+        b'x' if flags.contains('#') && n != 0 => "0x",
+        b'X' if flags.contains('#') && n != 0 => "0X",
+        b'o' if flags.contains('#') && !digits.starts_with('0') => "0",
+        _ => "",
+    };
+
+    let len = prefix.len() + digits.len();
+    let padding = width.saturating_sub(len);
+    let formatted = if flags.contains('-') {
+        format!("{}{}{}", prefix, digits, " ".repeat(padding))
+    } else if flags.contains('0') {
+        format!("{}{}{}", prefix, "0".repeat(padding), digits)
+    } else {
+        format!("{}{}{}", " ".repeat(padding), prefix, digits)
+    };
+
+    formatted.into_bytes()
+}
+
+/// Compiles a terminfo entry in the legacy format, with extended capabilities if any of the
+/// capabilities are not standard.
+#[cfg(test)]
+pub fn compile(
+    names: &str,
+    bools: &[&str],
+    numbers: &[(&str, u16)],
+    strings: &[(&str, &str)],
+) -> Vec<u8> {
+    fn push_u16(out: &mut Vec<u8>, n: u16) {
+        out.extend(&[n as u8, (n >> 8) as u8]);
+    }
+
+    fn align(out: &mut Vec<u8>) {
+        if out.len() % 2 == 1 {
+            out.push(0);
+        }
+    }
+
+    let index = |table: &[&str], name: &str| table.iter().position(|&n| n == name);
+    let mut standard_bools = vec![0; BOOL_NAMES.len()];
+    let mut standard_numbers = vec![0xffff; NUMBER_NAMES.len()];
+    let mut standard_strings = vec![None; STRING_NAMES.len()];
+    let (mut extended_bools, mut extended_numbers, mut extended_strings) = (vec![], vec![], vec![]);
+
+    for &name in bools {
+        match index(BOOL_NAMES, name) {
+            Some(i) => standard_bools[i] = 1,
+            None => extended_bools.push(name),
+        }
+    }
+    for &(name, value) in numbers {
+        match index(NUMBER_NAMES, name) {
+            Some(i) => standard_numbers[i] = value,
+            None => extended_numbers.push((name, value)),
+        }
+    }
+    for &(name, value) in strings {
+        match index(STRING_NAMES, name) {
+            Some(i) => standard_strings[i] = Some(value),
+            None => extended_strings.push((name, value)),
+        }
+    }
+
+    let mut table = vec![];
+    let mut offsets = vec![];
+    for value in &standard_strings {
+        match value {
+            Some(value) => {
+                offsets.push(table.len() as u16);
+                table.extend(value.bytes());
+                table.push(0);
+            }
+            None => offsets.push(0xffff),
+        }
+    }
+
+    let mut out = vec![];
+    for &n in &[
+        MAGIC,
+        names.len() as u16 + 1,
+        BOOL_NAMES.len() as u16,
+        NUMBER_NAMES.len() as u16,
+        STRING_NAMES.len() as u16,
+        table.len() as u16,
+    ] {
+        push_u16(&mut out, n);
+    }
+    out.extend(names.bytes());
+    out.push(0);
+    out.extend(&standard_bools);
+    align(&mut out);
+    for &n in standard_numbers.iter().chain(&offsets) {
+        push_u16(&mut out, n);
+    }
+    out.extend(&table);
+
+    if extended_bools.is_empty() && extended_numbers.is_empty() && extended_strings.is_empty() {
+        return out;
+    }
+
+    let mut table = vec![];
+    let mut value_offsets = vec![];
+    for &(_, value) in &extended_strings {
+        value_offsets.push(table.len() as u16);
+        table.extend(value.bytes());
+        table.push(0);
+    }
+    let names_start = table.len();
+    let mut name_offsets = vec![];
+    let extended_names = extended_bools
+        .iter()
+        .chain(extended_numbers.iter().map(|(name, _)| name))
+        .chain(extended_strings.iter().map(|(name, _)| name));
+    for name in extended_names {
+        name_offsets.push((table.len() - names_start) as u16);
+        table.extend(name.bytes());
+        table.push(0);
+    }
+
+    align(&mut out);
+    for &n in &[
+        extended_bools.len() as u16,
+        extended_numbers.len() as u16,
+        extended_strings.len() as u16,
+        (extended_strings.len() + name_offsets.len()) as u16,
+        table.len() as u16,
+    ] {
+        push_u16(&mut out, n);
+    }
+    out.extend(extended_bools.iter().map(|_| 1));
+    align(&mut out);
+    let numbers = extended_numbers.iter().map(|&(_, value)| value);
+    for n in numbers.chain(value_offsets).chain(name_offsets) {
+        push_u16(&mut out, n);
+    }
+    out.extend(&table);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, expand, TermInfo};
+
+    #[test]
+    fn parse() {
+        let entry = compile(
+            "xterm-test|test terminal",
+            &["am", "Tc"],
+            &[("colors", 256), ("cols", 80)],
+            &[
+                ("cup", "\x1b[%i%p1%d;%p2%dH"),
+                ("el", "\x1b[K"),
+                ("Ss", "\x1b[%p1%d q"),
+                ("Se", "\x1b[2 q"),
+            ],
+        );
+        let info = TermInfo::parse(&entry).unwrap();
+
+        assert_eq!(info.names(), &["xterm-test", "test terminal"]);
+        assert!(info.flag("am"));
+        assert!(info.flag("Tc"));
+        assert!(!info.flag("bce"));
+        assert_eq!(info.number("colors"), Some(256));
+        assert_eq!(info.number("lines"), None);
+        assert_eq!(info.string("el"), Some(&b"\x1b[K"[..]));
+        assert_eq!(info.string("Ss"), Some(&b"\x1b[%p1%d q"[..]));
+        assert_eq!(info.string("Se"), Some(&b"\x1b[2 q"[..]));
+        assert_eq!(info.string("smcup"), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(TermInfo::parse(b"").is_err());
+        assert!(TermInfo::parse(b"\x1a\x02\x01").is_err());
+
+        let entry = compile("truncated", &[], &[], &[("el", "\x1b[K")]);
+        assert!(TermInfo::parse(&entry[..entry.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn expand_parameters() {
+        let cup = b"\x1b[%i%p1%d;%p2%dH";
+        assert_eq!(expand(cup, &[4, 9]), b"\x1b[5;10H");
+
+        // The `setaf` capability of xterm-256color.
+        let setaf = b"\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m";
+        assert_eq!(expand(setaf, &[1]), b"\x1b[31m");
+        assert_eq!(expand(setaf, &[9]), b"\x1b[91m");
+        assert_eq!(expand(setaf, &[100]), b"\x1b[38;5;100m");
+
+        assert_eq!(expand(b"%p1%c%'A'%c", &[0x40]), b"@A");
+        assert_eq!(expand(b"%p1%Pa%ga%ga%*%d", &[7]), b"49");
+        assert_eq!(expand(b"%p1%p2%>%!%d", &[1, 2]), b"1");
+        assert_eq!(expand(b"100%%", &[]), b"100%");
+    }
+
+    #[test]
+    fn expand_formats() {
+        assert_eq!(expand(b"%p1%03d", &[5]), b"005");
+        assert_eq!(expand(b"%p1%:-4d|", &[5]), b"5   |");
+        assert_eq!(expand(b"%p1%x/%p1%#X/%p1%o", &[255]), b"ff/0XFF/377");
+        assert_eq!(expand(b"%p1%.3d", &[-7]), b"-007");
+    }
+
+    #[test]
+    fn expand_padding() {
+        assert_eq!(expand(b"\x1b[H\x1b[2J$<50>", &[]), b"\x1b[H\x1b[2J");
+        assert_eq!(expand(b"\x1b[%p1%dM$<2*/>", &[3]), b"\x1b[3M");
+    }
+}
@@ -0,0 +1,76 @@
+//! Display widths of text in the terminal.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{fe0f}';
+
+fn is_regional_indicator(c: char) -> bool {
+    c >= '\u{1f1e6}' && c <= '\u{1f1ff}'
+}
+
+/// Returns the number of terminal columns occupied by a grapheme cluster.
+///
+/// Combining marks and other zero-width characters do not contribute to the width of the cluster.
+/// Emoji sequences (joined with ZWJ, using the emoji presentation selector, or flags) are displayed
+/// as a single double-width character. Every cluster occupies at least one column, so that control
+/// characters are still visible.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    let is_emoji_sequence = grapheme.contains(EMOJI_PRESENTATION_SELECTOR)
+        || grapheme
+            .chars()
+            .filter(|&c| is_regional_indicator(c))
+            .count()
+            == 2
+        || (grapheme.contains(ZERO_WIDTH_JOINER) && grapheme.chars().count() > 1);
+
+    if is_emoji_sequence {
+        return 2;
+    }
+
+    grapheme
+        .chars()
+        .map(|c| c.width().unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+        .max(1)
+        .min(2)
+}
+
+/// Returns the number of terminal columns occupied by a string.
+pub fn str_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grapheme_width, str_width};
+
+    #[test]
+    fn ascii() {
+        assert_eq!(grapheme_width("a"), 1);
+        assert_eq!(str_width("hello"), 5);
+    }
+
+    #[test]
+    fn wide() {
+        assert_eq!(grapheme_width("中"), 2);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(grapheme_width("😀"), 2);
+    }
+
+    #[test]
+    fn combining() {
+        assert_eq!(grapheme_width("e\u{301}"), 1);
+        assert_eq!(str_width("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn emoji_sequences() {
+        // Family: man, woman, girl, joined with ZWJ.
+        assert_eq!(grapheme_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(grapheme_width("❤\u{fe0f}"), 2);
+        assert_eq!(grapheme_width("🇯🇵"), 2);
+    }
+}